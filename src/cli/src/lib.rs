@@ -19,7 +19,7 @@ extern crate thiserror;
 
 use log::{info, error};
 
-use pyo3::prelude::*;
+use pyo3::{exceptions::PySystemExit, prelude::*};
 
 use std::{
     fs::File,
@@ -34,7 +34,7 @@ use comfy_table::{
 use itertools::Itertools;
 use rust_fuzzy_search::fuzzy_compare;
 use tempfile::NamedTempFile;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
 use colored::*;
 
@@ -45,10 +45,10 @@ use crate::error::Error;
 
 use plotter::Theme;
 
-use sexp::{el, SexpParser, SexpTree, State};
+use sexp::{el, SexpParser, State};
 
-use reports::{bom, drc, erc, mouser};
-use simulation::{Circuit, Netlist};
+use reports::{bom, drc, erc, mouser, position, report::Report, variant::Variant};
+use simulation::{Circuit, Project};
 
 
 mod constant {
@@ -71,11 +71,28 @@ pub fn check_directory(path: &Path) -> Result<(), Error> {
     Ok(())
 }
 
-///load a sexp file and return a SexpTree
-fn load_sexp(input: &str) -> Result<SexpTree, Error> {
-    let doc = SexpParser::load(input)?;
-    let tree = SexpTree::from(doc.iter())?;
-    Ok(tree)
+///Write the report of a check in the selected format.
+fn write_report(report: &Report, output: &Path, format: ReportFormat) -> Result<(), Error> {
+    let content = match format {
+        ReportFormat::Json => report.to_json(),
+        ReportFormat::Junit => report.to_junit(),
+        ReportFormat::Sarif => report.to_sarif(),
+    };
+    check_directory(output)?;
+    let mut out = File::create(output)?;
+    out.write_all(content.as_bytes())?;
+    out.flush()?;
+    Ok(())
+}
+
+///Load the assembly variant from the project file next to the input.
+fn load_variant(input: &Path, name: &str) -> Result<Variant, Error> {
+    let project = input.with_extension("kicad_pro");
+    if project.exists() {
+        Ok(Variant::load(&project, name)?)
+    } else {
+        Ok(Variant::new(name))
+    }
 }
 
 fn absolute_path(path: &Path) -> String {
@@ -190,11 +207,18 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// create a BOM from a kicad schematic.
+    /// create a BOM from a kicad schematic, the grouping and the columns
+    /// are loaded from the kicad project file.
     Bom {
-        /// input kicad schema file.
-        #[arg(short, long)]
-        input: PathBuf,
+        /// input kicad schema files, the parts of several boards are combined.
+        /// the settings are loaded from the project of the first file and apply
+        /// to all boards, the variant is loaded from the project of each board.
+        #[arg(short, long, required = true)]
+        input: Vec<PathBuf>,
+        /// the number of each board in the panel or kit, in the order of the
+        /// input files. the boards without a count are used once.
+        #[arg(long, value_delimiter = ',')]
+        count: Vec<usize>,
         /// output file, this can be a json or excel file.
         #[arg(short, long)]
         output: Option<PathBuf>,
@@ -205,6 +229,24 @@ enum Commands {
         /// content.
         #[arg(short, long)]
         partlist: Option<PathBuf>,
+        /// write the parts not found in the partlist to a report file.
+        #[arg(long)]
+        report: Option<PathBuf>,
+        /// the report format.
+        #[arg(short, long, value_enum, default_value_t=ReportFormat::Json)]
+        format: ReportFormat,
+        /// the output columns, symbol fields like `MPN` or `${QUANTITY}`.
+        #[arg(long, value_delimiter = ',')]
+        columns: Option<Vec<String>>,
+        /// the fields for grouping the items.
+        #[arg(long, value_delimiter = ',')]
+        group_by: Option<Vec<String>>,
+        /// skip the parts that are not populated.
+        #[arg(long)]
+        exclude_dnp: bool,
+        /// the assembly variant, from the project file or the `Variant.<name>` fields.
+        #[arg(long)]
+        variant: Option<String>,
     },
     /// convet a notebook
     Convert {
@@ -215,7 +257,8 @@ enum Commands {
         #[arg(short, long)]
         output: PathBuf,
     },
-    /// run the drc checks on a kicad pcb.
+    /// run the drc checks on a kicad pcb, the design rules are loaded
+    /// from the kicad project file.
     Drc {
         /// input file
         #[arg(short, long)]
@@ -223,8 +266,19 @@ enum Commands {
         /// ouptut file
         #[arg(short, long)]
         output: Option<PathBuf>,
+        /// run the drc with kicad-cli
+        #[arg(long)]
+        kicad: bool,
+        /// compare the pcb with the schematic
+        #[arg(long)]
+        parity: bool,
+        /// the format of the output file.
+        #[arg(short, long, value_enum, default_value_t=ReportFormat::Json)]
+        format: ReportFormat,
     },
-    /// run the erc checks on a kicad schematic.
+    /// run the erc checks on a kicad schematic, the rule severities and
+    /// exclusions are loaded from the kicad project file. exits with an
+    /// error code when errors are found.
     Erc {
         /// input file
         #[arg(short, long)]
@@ -232,6 +286,21 @@ enum Commands {
         /// ouptut file
         #[arg(short, long)]
         output: Option<PathBuf>,
+        /// the format of the output file.
+        #[arg(short, long, value_enum, default_value_t=ReportFormat::Json)]
+        format: ReportFormat,
+    },
+    /// export the netlist of a kicad schematic.
+    Netlist {
+        /// input kicad schema file.
+        #[arg(short, long)]
+        input: PathBuf,
+        /// output file, the netlist is printed when not set.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// the netlist format.
+        #[arg(short, long, value_enum, default_value_t=NetlistFormat::Kicad)]
+        format: NetlistFormat,
     },
     /// plot a schematic or pcb from kicad file.
     Plot {
@@ -259,6 +328,21 @@ enum Commands {
         /// Output the PCB layers to seperate files.
         #[arg(long)]
         split: bool,
+        /// the assembly variant, the parts that are not populated are crossed out.
+        #[arg(long)]
+        variant: Option<String>,
+    },
+    /// write the pick and place file for a kicad pcb.
+    Pos {
+        /// input file
+        #[arg(short, long)]
+        input: PathBuf,
+        /// ouptut file, the positions are printed when not set.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// the assembly variant, from the project file or the `Variant.<name>` fields.
+        #[arg(long)]
+        variant: Option<String>,
     },
     /// search for a symbol in the kicad library.
     Search {
@@ -283,6 +367,30 @@ enum Commands {
     },
 }
 
+///The netlist export formats.
+#[derive(Clone, Debug, Default, ValueEnum)]
+enum NetlistFormat {
+    ///KiCad s-expression netlist.
+    #[default]
+    Kicad,
+    ///JSON graph of the nets.
+    Json,
+    ///Graphviz DOT graph.
+    Dot,
+}
+
+///The report formats of the checks.
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+enum ReportFormat {
+    ///JSON with the elektron report schema.
+    #[default]
+    Json,
+    ///JUnit XML.
+    Junit,
+    ///SARIF 2.1.0.
+    Sarif,
+}
+
 enum FileExtension {
     Schema,
     Pcb,
@@ -319,10 +427,62 @@ pub fn main() -> PyResult<()> {
     args.remove(0);
     let cli = Cli::parse_from(args);
 
+    //the exit status is raised as SystemExit, the python process is not terminated.
+    let mut status = Ok(());
     if let Err(error) = match cli.command {
-        Some(Commands::Bom { input, output, group, partlist }) => {
-            let tree = load_sexp(input.to_str().unwrap())?;
-            let results = bom::bom(&tree, group, partlist).unwrap();
+        Some(Commands::Bom { input, count, output, group, partlist, report, format, columns, group_by, exclude_dnp, variant }) => {
+            if count.len() > input.len() {
+                return Err(Error::Reports(format!(
+                    "{} {} board counts for {} input files",
+                    "Error:".red(),
+                    count.len(),
+                    input.len()
+                )).into());
+            }
+            let projects = input
+                .iter()
+                .map(|input| Project::load(input))
+                .collect::<Result<Vec<Project>, _>>()?;
+            //the settings of the first project apply to all boards.
+            let project = input[0].with_extension("kicad_pro");
+            let mut settings = if project.exists() {
+                bom::BomSettings::load(&project)?
+            } else {
+                bom::BomSettings::default()
+            };
+            settings.group |= group;
+            settings.exclude_dnp |= exclude_dnp;
+            if let Some(columns) = columns {
+                settings = settings.with_columns(&columns);
+            }
+            if let Some(group_by) = group_by {
+                settings.group_by = group_by;
+            }
+            let results = if let ([project], []) = (projects.as_slice(), count.as_slice()) {
+                if let Some(variant) = variant {
+                    settings.variant = Some(load_variant(&input[0], &variant)?);
+                }
+                bom::bom_project(project, &settings, partlist)?
+            } else {
+                let mut boards: Vec<bom::BomBoard> = Vec::new();
+                for (index, (input, project)) in input.iter().zip(&projects).enumerate() {
+                    boards.push(bom::BomBoard {
+                        name: input.file_stem().unwrap().to_str().unwrap().to_string(),
+                        project,
+                        count: count.get(index).copied().unwrap_or(1),
+                        //every board has its own variant.
+                        variant: match &variant {
+                            Some(variant) => Some(load_variant(input, variant)?),
+                            None => None,
+                        },
+                    });
+                }
+                bom::bom_boards(&boards, &settings, partlist)?
+            };
+            if let Some(report) = report {
+                let missing = results.1.as_deref().unwrap_or_default();
+                write_report(&Report::bom(&input[0], missing), &report, format)?;
+            }
 
             if let Some(output) = output {
                 let ext = output.extension();
@@ -339,15 +499,11 @@ pub fn main() -> PyResult<()> {
                         if ext == constant::EXT_JSON {
                             let mut data = json::JsonValue::new_array();
                             for item in &results.0 {
-                                data.push(json::object! {
-                                    amount: item.amount,
-                                    reference: item.references.clone(),
-                                    value: item.value.clone(),
-                                    footprint: item.footprint.clone(),
-                                    datasheet: item.datasheet.clone(),
-                                    description: item.description.clone()
-                                })
-                                .unwrap();
+                                let mut row = json::JsonValue::new_object();
+                                for column in &settings.columns {
+                                    row[column.label.as_str()] = item.field(&column.name).into();
+                                }
+                                data.push(row).unwrap();
                             }
                             if let Err(err) = check_directory(&output) {
                                 return Err(Error::FileIo(format!(
@@ -401,33 +557,26 @@ pub fn main() -> PyResult<()> {
                     .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
                     .set_content_arrangement(ContentArrangement::Dynamic)
                     // .set_width(40)
-                    .set_header(vec![
-                        "#",
-                        "Ref",
-                        "Value",
-                        "Footprint",
-                        "Datasheet",
-                        "Description",
-                    ]);
+                    .set_header(settings.columns.iter().map(|c| c.label.clone()));
 
                 results.0.iter().for_each(|item| {
-                    table.add_row(vec![
-                        Cell::new(item.amount),
-                        Cell::new(item.references.join(" ")),
-                        Cell::new(item.value.clone()),
-                        Cell::new(item.footprint.clone()),
-                        Cell::new(item.datasheet.clone()),
-                        Cell::new(item.description.clone()),
-                    ]);
+                    table.add_row(
+                        settings.columns.iter().map(|c| Cell::new(item.field(&c.name))),
+                    );
                 });
 
                 println!("{table}");
             }
             Ok(())
         },
-        Some(Commands::Drc { input, output }) => {
+        Some(Commands::Drc { input, output, kicad, parity, format }) => {
             info!("Write DRC: input:{}, output:{:?}", input.to_str().unwrap(), output);
-            let results = match drc::drc(input.to_str().unwrap()) {
+            let results = if kicad {
+                drc::kicad_drc(input.to_str().unwrap())
+            } else {
+                drc::drc(input.to_str().unwrap())
+            };
+            let mut results = match results {
                 Ok(result) => result,
                 Err(error) => {
                     return Err(Error::FileIo(format!(
@@ -438,30 +587,22 @@ pub fn main() -> PyResult<()> {
                     )).into());
                 }
             };
-            if let Some(output) = output {
-                let mut data = json::JsonValue::new_array();
-                for item in &results.errors {
-                    let mut pos = json::array![];
-                    for item in &item.items {
-                        pos.push(json::object! {
-                            description: item.description.clone(),
-                            pos: format!("{}x{}", item.pos.0, item.pos.1),
-                            uuid: item.uuid.clone(),
-                        }).unwrap();
+            if parity {
+                let schema = input.with_extension("kicad_sch");
+                match drc::parity(&schema, input.to_str().unwrap()) {
+                    Ok(items) => results.errors.extend(items),
+                    Err(error) => {
+                        return Err(Error::FileIo(format!(
+                            "{} can not compare the pcb with the schematic: {} ({}))",
+                            "Error".red(),
+                            schema.to_str().unwrap(),
+                            error
+                        )).into());
                     }
-                    data.push(json::object! {
-                        type: item.drc_type.clone(),
-                        severity: item.severity.clone(),
-                        description: item.description.clone(),
-                        items: pos,
-                    })
-                    .unwrap();
                 }
-                check_directory(&output)?;
-                let mut out = File::create(output)?;
-                data.write(&mut out)?;
-                out.flush()?;
-
+            }
+            if let Some(output) = output {
+                write_report(&Report::drc(&results), &output, format)?;
             } else {
 
                 println!("DRC: {:?}", results);
@@ -485,7 +626,7 @@ pub fn main() -> PyResult<()> {
             }
             Ok(())
         },
-        Some(Commands::Erc { input, output }) => {
+        Some(Commands::Erc { input, output, format }) => {
             info!("Write ERC: input:{}, output:{:?}", input.to_str().unwrap(), output);
             let Ok(results) = erc::erc(&input) else {
                 return Err(Error::FileIo(format!(
@@ -495,48 +636,70 @@ pub fn main() -> PyResult<()> {
                 )).into());
             };
             if let Some(output) = output {
-                let mut data = json::JsonValue::new_array();
-                for item in &results {
-                    data.push(json::object! {
-                        id: String::from("NoReference"),
-                        reference: item.reference.to_string(),
-                        position: item.at.to_string(),
-                    })
-                    .unwrap();
-                }
-                check_directory(&output)?;
-                let mut out = File::create(output)?;
-                data.write(&mut out)?;
-                out.flush()?;
+                write_report(&Report::erc(&input, &results), &output, format)?;
             } else {
                 let mut table = Table::new();
                 table
                     .load_preset(UTF8_FULL)
                     .apply_modifier(UTF8_ROUND_CORNERS)
                     .set_content_arrangement(ContentArrangement::Dynamic)
-                    .set_header(vec!["ID", "Reference", "Position"]);
+                    .set_header(vec!["ID", "Severity", "Reference", "Description", "Position"]);
 
                 results.iter().for_each(|item| {
                     table.add_row(vec![
                         Cell::new(item.id.clone()),
+                        Cell::new(item.severity),
                         Cell::new(item.reference.clone()),
+                        Cell::new(item.description.clone()),
                         Cell::new(item.at.to_string()),
                     ]);
                 });
 
                 println!("{table}");
             }
+            let errors = results
+                .iter()
+                .filter(|item| item.severity == erc::Severity::Error)
+                .count();
+            if errors > 0 {
+                error!("ERC found {} errors", errors);
+                status = Err(PySystemExit::new_err(1));
+            }
+            Ok(())
+        },
+        Some(Commands::Netlist { input, output, format }) => {
+            let project = Project::load(&input)?;
+            let connectivity = project.connectivity()?;
+            let content = match format {
+                NetlistFormat::Kicad => connectivity.to_kicad()?,
+                NetlistFormat::Json => connectivity.to_json(),
+                NetlistFormat::Dot => connectivity.to_dot(),
+            };
+            if let Some(output) = output {
+                check_directory(&output)?;
+                let mut out = File::create(output)?;
+                out.write_all(content.as_bytes())?;
+                out.flush()?;
+            } else {
+                print!("{}", content);
+            }
             Ok(())
         },
-        Some(Commands::Plot { input, output, border, theme, scale, pages, layers, split}) => {
+        Some(Commands::Plot { input, output, border, theme, scale, pages, layers, split, variant}) => {
             match FileExtension::from(&input) {
                 Ok(FileExtension::Schema) => {
+                    let dnp = if let Some(variant) = variant {
+                        Some(load_variant(&input, &variant)?.dnp_references(&Project::load(&input)?)?)
+                    } else {
+                        None
+                    };
                     Ok(plotter::Schema::new(&input)
                         .border(border)
                         .theme(theme)
                         .scale(scale)
                         .pages(pages)
                         .split(split)
+                        .dnp(dnp)
                         .plot(&output)?)
                 },
                 Ok(FileExtension::Pcb) => {
@@ -554,6 +717,25 @@ pub fn main() -> PyResult<()> {
                 }
             }
         },
+        Some(Commands::Pos { input, output, variant }) => {
+            info!("Write pick and place: input:{}, output:{:?}", input.to_str().unwrap(), output);
+            let variant = if let Some(variant) = variant {
+                Some(load_variant(&input, &variant)?)
+            } else {
+                None
+            };
+            let positions = position::positions(input.to_str().unwrap(), variant.as_ref())?;
+            let csv = position::to_csv(&positions);
+            if let Some(output) = output {
+                check_directory(&output)?;
+                let mut out = File::create(&output)?;
+                out.write_all(csv.as_bytes())?;
+                out.flush()?;
+            } else {
+                print!("{}", csv);
+            }
+            Ok(())
+        },
         Some(Commands::Convert { input, output }) => {
             info!("Write notebook: input:{}, output:{:?}", input.to_str().unwrap(), output);
 
@@ -600,14 +782,13 @@ pub fn main() -> PyResult<()> {
             search(&term, path)
         },
         Some(Commands::Spice { input, output, path }) => {
-            let tree = load_sexp(input.to_str().unwrap())?;
-            let netlist = Netlist::from(&tree);
-            if let Ok(netlist) = netlist {
+            let project = Project::load(&input);
+            if let Ok(project) = project {
                 let path =
                     path.iter().map(|p| p.to_str().unwrap().to_string()).collect::<Vec<String>>();
 
                 let mut circuit = Circuit::new(input.to_str().unwrap().to_string(), path);
-                netlist.circuit(&mut circuit).unwrap();
+                project.circuit(&mut circuit)?;
                 let output = output.map(|o|
                     o.to_str().unwrap().to_string());
                 circuit.save(output).unwrap();
@@ -625,7 +806,7 @@ pub fn main() -> PyResult<()> {
         error!("{}", error);
     }
 
-    Ok(())
+    status
 }
 
 /// A Python module implemented in Rust.
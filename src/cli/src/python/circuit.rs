@@ -1,9 +1,12 @@
 #![allow(clippy::borrow_deref_ref)]
-use std::collections::HashMap;
+use std::{collections::HashMap, fs::File, path::Path};
 
 use pyo3::{exceptions::PyOSError, prelude::*};
+use simulation::Backend;
 use simulation::Circuit as SpiceCircuit;
+use simulation::CodeModel;
 use simulation::Simulation as SpiceSimulation;
+use simulation::{read_rawfile, write_csv, write_parquet, write_rawfile, write_wav};
 
 use crate::error::Error;
 
@@ -22,24 +25,118 @@ impl Circuit {
         }
     }
 
+    /// parse a spice netlist.
+    #[staticmethod]
+    pub fn parse(content: &str, pathlist: Vec<String>) -> Result<Self, Error> {
+        Ok(Self {
+            circuit: SpiceCircuit::parse(content, pathlist)?,
+        })
+    }
+
+    /// load a spice netlist file, the includes are relative to the file.
+    #[staticmethod]
+    pub fn load(path: &str, pathlist: Vec<String>) -> Result<Self, Error> {
+        Ok(Self {
+            circuit: SpiceCircuit::load(Path::new(path), pathlist)?,
+        })
+    }
+
     fn __str__(&self) -> String {
         self.circuit.to_str(true).unwrap().join("\n")
     }
 
-    pub fn resistor(&mut self, reference: String, n0: String, n1: String, value: String) {
-        self.circuit.resistor(reference, n0, n1, value);
+    /// add an element by the spice primitive, like `element("E", "E1", ["1", "0", "2", "0"], "10")`.
+    pub fn element(
+        &mut self,
+        primitive: &str,
+        reference: String,
+        nodes: Vec<String>,
+        value: String,
+    ) -> Result<(), Error> {
+        self.circuit.element(primitive, reference, nodes, value)?;
+        Ok(())
+    }
+
+    pub fn resistor(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        value: String,
+    ) -> Result<(), Error> {
+        self.circuit.resistor(reference, n0, n1, value)?;
+        Ok(())
+    }
+
+    pub fn capacitor(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        value: String,
+    ) -> Result<(), Error> {
+        self.circuit.capacitor(reference, n0, n1, value)?;
+        Ok(())
     }
 
-    pub fn capacitor(&mut self, reference: String, n0: String, n1: String, value: String) {
-        self.circuit.capacitor(reference, n0, n1, value);
+    pub fn inductor(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        value: String,
+    ) -> Result<(), Error> {
+        self.circuit.inductor(reference, n0, n1, value)?;
+        Ok(())
     }
 
-    pub fn diode(&mut self, reference: String, n0: String, n1: String, value: String) {
-        self.circuit.diode(reference, n0, n1, value);
+    pub fn diode(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        value: String,
+    ) -> Result<(), Error> {
+        self.circuit.diode(reference, n0, n1, value)?;
+        Ok(())
     }
 
-    pub fn bjt(&mut self, reference: String, n0: String, n1: String, n2: String, value: String) {
-        self.circuit.bjt(reference, n0, n1, n2, value);
+    pub fn bjt(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        n2: String,
+        value: String,
+    ) -> Result<(), Error> {
+        self.circuit.bjt(reference, n0, n1, n2, value)?;
+        Ok(())
+    }
+
+    pub fn jfet(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        n2: String,
+        value: String,
+    ) -> Result<(), Error> {
+        self.circuit.jfet(reference, n0, n1, n2, value)?;
+        Ok(())
+    }
+
+    /// add a mosfet, the nodes are drain, gate, source and bulk.
+    pub fn mosfet(
+        &mut self,
+        reference: String,
+        nd: String,
+        ng: String,
+        ns: String,
+        nb: String,
+        value: String,
+    ) -> Result<(), Error> {
+        self.circuit.mosfet(reference, nd, ng, ns, nb, value)?;
+        Ok(())
     }
 
     pub fn circuit(
@@ -48,20 +145,223 @@ impl Circuit {
         n: Vec<String>,
         value: String,
     ) -> Result<(), Error> {
-        self.circuit.circuit(reference, n, value).unwrap();
+        self.circuit.circuit(reference, n, value)?;
         Ok(())
     }
+    /// add a subcircuit, the params are the defaults for the instances like `{"rval": "1k"}`.
+    #[pyo3(signature = (name, n, circuit, params=None))]
     pub fn subcircuit(
         &mut self,
         name: String,
         n: Vec<String>,
         circuit: Circuit,
+        params: Option<HashMap<String, String>>,
+    ) -> Result<(), Error> {
+        let mut params: Vec<(String, String)> = params.unwrap_or_default().into_iter().collect();
+        params.sort();
+        self.circuit
+            .parametric_subcircuit(name, n, params, circuit.circuit)?;
+        Ok(())
+    }
+    /// add a `.param` statement, the parameter is used in values like `{rval}`.
+    pub fn param(&mut self, name: String, value: String) -> Result<(), Error> {
+        self.circuit.param(name, value)?;
+        Ok(())
+    }
+    /// add a `.func` statement, like `func("db", ["x"], "{20*log10(x)}")`.
+    pub fn func(
+        &mut self,
+        name: String,
+        args: Vec<String>,
+        expression: String,
+    ) -> Result<(), Error> {
+        self.circuit.func(name, args, expression)?;
+        Ok(())
+    }
+    /// include a library file, with the section the file is loaded with `.lib`.
+    #[pyo3(signature = (path, section=None))]
+    pub fn include(&mut self, path: String, section: Option<String>) {
+        self.circuit.include(path, section);
+    }
+    pub fn model(&mut self, name: String, definition: String) {
+        self.circuit.model(name, definition);
+    }
+    pub fn voltage(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        value: String,
+    ) -> Result<(), Error> {
+        self.circuit.voltage(reference, n1, n2, value)?;
+        Ok(())
+    }
+    pub fn current(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        value: String,
+    ) -> Result<(), Error> {
+        self.circuit.current(reference, n1, n2, value)?;
+        Ok(())
+    }
+    pub fn vcvs(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        nc1: String,
+        nc2: String,
+        gain: String,
+    ) -> Result<(), Error> {
+        self.circuit.vcvs(reference, n1, n2, nc1, nc2, gain)?;
+        Ok(())
+    }
+    pub fn vccs(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        nc1: String,
+        nc2: String,
+        transconductance: String,
+    ) -> Result<(), Error> {
+        self.circuit
+            .vccs(reference, n1, n2, nc1, nc2, transconductance)?;
+        Ok(())
+    }
+    pub fn cccs(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        source: String,
+        gain: String,
+    ) -> Result<(), Error> {
+        self.circuit.cccs(reference, n1, n2, source, gain)?;
+        Ok(())
+    }
+    pub fn ccvs(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        source: String,
+        transresistance: String,
+    ) -> Result<(), Error> {
+        self.circuit
+            .ccvs(reference, n1, n2, source, transresistance)?;
+        Ok(())
+    }
+    pub fn coupling(
+        &mut self,
+        reference: String,
+        l1: String,
+        l2: String,
+        coupling: String,
+    ) -> Result<(), Error> {
+        self.circuit.coupling(reference, l1, l2, coupling)?;
+        Ok(())
+    }
+    pub fn switch(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        nc1: String,
+        nc2: String,
+        model: String,
+    ) -> Result<(), Error> {
+        self.circuit.switch(reference, n1, n2, nc1, nc2, model)?;
+        Ok(())
+    }
+    pub fn current_switch(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        source: String,
+        model: String,
+    ) -> Result<(), Error> {
+        self.circuit
+            .current_switch(reference, n1, n2, source, model)?;
+        Ok(())
+    }
+    pub fn transmission_line(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        n3: String,
+        n4: String,
+        value: String,
+    ) -> Result<(), Error> {
+        self.circuit
+            .transmission_line(reference, n1, n2, n3, n4, value)?;
+        Ok(())
+    }
+    pub fn behavioral(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        expression: String,
+    ) -> Result<(), Error> {
+        self.circuit.behavioral(reference, n1, n2, expression)?;
+        Ok(())
+    }
+    /// add a XSPICE code model element, vector ports are in brackets like `[a b]`.
+    pub fn xspice(
+        &mut self,
+        reference: String,
+        ports: Vec<String>,
+        model: String,
     ) -> Result<(), Error> {
-        self.circuit.subcircuit(name, n, circuit.circuit).unwrap();
+        self.circuit.xspice(reference, ports, model)?;
         Ok(())
     }
-    pub fn voltage(&mut self, reference: String, n1: String, n2: String, value: String) {
-        self.circuit.voltage(reference, n1, n2, value);
+    /// add a digital gate.
+    pub fn gate(
+        &mut self,
+        reference: String,
+        inputs: Vec<String>,
+        output: String,
+        model: String,
+    ) -> Result<(), Error> {
+        self.circuit.gate(reference, inputs, output, model)?;
+        Ok(())
+    }
+    /// add an `adc_bridge` or `dac_bridge`.
+    pub fn bridge(
+        &mut self,
+        reference: String,
+        inputs: Vec<String>,
+        outputs: Vec<String>,
+        model: String,
+    ) -> Result<(), Error> {
+        self.circuit.bridge(reference, inputs, outputs, model)?;
+        Ok(())
+    }
+    /// add the `.model` of a XSPICE code model, like `code_model("nand1", "d_nand", {"rise_delay": "1n"})`.
+    #[pyo3(signature = (name, model, params=None))]
+    pub fn code_model(
+        &mut self,
+        name: String,
+        model: &str,
+        params: Option<HashMap<String, String>>,
+    ) -> Result<(), Error> {
+        let Some(kind) = CodeModel::from_name(model) else {
+            return Err(simulation::Error::InvalidValue(name, model.to_string()).into());
+        };
+        let mut params: Vec<(String, String)> = params.unwrap_or_default().into_iter().collect();
+        params.sort();
+        self.circuit.code_model(name, kind, params)?;
+        Ok(())
+    }
+    /// the digital nodes of the XSPICE elements.
+    pub fn digital_nodes(&self) -> Vec<String> {
+        self.circuit.digital_nodes()
     }
     pub fn option(&mut self, option: String, value: String) {
         self.circuit.option(option, value);
@@ -73,8 +373,9 @@ impl Circuit {
         self.circuit.save(filename).unwrap();
         Ok(())
     }
+    /// change the value of an element, a `.param` or an instance parameter like `X1.rval`.
     pub fn set_value(&mut self, reference: &str, value: &str) -> Result<(), Error> {
-        self.circuit.set_value(reference, value).unwrap();
+        self.circuit.set_value(reference, value)?;
         Ok(())
     }
 }
@@ -87,11 +388,21 @@ pub struct Simulation {
 /// simulate the circuit with ngspice
 #[pymethods]
 impl Simulation {
+    /// create a new simulation, the backend is either `shared` (default)
+    /// or `batch` to run ngspice in a separate process.
     #[new]
-    pub fn new(circuit: Circuit) -> Self {
-        Self {
-            simulation: SpiceSimulation::new(circuit.circuit),
-        }
+    #[pyo3(signature = (circuit, backend=None))]
+    pub fn new(circuit: Circuit, backend: Option<&str>) -> PyResult<Self> {
+        let backend = match backend {
+            None | Some("shared") => Backend::Shared,
+            Some("batch") => Backend::batch(),
+            Some(backend) => {
+                return Err(PyOSError::new_err(format!("unknown backend: {}", backend)))
+            }
+        };
+        Ok(Self {
+            simulation: SpiceSimulation::new(circuit.circuit).backend(backend),
+        })
     }
 
     pub fn run(&self) -> PyResult<HashMap<String, HashMap<String, Vec<f64>>>> {
@@ -101,6 +412,47 @@ impl Simulation {
         }
     }
 
+    /// run the commands in the background.
+    pub fn bg_run(&mut self) -> PyResult<()> {
+        match self.simulation.bg_run() {
+            Ok(()) => Ok(()),
+            Err(err) => Err(PyOSError::new_err(err.to_string())),
+        }
+    }
+
+    /// stop the background simulation.
+    pub fn bg_halt(&self) {
+        self.simulation.bg_halt();
+    }
+
+    /// true while the background simulation is running.
+    pub fn is_running(&self) -> bool {
+        self.simulation.is_running()
+    }
+
+    /// the progress of the background simulation as (status, percent, plot).
+    pub fn progress(&self) -> Option<(String, f64, String)> {
+        self.simulation
+            .progress()
+            .map(|p| (p.status, p.percent, p.plot))
+    }
+
+    /// the vectors of the background simulation received so far.
+    pub fn vectors(&self) -> HashMap<String, Vec<f64>> {
+        self.simulation
+            .progress()
+            .map(|p| p.vectors.into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// wait for the background simulation and return the results.
+    pub fn bg_wait(&mut self, py: Python) -> PyResult<HashMap<String, HashMap<String, Vec<f64>>>> {
+        match py.allow_threads(|| self.simulation.bg_wait()) {
+            Ok(res) => Ok(res),
+            Err(err) => Err(PyOSError::new_err(err.to_string())),
+        }
+    }
+
     pub fn op(&mut self, py: Python) -> PyResult<HashMap<String, Vec<f64>>> {
         let res = self.simulation.op();
         if let Ok(res) = res {
@@ -196,4 +548,51 @@ impl Simulation {
             Err(PyOSError::new_err(String::from("unknown error")))
         }
     }
+
+    /// write the results to a ngspice rawfile, ascii or binary.
+    #[staticmethod]
+    #[pyo3(signature = (path, plots, binary=true))]
+    pub fn write_rawfile(
+        path: &str,
+        plots: HashMap<String, HashMap<String, Vec<f64>>>,
+        binary: bool,
+    ) -> Result<(), Error> {
+        let mut file = File::create(path)?;
+        write_rawfile(&mut file, &plots, binary)?;
+        Ok(())
+    }
+
+    /// read the results from a ngspice rawfile.
+    #[staticmethod]
+    pub fn read_rawfile(path: &str) -> Result<HashMap<String, HashMap<String, Vec<f64>>>, Error> {
+        Ok(read_rawfile(&std::fs::read(path)?)?)
+    }
+
+    /// write the vectors of a plot to a csv file.
+    #[staticmethod]
+    pub fn write_csv(path: &str, vectors: HashMap<String, Vec<f64>>) -> Result<(), Error> {
+        let mut file = File::create(path)?;
+        write_csv(&mut file, &vectors)?;
+        Ok(())
+    }
+
+    /// write the vectors of a plot to a parquet file.
+    #[staticmethod]
+    pub fn write_parquet(path: &str, vectors: HashMap<String, Vec<f64>>) -> Result<(), Error> {
+        write_parquet(File::create(path)?, &vectors)?;
+        Ok(())
+    }
+
+    /// write a transient result as wav file, resampled to the sample rate.
+    #[staticmethod]
+    #[pyo3(signature = (path, time, values, sample_rate=44100))]
+    pub fn write_wav(
+        path: &str,
+        time: Vec<f64>,
+        values: Vec<f64>,
+        sample_rate: u32,
+    ) -> Result<(), Error> {
+        write_wav(Path::new(path), &time, &values, sample_rate)?;
+        Ok(())
+    }
 }
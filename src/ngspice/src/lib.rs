@@ -69,6 +69,8 @@ pub enum NgSpiceError {
     TryFromIntFailed,
     #[error("Spice Error: {0}\n{1}")]
     Spice(i32, String),
+    #[error("ngspice has exited.")]
+    Exited,
 }
 
 ///The main struct to use ngspice.
@@ -85,6 +87,45 @@ pub enum ComplexSlice<'a> {
     Complex(&'a [ngcomplex]),
 }
 
+///Value of a vector for one simulation point, sent from the background thread.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VecValue {
+    pub name: String,
+    pub real: f64,
+    pub imag: f64,
+    pub is_scale: bool,
+    pub is_complex: bool,
+}
+
+///Vector description, sent before the simulation starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VecInit {
+    pub number: i32,
+    pub name: String,
+    pub is_real: bool,
+}
+
+///Plot description, sent before the simulation starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlotInit {
+    pub name: String,
+    pub title: String,
+    pub date: String,
+    pub plot_type: String,
+    pub vectors: Vec<VecInit>,
+}
+
+///Value of an event node at a simulation step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvtValue {
+    ///The simulation time.
+    pub step: f64,
+    ///true for the value of the operating point.
+    pub dcop: bool,
+    ///The node value, like `0s` or `Uz` for digital nodes.
+    pub value: String,
+}
+
 ///result vector info.
 #[derive(Debug)]
 pub struct VectorInfo<'a> {
@@ -121,6 +162,97 @@ unsafe extern "C" fn send_char<C: Callbacks>(
     0
 }
 
+unsafe extern "C" fn send_stat<C: Callbacks>(
+    arg1: *mut c_char,
+    _arg2: c_int,
+    context: *mut c_void,
+) -> c_int {
+    let spice = &mut *(context as *mut NgSpice<C>);
+    let cb = &mut spice.callbacks;
+    let str_res = CStr::from_ptr(arg1).to_str();
+    if let Ok(s) = str_res {
+        cb.send_stat(s);
+    }
+    0
+}
+
+///convert a nullable c string.
+unsafe fn to_string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(ptr).to_string_lossy().to_string()
+    }
+}
+
+unsafe extern "C" fn send_data<C: Callbacks>(
+    values: pvecvaluesall,
+    count: c_int,
+    _id: c_int,
+    context: *mut c_void,
+) -> c_int {
+    let spice = &mut *(context as *mut NgSpice<C>);
+    let cb = &mut spice.callbacks;
+    if values.is_null() {
+        return 0;
+    }
+    let values = &*values;
+    let mut result = Vec::new();
+    for i in 0..values.veccount as isize {
+        let value = &**values.vecsa.offset(i);
+        result.push(VecValue {
+            name: to_string(value.name),
+            real: value.creal,
+            imag: value.cimag,
+            is_scale: value.is_scale,
+            is_complex: value.is_complex,
+        });
+    }
+    cb.send_data(result, count);
+    0
+}
+
+unsafe extern "C" fn send_init_data<C: Callbacks>(
+    init: pvecinfoall,
+    _id: c_int,
+    context: *mut c_void,
+) -> c_int {
+    let spice = &mut *(context as *mut NgSpice<C>);
+    let cb = &mut spice.callbacks;
+    if init.is_null() {
+        return 0;
+    }
+    let init = &*init;
+    let mut vectors = Vec::new();
+    for i in 0..init.veccount as isize {
+        let vec = &**init.vecs.offset(i);
+        vectors.push(VecInit {
+            number: vec.number,
+            name: to_string(vec.vecname),
+            is_real: vec.is_real,
+        });
+    }
+    cb.send_init_data(PlotInit {
+        name: to_string(init.name),
+        title: to_string(init.title),
+        date: to_string(init.date),
+        plot_type: to_string(init.type_),
+        vectors,
+    });
+    0
+}
+
+unsafe extern "C" fn bg_thread_running<C: Callbacks>(
+    not_running: bool,
+    _id: c_int,
+    context: *mut c_void,
+) -> c_int {
+    let spice = &mut *(context as *mut NgSpice<C>);
+    let cb = &mut spice.callbacks;
+    cb.bg_thread_running(!not_running);
+    0
+}
+
 unsafe extern "C" fn controlled_exit<C: Callbacks>(
     status: c_int,
     unload: bool,
@@ -213,11 +345,11 @@ impl<'a, C: Callbacks> NgSpice<'a, C> {
             let rawptr = std::sync::Arc::as_ptr(&ptr);
             ptr.ngspice.ngSpice_Init(
                 Some(send_char::<C>),
-                None,
+                Some(send_stat::<C>),
                 Some(controlled_exit::<C>),
-                None,
-                None,
-                None,
+                Some(send_data::<C>),
+                Some(send_init_data::<C>),
+                Some(bg_thread_running::<C>),
                 rawptr as _,
             );
             Ok(ptr)
@@ -229,10 +361,15 @@ impl<'a, C: Callbacks> NgSpice<'a, C> {
         self.exited
     }
 
+    ///true if the background thread is running.
+    pub fn is_running(&self) -> bool {
+        unsafe { self.ngspice.ngSpice_running() }
+    }
+
     ///send a command to ngspice.
     pub fn command(&self, s: &str) -> Result<(), NgSpiceError> {
         if self.exited {
-            panic!("NgSpice exited")
+            return Err(NgSpiceError::Exited);
         }
         let cs = CString::new(s)?;
         let raw = cs.into_raw();
@@ -242,7 +379,6 @@ impl<'a, C: Callbacks> NgSpice<'a, C> {
             if ret == 0 {
                 Ok(())
             } else {
-                println!("Command Error: {}", ret);
                 Err(ret.into())
             }
         }
@@ -346,6 +482,51 @@ impl<'a, C: Callbacks> NgSpice<'a, C> {
             }
         }
     }
+
+    ///get the names of the event nodes, empty when ngspice is built without XSPICE.
+    pub fn all_evt_nodes(&self) -> Result<Vec<String>, NgSpiceError> {
+        if self.ngspice.ngSpice_AllEvtNodes.is_err() {
+            return Ok(Vec::new());
+        }
+        unsafe {
+            let ptrs = self.ngspice.ngSpice_AllEvtNodes();
+            let mut strs: Vec<String> = Vec::new();
+            if ptrs.is_null() {
+                return Ok(strs);
+            }
+            let mut i = 0;
+            while !(*ptrs.offset(i)).is_null() {
+                let ptr = CStr::from_ptr(*ptrs.offset(i)).to_str()?;
+                strs.push(String::from(ptr));
+                i += 1;
+            }
+            Ok(strs)
+        }
+    }
+
+    ///get the values of an event node.
+    pub fn evt_node_info(&self, node: &str) -> Result<Vec<EvtValue>, NgSpiceError> {
+        let cs = CString::new(node)?;
+        let raw = cs.into_raw();
+        unsafe {
+            let info = self.ngspice.ngGet_Evt_NodeInfo(raw);
+            drop(CString::from_raw(raw));
+            if info.is_null() {
+                return Err(NgSpiceError::NoResults);
+            }
+            let len: usize = (*info).num_steps.try_into()?;
+            let mut values = Vec::with_capacity(len);
+            for i in 0..len {
+                let data = *(*info).evt_dect.add(i);
+                values.push(EvtValue {
+                    step: (*data).step,
+                    dcop: (*data).dcop != 0,
+                    value: CStr::from_ptr((*data).node_value).to_str()?.to_string(),
+                });
+            }
+            Ok(values)
+        }
+    }
 }
 
 trait Simulator<'a, C: Callbacks> {
@@ -372,9 +553,21 @@ impl<'a, C: Callbacks> Simulator<'a, C> for std::sync::Arc<NgSpice<'a, C>> {
 }
 
 ///type for the ngspice callback.
+///
+///`send_stat`, `send_data`, `send_init_data` and `bg_thread_running` are
+///called from the ngspice background thread when a command is prefixed
+///with `bg_`.
 pub trait Callbacks {
     fn send_char(&mut self, _s: &str) {}
     fn controlled_exit(&mut self, _status: i32, _unload: bool, _quit: bool) {}
+    ///simulation status, like `tran: 45.3%`.
+    fn send_stat(&mut self, _s: &str) {}
+    ///the values of all vectors for a simulation point.
+    fn send_data(&mut self, _values: Vec<VecValue>, _count: i32) {}
+    ///the vectors of a new plot.
+    fn send_init_data(&mut self, _plot: PlotInit) {}
+    ///the background thread is started or stopped.
+    fn bg_thread_running(&mut self, _running: bool) {}
 }
 
 // TODO test fail on github
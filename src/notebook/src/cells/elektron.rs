@@ -5,10 +5,13 @@ use std::path::{Path, PathBuf};
 use crate::{error::NotebookError, notebook::ArgType, utils::check_directory};
 
 use pyo3::Bound;
-use reports::{bom::BomItem, drc, erc};
+use reports::{
+    bom::{BomBoard, BomItem, BomSettings},
+    drc, erc,
+};
 
 use plotter::{gerber, schema::SchemaPlot, svg::SvgPlotter};
-use sexp::{SexpParser, SexpTree};
+use simulation::Project;
 
 use super::super::cells::{CellWrite, CellWriter};
 use super::param_or;
@@ -109,50 +112,80 @@ fn bom(out: &mut dyn std::io::Write,
             None,
         ));
     };
+    let error = |message: String| {
+        NotebookError::new(
+            source.to_string(),
+            String::from("ElektronCell"),
+            String::from("IoError"),
+            message,
+            cell.0,
+            cell.0,
+            None,
+        )
+    };
+    //the number of each board in the panel or kit, in the order of the input.
+    let count = match args.get("count") {
+        Some(ArgType::List(count)) => count.clone(),
+        Some(ArgType::String(count)) => vec![count.clone()],
+        _ => Vec::new(),
+    };
+    let count = count
+        .iter()
+        .map(|c| c.parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|err| error(format!("invalid board count: {}", err)))?;
+    let settings = BomSettings {
+        group,
+        ..Default::default()
+    };
     let mut missing: Vec<BomItem> = Vec::new();
+    let mut projects: Vec<(String, Project)> = Vec::new();
     writeln!(out, "bom:").unwrap();
     for input in input {
         let input_file = Path::new(&Path::new(source).parent().unwrap())
             .join(input)
             .join(format!("{}.kicad_sch", input));
 
-        let doc = SexpParser::load(input_file.to_str().unwrap()).unwrap();
-        let tree = SexpTree::from(doc.iter()).unwrap();
-        let res = reports::bom::bom(&tree, group, partlist.clone());
-
-        match res {
-            Ok(res) => {
-                if let Some(mut m) = res.1 {
-                    missing.append(&mut m);
-                }
-                //output the bom as frontmatter
-                writeln!(out, "  {}:", input).unwrap();
-                for item in res.0 {
-                    writeln!(out, "    -").unwrap();
-                    writeln!(out, "       amount: {}", item.amount).unwrap();
-                    writeln!(out, "       value: {}", item.value).unwrap();
-                    writeln!(out, "       references: {}", item.references.join(" "))
-                        .unwrap();
-                    writeln!(out, "       description: {}", item.description).unwrap();
-                    writeln!(out, "       footprint: {}", item.footprint).unwrap();
-                }
-            }
-            Err(err) => {
-                return Err(NotebookError::new(
-                    source.to_string(),
-                    String::from("ElektronCell"),
-                    String::from("IoError"),
-                    format!("can not create bom: {:?}", err),
-                    cell.0,
-                    cell.0,
-                    None,
-                ));
-            }
+        let project = Project::load(&input_file)
+            .map_err(|err| error(format!("can not load schema: {:?}", err)))?;
+        let res = reports::bom::bom_project(&project, &settings, partlist.clone())
+            .map_err(|err| error(format!("can not create bom: {:?}", err)))?;
+        if let Some(mut m) = res.1 {
+            missing.append(&mut m);
         }
+        //output the bom as frontmatter
+        writeln!(out, "  {}:", input).unwrap();
+        bom_items(out, &res.0);
+        projects.push((input.to_string(), project));
+    }
+    //the parts of all boards for a panel or kit
+    if projects.len() > 1 {
+        let boards: Vec<BomBoard> = projects
+            .iter()
+            .enumerate()
+            .map(|(index, (name, project))| BomBoard {
+                name: name.to_string(),
+                project,
+                count: count.get(index).copied().unwrap_or(1),
+                variant: None,
+            })
+            .collect();
+        let res = reports::bom::bom_boards(&boards, &settings, partlist.clone())
+            .map_err(|err| error(format!("can not create bom: {:?}", err)))?;
+        writeln!(out, "bom_total:").unwrap();
+        writeln!(out, "  items:").unwrap();
+        bom_items(out, &res.0);
     }
     writeln!(out, "bom_missing:").unwrap();
     writeln!(out, "  items:").unwrap();
-    for item in &missing {
+    bom_items(out, &missing);
+    writeln!(out, "  count: {}", missing.len()).unwrap();
+    Ok(())
+}
+
+///Write the BOM items as frontmatter.
+fn bom_items(out: &mut dyn std::io::Write, items: &[BomItem]) {
+    for item in items {
         writeln!(out, "    -").unwrap();
         writeln!(out, "       amount: {}", item.amount).unwrap();
         writeln!(out, "       value: {}", item.value).unwrap();
@@ -160,8 +193,6 @@ fn bom(out: &mut dyn std::io::Write,
         writeln!(out, "       description: {}", item.description).unwrap();
         writeln!(out, "       footprint: {}", item.footprint).unwrap();
     }
-    writeln!(out, "  count: {}", missing.len()).unwrap();
-    Ok(())
 }
 
 fn drc(out: &mut dyn std::io::Write,
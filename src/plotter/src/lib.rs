@@ -73,6 +73,7 @@ pub struct Schema<'a> {
     my_scale: f64,
     my_pages: Option<Vec<usize>>,
     my_split: bool,
+    my_dnp: Option<Vec<String>>,
 }
 
 impl<'a> Schema<'a> {
@@ -84,6 +85,7 @@ impl<'a> Schema<'a> {
             my_scale: 1.0,
             my_pages: None,
             my_split: false,
+            my_dnp: None,
         }
     }
     pub fn input(mut self, input: &'a Path) -> Self {
@@ -110,6 +112,11 @@ impl<'a> Schema<'a> {
         self.my_split = split;
         self
     }
+    ///Cross out the symbols with the references, like for an assembly variant.
+    pub fn dnp(mut self, references: Option<Vec<String>>) -> Self {
+        self.my_dnp = references;
+        self
+    }
     pub fn plot(self, output: &Path) -> Result<(), Error> {
         info!(
             "Write schema: input:{:?}, output:{:?}, border: {} theme: {:?}",
@@ -126,6 +133,9 @@ impl<'a> Schema<'a> {
                 if let Some(pages) = self.my_pages {
                     plotter = plotter.pages(pages);
                 }
+                if let Some(dnp) = self.my_dnp {
+                    plotter = plotter.dnp(dnp);
+                }
 
                 plotter.open(self.my_input)?;
                 for page in plotter.iter() {
@@ -611,6 +621,7 @@ pub enum Style {
     Junction,
     NoConnect,
     NotFound,
+    Dnp,
     Outline,
     PinDecoration,
     Pin,
@@ -710,6 +721,7 @@ impl fmt::Display for Style {
             Style::Junction => write!(f, "schema-junction"),
             Style::NoConnect => write!(f, "no-connect"),
             Style::NotFound => write!(f, "not-found"),
+            Style::Dnp => write!(f, "schema-dnp"),
             Style::Outline => write!(f, "schema-outline"),
             Style::Pin => write!(f, "schema-pin"),
             Style::Polyline => write!(f, "schema-polyline"),
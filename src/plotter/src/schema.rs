@@ -28,6 +28,7 @@ const PIN_NUMER_OFFSET: f64 = 0.6;
 
 pub struct SchemaPlot<'a> {
     schema_pages: HashMap<usize, String>,
+    schema_paths: HashMap<usize, Vec<String>>,
     pages: Option<Vec<usize>>,
     theme: Themer<'a>,
     border: bool,
@@ -36,6 +37,7 @@ pub struct SchemaPlot<'a> {
     name: String,
     path: String,
     tree: Option<SexpTree>,
+    dnp: Option<Vec<String>>,
 }
 
 impl Outline for SchemaPlot<'_> {}
@@ -79,10 +81,17 @@ impl<'a> SchemaPlot<'a> {
         self.name = name.to_string();
         self
     }
+    /// The references of the symbols that are not populated, they are crossed out.
+    /// Without the references the symbols with the `dnp` attribute or a `DNP` property are crossed out.
+    pub fn dnp(mut self, references: Vec<String>) -> Self {
+        self.dnp = Some(references);
+        self
+    }
     /// create a new SchemaPlot with defalt values.
     pub fn new() -> Self {
         Self {
             schema_pages: HashMap::new(),
+            schema_paths: HashMap::new(),
             pages: None,
             theme: Themer::new(Theme::Kicad2020),
             border: true,
@@ -91,6 +100,7 @@ impl<'a> SchemaPlot<'a> {
             name: String::from("none"),
             path: String::new(),
             tree: None,
+            dnp: None,
         }
     }
 
@@ -115,6 +125,8 @@ impl<'a> SchemaPlot<'a> {
             let sheetpath = project.query("path").next().unwrap();
             let number: usize = sheetpath.value("page").unwrap();
             self.schema_pages.insert(number, path);
+            let uuid: Option<String> = page.value("uuid");
+            self.schema_paths.insert(number, uuid.into_iter().collect());
         }
         trace!("schema_pages: {:#?}", self.schema_pages);
         self.tree = Some(tree);
@@ -165,7 +177,7 @@ impl<'a> SchemaPlot<'a> {
 
         //TODO handle portraint and landscape
 
-        let mut plot_items = self.parse_items(&tree, netlist);
+        let mut plot_items = self.parse_items(&tree, netlist, page);
         let size = if self.border {
             arr2(&[[0.0, 0.0], [paper_size.0, paper_size.1]])
         } else {
@@ -204,6 +216,7 @@ impl<'a> SchemaPlot<'a> {
         &self,
         document: &SexpTree,
         netlist: Option<Netlist>,
+        page: &usize,
     ) -> Vec<PlotItem> {
 
         //plot the border
@@ -237,7 +250,7 @@ impl<'a> SchemaPlot<'a> {
                 el::RECTANGLE => self.plot(RectangleElement { item }, &mut plot_items),
                 el::SHEET => self.plot(SheetElement { item }, &mut plot_items),
                 el::SHEET_PIN => self.plot(SheetPinElement { item }, &mut plot_items),
-                el::SYMBOL => self.plot(SymbolElement { item, document, netlist: &netlist }, &mut plot_items),
+                el::SYMBOL => self.plot(SymbolElement { item, document, netlist: &netlist, page }, &mut plot_items),
                 el::WIRE => self.plot(WireElement{ item }, &mut plot_items),
                 el::TEXT => self.plot(TextElement{ item }, &mut plot_items),
                 el::TEXT_BOX => self.plot(TextBoxElement{ item }, &mut plot_items),
@@ -262,6 +275,42 @@ impl<'a> SchemaPlot<'a> {
         }
         plot_items
     }
+
+    /// The reference of the symbol instance on the page, falls back to the Reference property.
+    fn reference(&self, page: &usize, symbol: &Sexp) -> Option<String> {
+        let Some(root) = self.tree.as_ref().and_then(|tree| tree.root().ok()) else {
+            return symbol.property(el::PROPERTY_REFERENCE);
+        };
+        let sheets = self.schema_paths.get(page).cloned().unwrap_or_default();
+        let find = |instances: &Sexp, path: &str| -> Option<String> {
+            instances
+                .query("path")
+                .find(|p| SexpValueQuery::<String>::get(*p, 0).as_deref() == Some(path))
+                .and_then(|p| p.query("reference").next())
+                .and_then(|r| r.get(0))
+        };
+        //KiCad 7 stores the instances in the symbol
+        let root_uuid: Option<String> = root.value("uuid");
+        if let Some(root_uuid) = root_uuid {
+            let path = format!("/{}", std::iter::once(root_uuid).chain(sheets.iter().cloned()).collect::<Vec<String>>().join("/"));
+            for instances in symbol.query("instances") {
+                for project in instances.query("project") {
+                    if let Some(reference) = find(project, &path) {
+                        return Some(reference);
+                    }
+                }
+            }
+        }
+        //KiCad 6 stores the instances in the root schema
+        let symbol_uuid: Option<String> = symbol.value("uuid");
+        if let (Some(instances), Some(symbol_uuid)) = (root.query("symbol_instances").next(), symbol_uuid) {
+            let path = format!("/{}", sheets.iter().cloned().chain(std::iter::once(symbol_uuid)).collect::<Vec<String>>().join("/"));
+            if let Some(reference) = find(instances, &path) {
+                return Some(reference);
+            }
+        }
+        symbol.property(el::PROPERTY_REFERENCE)
+    }
 }
 
 trait PlotElement<T> {
@@ -842,6 +891,7 @@ struct SymbolElement<'a> {
     item: &'a Sexp,
     document: &'a SexpTree,
     netlist: &'a Option<Netlist<'a>>,
+    page: &'a usize,
 }
 
 impl<'a> PlotElement<SymbolElement<'a>> for SchemaPlot<'a> {
@@ -910,6 +960,7 @@ impl<'a> PlotElement<SymbolElement<'a>> for SchemaPlot<'a> {
             let lib_id: String = item.item.value(el::LIB_ID).unwrap();
             let item_unit: usize = item.item.value(el::SYMBOL_UNIT).unwrap();
             if let Some(lib) = utils::get_library(item.document.root().unwrap(), &lib_id) {
+                let start = plot_items.len();
                 for _unit in lib.query(el::SYMBOL) {
                     let unit: usize = utils::unit_number(_unit.get(0).unwrap());
                     if unit == 0 || unit == item_unit {
@@ -1064,6 +1115,30 @@ impl<'a> PlotElement<SymbolElement<'a>> for SchemaPlot<'a> {
                         }
                     }
                 }
+
+                //cross out the symbol when it is not populated
+                let dnp: bool = if let Some(references) = &self.dnp {
+                    self.reference(item.page, item.item).is_some_and(|r| references.contains(&r))
+                } else {
+                    utils::is_dnp(item.item)
+                };
+                if dnp && plot_items.len() > start {
+                    let bounds = self.bounds(&plot_items[start..]);
+                    for pts in [
+                        arr2(&[[bounds[[0, 0]], bounds[[0, 1]]], [bounds[[1, 0]], bounds[[1, 1]]]]),
+                        arr2(&[[bounds[[0, 0]], bounds[[1, 1]]], [bounds[[1, 0]], bounds[[0, 1]]]]),
+                    ] {
+                        plot_items.push(PlotItem::Line(
+                            30,
+                            Line::new(
+                                pts,
+                                self.theme.get_stroke(Stroke::new(), &[Style::Dnp]),
+                                Some(LineCap::Round),
+                                None,
+                            ),
+                        ));
+                    }
+                }
             } else {
                 let pts = arr2(&[[0.0, 0.0], [10.0, 10.0]]);
                 plot_items.push(PlotItem::Rectangle(
@@ -1351,3 +1426,44 @@ impl<'a> PlotElement<PinElement<'a>> for SchemaPlot<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use sexp::{SexpParser, SexpTree};
+
+    use super::SchemaPlot;
+
+    fn plot(content: &str, dnp: Option<Vec<String>>) -> usize {
+        let sexp = SexpParser::from(content.to_string());
+        let tree = SexpTree::from(sexp.iter()).unwrap();
+        let mut plotter = SchemaPlot::new().border(false);
+        if let Some(dnp) = dnp {
+            plotter = plotter.dnp(dnp);
+        }
+        plotter.open_buffer(tree.clone());
+        plotter.parse_items(&tree, None, &1).len()
+    }
+
+    // cross out the symbols by the instance reference
+    #[test]
+    fn test_dnp_instance_reference() {
+        let content = std::fs::read_to_string("tests/dco.kicad_sch")
+            .unwrap()
+            .replace("(reference \"R7\")", "(reference \"R70\")");
+        let items = plot(&content, Some(Vec::new()));
+        assert_eq!(items + 2, plot(&content, Some(vec![String::from("R70")])));
+        assert_eq!(items, plot(&content, Some(vec![String::from("R7")])));
+    }
+
+    // cross out the symbols with the DNP property
+    #[test]
+    fn test_dnp_property() {
+        let content = std::fs::read_to_string("tests/dco.kicad_sch").unwrap();
+        let items = plot(&content, None);
+        let content = content.replace(
+            "(property \"Reference\" \"R7\" (at 41.75 -11.34 -270)",
+            "(property \"DNP\" \"yes\" (at 0 0 0)\n      (effects (font (size 1.27 1.27)) hide)\n    )\n    (property \"Reference\" \"R7\" (at 41.75 -11.34 -270)",
+        );
+        assert_eq!(items + 2, plot(&content, None));
+    }
+}
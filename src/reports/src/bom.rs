@@ -1,5 +1,19 @@
 //! Create a BOM for the Schema.
 //!
+//! The items are grouped by the `group_by` fields and the output columns
+//! are selected with the [`BomSettings`]. Besides the fields of the
+//! [`BomItem`], every symbol property can be used as a column, like `MPN`
+//! or `Manufacturer`. The settings are loaded from the `bom_settings` of the
+//! KiCad project file, the special fields `${QUANTITY}` and `${DNP}` are the
+//! amount and the do not populate flag.
+//!
+//! The BOM of a hierarchical project is created with [`bom_project`], the
+//! BOM of a panel or kit with several boards with [`bom_boards`].
+//!
+//! With an assembly [`Variant`] in the settings the parts that are not
+//! populated in the variant are skipped and the substituted fields are
+//! replaced.
+//!
 //! # Example:
 //!
 //! use elektron::sexp::Schema;
@@ -9,13 +23,30 @@
 //! let result = bom(&schema, true, Some(String::from("files/partlist.yaml"))).unwrap();
 //! println!("Items not found {:#?}", result.1);
 //!
-use crate::Error;
-use sexp::{el, SexpProperty, SexpTree, SexpValueQuery};
-use std::{collections::HashMap, path::PathBuf};
+use crate::{
+    variant::{Assembly, Variant},
+    Error,
+};
+use sexp::{el, utils, Sexp, SexpProperty, SexpTree, SexpValueQuery};
+use simulation::Project;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
 use yaml_rust::{Yaml, YamlLoader};
 
+///The field name of the amount.
+pub const QUANTITY: &str = "${QUANTITY}";
+///The field name of the do not populate flag.
+pub const DNP: &str = "${DNP}";
+
+const FOOTPRINT: &str = "Footprint";
+const DATASHEET: &str = "Datasheet";
+const DESCRIPTION: &str = "Description";
+
 /// BOM Item
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct BomItem {
     pub amount: usize,
     pub references: Vec<String>,
@@ -24,6 +55,162 @@ pub struct BomItem {
     pub datasheet: String,
     pub description: String,
     pub mouser_nr: String,
+    ///The part is not populated.
+    pub dnp: bool,
+    ///The other properties of the symbol by name.
+    pub fields: HashMap<String, String>,
+}
+
+impl BomItem {
+    ///Get the value of a field by name, like `Value` or `MPN`.
+    pub fn field(&self, name: &str) -> String {
+        match name {
+            QUANTITY => self.amount.to_string(),
+            DNP => {
+                if self.dnp {
+                    String::from("DNP")
+                } else {
+                    String::new()
+                }
+            }
+            el::PROPERTY_REFERENCE => self.references.join(" "),
+            el::PROPERTY_VALUE => self.value.clone(),
+            FOOTPRINT => self.footprint.clone(),
+            DATASHEET => self.datasheet.clone(),
+            DESCRIPTION => self.description.clone(),
+            _ => self.fields.get(name).cloned().unwrap_or_default(),
+        }
+    }
+
+    ///Set the value of a field by name.
+    pub fn set_field(&mut self, name: &str, value: &str) {
+        match name {
+            el::PROPERTY_VALUE => self.value = value.to_string(),
+            FOOTPRINT => self.footprint = value.to_string(),
+            DATASHEET => self.datasheet = value.to_string(),
+            DESCRIPTION => self.description = value.to_string(),
+            _ => {
+                self.fields.insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+}
+
+///A column of the BOM output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BomColumn {
+    ///The field name.
+    pub name: String,
+    ///The column header.
+    pub label: String,
+}
+
+impl BomColumn {
+    ///Create the column, the label is the field name.
+    pub fn new(name: &str) -> Self {
+        let label = match name {
+            QUANTITY => "Qty",
+            DNP => "DNP",
+            _ => name,
+        };
+        Self {
+            name: name.to_string(),
+            label: label.to_string(),
+        }
+    }
+}
+
+///The BOM settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BomSettings {
+    ///Group the items with equal `group_by` fields.
+    pub group: bool,
+    ///The fields for grouping the items.
+    pub group_by: Vec<String>,
+    ///The output columns in order.
+    pub columns: Vec<BomColumn>,
+    ///Skip the parts that are not populated.
+    pub exclude_dnp: bool,
+    ///The assembly variant, the parts that are not populated are skipped.
+    pub variant: Option<Variant>,
+}
+
+impl Default for BomSettings {
+    fn default() -> Self {
+        Self {
+            group: false,
+            group_by: vec![el::PROPERTY_VALUE.to_string(), FOOTPRINT.to_string()],
+            columns: [
+                QUANTITY,
+                el::PROPERTY_REFERENCE,
+                el::PROPERTY_VALUE,
+                FOOTPRINT,
+                DATASHEET,
+                DESCRIPTION,
+            ]
+            .iter()
+            .map(|name| BomColumn::new(name))
+            .collect(),
+            exclude_dnp: false,
+            variant: None,
+        }
+    }
+}
+
+impl BomSettings {
+    ///Load the settings from the `bom_settings` of a KiCad project file.
+    ///
+    ///The defaults are used when the project has no BOM settings.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let error = |msg: String| Error::ProjectError(path.display().to_string(), msg);
+        let content = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let project = json::parse(&content).map_err(|e| error(e.to_string()))?;
+        let bom = &project["schematic"]["bom_settings"];
+        let mut settings = Self::default();
+        if bom.is_null() {
+            return Ok(settings);
+        }
+        settings.group = bom["group_symbols"].as_bool().unwrap_or(settings.group);
+        settings.exclude_dnp = bom["exclude_dnp"].as_bool().unwrap_or(false);
+        if bom["fields_ordered"].is_array() {
+            settings.group_by.clear();
+            settings.columns.clear();
+            for field in bom["fields_ordered"].members() {
+                let Some(name) = field["name"].as_str() else {
+                    return Err(error(String::from("field without name")));
+                };
+                if field["group_by"].as_bool().unwrap_or(false) {
+                    settings.group_by.push(name.to_string());
+                }
+                if field["show"].as_bool().unwrap_or(true) {
+                    let mut column = BomColumn::new(name);
+                    if let Some(label) = field["label"].as_str() {
+                        column.label = label.to_string();
+                    }
+                    settings.columns.push(column);
+                }
+            }
+        }
+        Ok(settings)
+    }
+
+    ///Select the output columns by field name.
+    pub fn with_columns(mut self, names: &[String]) -> Self {
+        self.columns = names.iter().map(|name| BomColumn::new(name)).collect();
+        self
+    }
+
+    ///The group key of the item, the populated and the not populated parts
+    ///are never grouped together.
+    fn key(&self, item: &BomItem) -> String {
+        self.group_by
+            .iter()
+            .filter(|name| *name != el::PROPERTY_REFERENCE && *name != QUANTITY)
+            .map(|name| item.field(name))
+            .chain(std::iter::once(item.field(DNP)))
+            .collect::<Vec<String>>()
+            .join(":")
+    }
 }
 
 impl From<Yaml> for BomItem {
@@ -68,6 +255,8 @@ impl From<Yaml> for BomItem {
             datasheet,
             description,
             mouser_nr,
+            dnp: false,
+            fields: HashMap::new(),
         }
     }
 }
@@ -141,9 +330,33 @@ pub fn merge_item(item: &BomItem, part: Option<&BomItem>) -> BomItem {
         datasheet,
         description,
         mouser_nr,
+        dnp: item.dnp,
+        fields: item.fields.clone(),
     }
 }
 
+///The properties of the symbol without the fields of the BomItem.
+fn fields(symbol: &Sexp) -> HashMap<String, String> {
+    symbol
+        .query(el::PROPERTY)
+        .filter_map(|property| {
+            let name: String = property.get(0)?;
+            let value: String = property.get(1)?;
+            Some((name, value))
+        })
+        .filter(|(name, _)| {
+            ![
+                el::PROPERTY_REFERENCE,
+                el::PROPERTY_VALUE,
+                FOOTPRINT,
+                DATASHEET,
+                DESCRIPTION,
+            ]
+            .contains(&name.as_str())
+        })
+        .collect()
+}
+
 /// Create the BOM for a Schema.
 ///
 /// # Arguments
@@ -158,7 +371,179 @@ pub fn bom(
     group: bool,
     partlist: Option<PathBuf>,
 ) -> Result<(Vec<BomItem>, Option<Vec<BomItem>>), Error> {
+    let settings = BomSettings {
+        group,
+        ..Default::default()
+    };
+    bom_with(document, &settings, partlist)
+}
 
+/// Create the BOM for a Schema with the settings.
+///
+/// # Arguments
+///
+/// * `document` - A Schema struct.
+/// * `settings` - The grouping and the filter of the items.
+/// * `partlist` - A YAML file with the parts description.
+/// * `return`   - Tuple with a Vec<BomItem> and the missing items.
+pub fn bom_with(
+    document: &SexpTree,
+    settings: &BomSettings,
+    partlist: Option<PathBuf>,
+) -> Result<(Vec<BomItem>, Option<Vec<BomItem>>), Error> {
+    let mut bom_items: Vec<BomItem> = Vec::new();
+    for symbol in document.root()?.query(el::SYMBOL) {
+        if let Some(reference) = symbol.property(el::PROPERTY_REFERENCE) {
+            bom_items.extend(bom_item(symbol, reference, settings));
+        }
+    }
+    collect(bom_items, settings, partlist)
+}
+
+/// Create the BOM for a hierarchical project.
+///
+/// The symbols of all sheets are collected, a sheet that is used more
+/// than once adds the parts for every instance with the instance
+/// references.
+///
+/// # Arguments
+///
+/// * `project`  - The loaded project.
+/// * `settings` - The grouping and the filter of the items.
+/// * `partlist` - A YAML file with the parts description.
+/// * `return`   - Tuple with a Vec<BomItem> and the missing items.
+pub fn bom_project(
+    project: &Project,
+    settings: &BomSettings,
+    partlist: Option<PathBuf>,
+) -> Result<(Vec<BomItem>, Option<Vec<BomItem>>), Error> {
+    collect(project_items(project, settings, "", 1)?, settings, partlist)
+}
+
+///A board of a panel or kit BOM.
+pub struct BomBoard<'a> {
+    ///The name of the board, used as prefix of the references.
+    pub name: String,
+    pub project: &'a Project,
+    ///The number of boards in the panel or kit.
+    pub count: usize,
+    ///The assembly variant of the board.
+    pub variant: Option<Variant>,
+}
+
+/// Create the BOM for several boards.
+///
+/// The references are prefixed with the board name, like `main:R1`, and
+/// the amounts are multiplied with the number of boards. Each board uses
+/// its own assembly variant, the variant of the settings is not used.
+///
+/// # Arguments
+///
+/// * `boards`   - The boards of the panel or kit.
+/// * `settings` - The grouping and the filter of the items.
+/// * `partlist` - A YAML file with the parts description.
+/// * `return`   - Tuple with a Vec<BomItem> and the missing items.
+pub fn bom_boards(
+    boards: &[BomBoard],
+    settings: &BomSettings,
+    partlist: Option<PathBuf>,
+) -> Result<(Vec<BomItem>, Option<Vec<BomItem>>), Error> {
+    let mut bom_items: Vec<BomItem> = Vec::new();
+    for board in boards {
+        let prefix = format!("{}:", board.name);
+        let settings = BomSettings {
+            variant: board.variant.clone(),
+            ..settings.clone()
+        };
+        bom_items.extend(project_items(
+            board.project,
+            &settings,
+            &prefix,
+            board.count,
+        )?);
+    }
+    collect(bom_items, settings, partlist)
+}
+
+///The items of all sheet instances of the project.
+fn project_items(
+    project: &Project,
+    settings: &BomSettings,
+    prefix: &str,
+    count: usize,
+) -> Result<Vec<BomItem>, Error> {
+    let mut bom_items: Vec<BomItem> = Vec::new();
+    for (index, sheet) in project.sheets.iter().enumerate() {
+        for symbol in sheet.tree.root()?.query(el::SYMBOL) {
+            let Some(reference) = project.reference(index, symbol) else {
+                continue;
+            };
+            if let Some(mut item) = bom_item(symbol, reference, settings) {
+                item.references = vec![format!("{}{}", prefix, item.references[0])];
+                item.amount = count;
+                bom_items.push(item);
+            }
+        }
+    }
+    Ok(bom_items)
+}
+
+///Create the item for the symbol, `None` when the symbol is not in the BOM.
+fn bom_item(symbol: &Sexp, reference: String, settings: &BomSettings) -> Option<BomItem> {
+    let unit: usize = symbol.value("unit").unwrap_or(1);
+    let lib_id: String = symbol.value("lib_id").unwrap();
+    let on_board: bool = symbol.value("on_board").unwrap();
+    let in_bom: bool = symbol.value("in_bom").unwrap();
+    let mut dnp = utils::is_dnp(symbol);
+    let mut substitutes = Vec::new();
+    if let Some(variant) = &settings.variant {
+        let field: Option<String> = symbol.property(&variant.field());
+        match variant.assembly(&reference, field.as_deref(), dnp) {
+            Assembly::Fitted => dnp = false,
+            Assembly::Dnp => dnp = true,
+            Assembly::Substituted(fields) => {
+                dnp = false;
+                substitutes = fields;
+            }
+        }
+    }
+    if unit == 1
+        && on_board
+        && in_bom
+        && !(dnp && (settings.exclude_dnp || settings.variant.is_some()))
+        && !lib_id.starts_with("power:")
+        && !lib_id.starts_with("Mechanical:")
+    {
+        let mut item = BomItem {
+            amount: 1,
+            references: vec![reference],
+            value: symbol.property("Value").unwrap(),
+            footprint: symbol.property("Footprint").unwrap(),
+            datasheet: symbol.property("Datasheet").unwrap(),
+            description: if let Some(description) = symbol.property("Description") {
+                description
+            } else {
+                String::new()
+            },
+            mouser_nr: String::new(),
+            dnp,
+            fields: fields(symbol),
+        };
+        for (name, value) in substitutes {
+            item.set_field(&name, &value);
+        }
+        Some(item)
+    } else {
+        None
+    }
+}
+
+///Merge the items with the partlist, group and sort them.
+fn collect(
+    items: Vec<BomItem>,
+    settings: &BomSettings,
+    partlist: Option<PathBuf>,
+) -> Result<(Vec<BomItem>, Option<Vec<BomItem>>), Error> {
     let partlist = if let Some(partlist) = partlist {
         Some(get_partlist(partlist.to_str().unwrap())?)
     } else {
@@ -166,50 +551,24 @@ pub fn bom(
     };
     let mut bom_items: Vec<BomItem> = Vec::new();
     let mut missing_items: Vec<BomItem> = Vec::new();
-
-    for item in document.root()?.query(el::SYMBOL) {
-        let unit: usize = item.value("unit").unwrap_or(1);
-        let lib_id: String = item.value("lib_id").unwrap();
-        let on_board: bool = item.value("on_board").unwrap();
-        let in_bom: bool = item.value("in_bom").unwrap();
-        if unit == 1
-            && on_board
-            && in_bom
-            && !lib_id.starts_with("power:")
-            && !lib_id.starts_with("Mechanical:")
-        {
-            let bom_item = BomItem {
-                amount: 1,
-                references: vec![item.property("Reference").unwrap()],
-                value: item.property("Value").unwrap(),
-                footprint: item.property("Footprint").unwrap(),
-                datasheet: item.property("Datasheet").unwrap(),
-                description: if let Some(description) = item.property("Description") {
-                    description
-                } else {
-                    String::new()
-                },
-                mouser_nr: String::new(),
-            };
-            if let Some(partlist) = &partlist {
-                let part = search_part(partlist, &bom_item.footprint, &bom_item.value);
-                if part.is_none() {
-                    missing_items.push(bom_item.clone());
-                    bom_items.push(bom_item);
-                } else {
-                    bom_items.push(merge_item(&bom_item, part));
-                }
-            } else {
+    for bom_item in items {
+        if let Some(partlist) = &partlist {
+            let part = search_part(partlist, &bom_item.footprint, &bom_item.value);
+            if part.is_none() {
+                missing_items.push(bom_item.clone());
                 bom_items.push(bom_item);
+            } else {
+                bom_items.push(merge_item(&bom_item, part));
             }
+        } else {
+            bom_items.push(bom_item);
         }
     }
 
-    if group {
+    if settings.group && !settings.group_by.is_empty() {
         let mut map: HashMap<String, Vec<&BomItem>> = HashMap::new();
         for item in &bom_items {
-            let key = format!("{}:{}", item.value, item.footprint);
-            map.entry(key).or_default().push(item);
+            map.entry(settings.key(item)).or_default().push(item);
         }
         bom_items = map
             .values()
@@ -219,13 +578,9 @@ pub fn bom(
                     refs.push(v.references.first().unwrap().to_string());
                 }
                 BomItem {
-                    amount: value.len(),
+                    amount: value.iter().map(|v| v.amount).sum(),
                     references: refs,
-                    value: value[0].value.to_string(),
-                    footprint: value[0].footprint.to_string(),
-                    datasheet: value[0].datasheet.to_string(),
-                    description: value[0].description.to_string(),
-                    mouser_nr: value[0].mouser_nr.to_string(),
+                    ..value[0].clone()
                 }
             })
             .collect();
@@ -248,4 +603,20 @@ pub fn bom(
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::{BomItem, BomSettings};
+
+    #[test]
+    fn test_key_dnp() {
+        let settings = BomSettings::default();
+        let item = BomItem {
+            value: String::from("4.7k"),
+            ..Default::default()
+        };
+        let dnp = BomItem {
+            dnp: true,
+            ..item.clone()
+        };
+        assert_ne!(settings.key(&item), settings.key(&dnp));
+    }
+}
@@ -1,21 +1,40 @@
 //! Run the DRC schecks for the board.
 //!
+//! The checks are done natively and don't need KiCad: the clearance between
+//! copper items of different nets, the track width, the annular ring of the
+//! vias and pads, the distance between the holes, the clearance of the copper
+//! to the board edge and the items of a net that are not connected.
+//!
+//! The design rules and the net classes are loaded from the `board` and the
+//! `net_settings` section of the KiCad project file next to the board. The
+//! copper zones are used for the connections, they are not checked for
+//! clearance, this is done by the zone fill.
+//!
+//! The schematic parity is checked with [`parity`], the footprints, values
+//! and pad nets of the board are compared with the netlist of the schematic.
+//!
+//! The checks of `kicad-cli pcb drc` are available with [`kicad_drc`].
+//!
 //! # Example:
 //!
-//! use elektron::sexp::Schema;
 //! use elektron::reports::drc::drc;
 //!
-//! let schema = Schema::load("files/summe/summe.kicad_sch").unwrap();
-//! let results = drc(&schema).unwrap();
+//! let results = drc("files/summe/summe.kicad_pcb").unwrap();
 use rand::Rng;
+use regex::Regex;
+use std::collections::HashMap;
 use std::env::temp_dir;
 use std::io::prelude::*;
+use std::path::Path;
 use std::process::Command;
 use std::{
     fmt,
     fs::{self, File},
 };
 
+use sexp::{el, Sexp, SexpParser, SexpTree, SexpValueQuery, SexpValuesQuery};
+use simulation::{Connectivity, Net, Project};
+
 use crate::Error;
 
 #[derive(Clone, Debug)]
@@ -86,122 +105,1358 @@ impl fmt::Display for DrcItems {
     }
 }
 
+///The name of the default net class.
+const DEFAULT_NETCLASS: &str = "Default";
+
+///The design rules for the DRC.
+#[derive(Debug, Clone)]
+pub struct DrcSettings {
+    ///The minimal clearance, the net class clearance is used when it is larger.
+    pub min_clearance: f64,
+    pub min_track_width: f64,
+    pub min_annular_width: f64,
+    pub min_hole_to_hole: f64,
+    pub min_copper_edge_clearance: f64,
+    ///The clearance by net class name.
+    pub clearances: HashMap<String, f64>,
+    ///The net class by net name.
+    pub netclasses: HashMap<String, String>,
+    ///The net name patterns with the net class, `*` and `?` are wildcards.
+    pub patterns: Vec<(String, String)>,
+}
+
+///The KiCad defaults for a new project.
+impl Default for DrcSettings {
+    fn default() -> Self {
+        Self {
+            min_clearance: 0.0,
+            min_track_width: 0.2,
+            min_annular_width: 0.1,
+            min_hole_to_hole: 0.25,
+            min_copper_edge_clearance: 0.5,
+            clearances: HashMap::from([(String::from(DEFAULT_NETCLASS), 0.2)]),
+            netclasses: HashMap::new(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+impl DrcSettings {
+    ///Load the design rules from the KiCad project file.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let error = |msg: String| Error::ProjectError(path.display().to_string(), msg);
+        let content = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let project = json::parse(&content).map_err(|e| error(e.to_string()))?;
+        let mut settings = Self::default();
+        let rules = &project["board"]["design_settings"]["rules"];
+        for (name, value) in [
+            ("min_clearance", &mut settings.min_clearance),
+            ("min_track_width", &mut settings.min_track_width),
+            ("min_via_annular_width", &mut settings.min_annular_width),
+            ("min_hole_to_hole", &mut settings.min_hole_to_hole),
+            (
+                "min_copper_edge_clearance",
+                &mut settings.min_copper_edge_clearance,
+            ),
+        ] {
+            if let Some(rule) = rules[name].as_f64() {
+                *value = rule;
+            }
+        }
+        let net_settings = &project["net_settings"];
+        for class in net_settings["classes"].members() {
+            let Some(name) = class["name"].as_str() else {
+                return Err(error(String::from("net class without name")));
+            };
+            if let Some(clearance) = class["clearance"].as_f64() {
+                settings.clearances.insert(name.to_string(), clearance);
+            }
+            //the nets are listed in the net class in KiCad 6.
+            for net in class["nets"].members() {
+                if let Some(net) = net.as_str() {
+                    settings
+                        .netclasses
+                        .insert(net.to_string(), name.to_string());
+                }
+            }
+        }
+        for (net, class) in net_settings["netclass_assignments"].entries() {
+            if let Some(class) = class.as_str() {
+                settings
+                    .netclasses
+                    .insert(net.to_string(), class.to_string());
+            }
+        }
+        for pattern in net_settings["netclass_patterns"].members() {
+            if let (Some(class), Some(pattern)) =
+                (pattern["netclass"].as_str(), pattern["pattern"].as_str())
+            {
+                settings
+                    .patterns
+                    .push((pattern.to_string(), class.to_string()));
+            }
+        }
+        Ok(settings)
+    }
+
+    ///The net class of the net.
+    pub fn netclass(&self, net: &str) -> String {
+        if let Some(class) = self.netclasses.get(net) {
+            return class.to_string();
+        }
+        for (pattern, class) in &self.patterns {
+            let regex = format!(
+                "^{}$",
+                regex::escape(pattern)
+                    .replace("\\*", ".*")
+                    .replace("\\?", ".")
+            );
+            if Regex::new(&regex).is_ok_and(|r| r.is_match(net)) {
+                return class.to_string();
+            }
+        }
+        String::from(DEFAULT_NETCLASS)
+    }
+
+    ///The clearance for the net.
+    pub fn clearance(&self, net: &str) -> f64 {
+        let class = self
+            .clearances
+            .get(&self.netclass(net))
+            .or(self.clearances.get(DEFAULT_NETCLASS))
+            .copied()
+            .unwrap_or(0.0);
+        class.max(self.min_clearance)
+    }
+}
+
 /// Run the DRC schecks for the board.
 ///
+/// The design rules are loaded from the KiCad project file next to the board.
+///
 /// # Arguments
 ///
 /// * `document` - A PCB filename.
 /// * `return`   - DrcResult with the errors.
 ///
 pub fn drc(document: &str) -> Result<DrcResult, Error> {
-    let mut rng = rand::thread_rng();
-    let num: u32 = rng.gen();
-    let output = String::new() + temp_dir().to_str().unwrap() + "/" + &num.to_string() + ".json";
-
-    let result = if cfg!(target_os = "windows") {
-        todo!("implement command for windows");
+    let project = Path::new(document).with_extension("kicad_pro");
+    let settings = if project.exists() {
+        DrcSettings::load(&project)?
     } else {
-        Command::new("kicad-cli")
-            .arg("pcb")
-            .arg("drc")
-            .arg("--severity-all")
-            .arg("--format")
-            .arg("json")
-            .arg("--output")
-            .arg(output.clone())
-            .arg(document)
-            .output()
-            .expect("failed to execute process")
+        DrcSettings::default()
     };
+    drc_with(document, &settings)
+}
 
-    if result.status.code() != Some(0) {
-        return Err(Error::IoError(output.to_string(), format!("failed to generate drc report from {}", document)));
+/// Run the DRC schecks for the board with the design rules.
+pub fn drc_with(document: &str, settings: &DrcSettings) -> Result<DrcResult, Error> {
+    let doc = match SexpParser::load(document) {
+        Ok(doc) => doc,
+        Err(err) => return Err(Error::IoError(document.to_string(), err.to_string())),
+    };
+    let tree = SexpTree::from(doc.iter())?;
+    let mut result = drc_from_tree(&tree, settings)?;
+    result.source = document.to_string();
+    Ok(result)
+}
+
+/// Run the DRC schecks for the board document.
+pub fn drc_from_tree(document: &SexpTree, settings: &DrcSettings) -> Result<DrcResult, Error> {
+    let board = Board::from(document.root()?);
+    let mut errors = Vec::new();
+    errors.append(&mut track_width(&board, settings));
+    errors.append(&mut annular_width(&board, settings));
+    errors.append(&mut clearance(&board, settings));
+    errors.append(&mut hole_to_hole(&board, settings));
+    errors.append(&mut edge_clearance(&board, settings));
+    errors.append(&mut unconnected(&board));
+    Ok(DrcResult {
+        coordinate_units: String::from("mm"),
+        date: String::new(),
+        kicad_version: String::new(),
+        source: String::new(),
+        errors,
+    })
+}
+
+type Point = (f64, f64);
+
+///The distance to treat items as connected.
+const EPSILON: f64 = 1e-3;
+
+///Outline of an item, the points widened by the radius.
+///
+///A track is the line from start to end with the half width as radius, a via
+///a single point, a pad a polygon.
+#[derive(Debug, Clone)]
+struct Outline {
+    pts: Vec<Point>,
+    closed: bool,
+    radius: f64,
+}
+
+impl Outline {
+    fn point(pt: Point, radius: f64) -> Self {
+        Self {
+            pts: vec![pt],
+            closed: false,
+            radius,
+        }
     }
 
-    let mut file = match File::open(output.clone()) {
-        Ok(file) => file,
-        Err(err) => return Err(Error::NetlistFileError(output.to_string(), err.to_string())),
+    fn line(pts: Vec<Point>, radius: f64) -> Self {
+        Self {
+            pts,
+            closed: false,
+            radius,
+        }
+    }
+
+    fn polygon(pts: Vec<Point>, radius: f64) -> Self {
+        Self {
+            pts,
+            closed: true,
+            radius,
+        }
+    }
+
+    fn edges(&self) -> Vec<(Point, Point)> {
+        if self.pts.len() == 1 {
+            return vec![(self.pts[0], self.pts[0])];
+        }
+        let mut edges: Vec<(Point, Point)> = self.pts.windows(2).map(|w| (w[0], w[1])).collect();
+        if self.closed && self.pts.len() > 2 {
+            edges.push((self.pts[self.pts.len() - 1], self.pts[0]));
+        }
+        edges
+    }
+
+    ///Even-odd test if the point is in the polygon.
+    fn contains(&self, pt: Point) -> bool {
+        if !self.closed || self.pts.len() < 3 {
+            return false;
+        }
+        let mut inside = false;
+        for (a, b) in self.edges() {
+            if (a.1 > pt.1) != (b.1 > pt.1) && pt.0 < (b.0 - a.0) * (pt.1 - a.1) / (b.1 - a.1) + a.0
+            {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    fn bounds(&self) -> (Point, Point) {
+        let mut min = (f64::MAX, f64::MAX);
+        let mut max = (f64::MIN, f64::MIN);
+        for pt in &self.pts {
+            min = (min.0.min(pt.0), min.1.min(pt.1));
+            max = (max.0.max(pt.0), max.1.max(pt.1));
+        }
+        (
+            (min.0 - self.radius, min.1 - self.radius),
+            (max.0 + self.radius, max.1 + self.radius),
+        )
+    }
+
+    ///The gap between the outlines, negative when they overlap.
+    fn gap(&self, other: &Outline) -> f64 {
+        let spine = if self.contains(other.pts[0]) || other.contains(self.pts[0]) {
+            0.0
+        } else {
+            let edges = other.edges();
+            self.edges()
+                .iter()
+                .flat_map(|(a, b)| edges.iter().map(|(c, d)| segment_distance(*a, *b, *c, *d)))
+                .fold(f64::MAX, f64::min)
+        };
+        spine - self.radius - other.radius
+    }
+
+    ///Test if the outlines are further apart than the distance.
+    fn is_apart(&self, other: &Outline, distance: f64) -> bool {
+        let (min, max) = self.bounds();
+        let (other_min, other_max) = other.bounds();
+        min.0 > other_max.0 + distance
+            || other_min.0 > max.0 + distance
+            || min.1 > other_max.1 + distance
+            || other_min.1 > max.1 + distance
+    }
+}
+
+fn point_distance(p: Point, a: Point, b: Point) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length).clamp(0.0, 1.0)
     };
-    let mut data = String::new();
-    file.read_to_string(&mut data).expect("Unable to read string");
-
-    let js = json::parse(&data);
-    let mut drc_result = DrcResult::new();
-    if let Ok(js) = js {
-        match js {
-            json::JsonValue::Null => todo!(),
-            json::JsonValue::Short(_) => todo!(),
-            json::JsonValue::String(_) => todo!(),
-            json::JsonValue::Number(_) => todo!(),
-            json::JsonValue::Boolean(_) => todo!(),
-            json::JsonValue::Object(obj) => {
-                drc_result = DrcResult {
-                    coordinate_units: obj.get("coordinate_units").map_or(String::from("NONE"), |m| m.to_string()),
-                    date: obj.get("date").map_or(String::from("NONE"), |m| m.to_string()),
-                    kicad_version: obj.get("kicad_version").map_or(String::from("NONE"), |m| m.to_string()),
-                    source: obj.get("source").map_or(String::from("NONE"), |m| m.to_string()),
-                    errors: Vec::new(),
+    ((a.0 + t * dx - p.0).powi(2) + (a.1 + t * dy - p.1).powi(2)).sqrt()
+}
+
+fn segment_distance(a: Point, b: Point, c: Point, d: Point) -> f64 {
+    let cross =
+        |o: Point, p: Point, q: Point| (p.0 - o.0) * (q.1 - o.1) - (p.1 - o.1) * (q.0 - o.0);
+    let (d1, d2) = (cross(c, d, a), cross(c, d, b));
+    let (d3, d4) = (cross(a, b, c), cross(a, b, d));
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+    {
+        return 0.0;
+    }
+    point_distance(a, c, d)
+        .min(point_distance(b, c, d))
+        .min(point_distance(c, a, b))
+        .min(point_distance(d, a, b))
+}
+
+///Rotate the point like KiCad, counterclockwise with the y axis down.
+fn rotate(pt: Point, angle: f64) -> Point {
+    let theta = angle.to_radians();
+    (
+        pt.0 * theta.cos() + pt.1 * theta.sin(),
+        -pt.0 * theta.sin() + pt.1 * theta.cos(),
+    )
+}
+
+///The points of an arc through three points.
+fn arc(start: Point, mid: Point, end: Point) -> Vec<Point> {
+    let d =
+        2.0 * (start.0 * (mid.1 - end.1) + mid.0 * (end.1 - start.1) + end.0 * (start.1 - mid.1));
+    if d.abs() < f64::EPSILON {
+        return vec![start, end];
+    }
+    let sq = |p: Point| p.0 * p.0 + p.1 * p.1;
+    let center = (
+        (sq(start) * (mid.1 - end.1) + sq(mid) * (end.1 - start.1) + sq(end) * (start.1 - mid.1))
+            / d,
+        (sq(start) * (end.0 - mid.0) + sq(mid) * (start.0 - end.0) + sq(end) * (mid.0 - start.0))
+            / d,
+    );
+    let radius = sq((start.0 - center.0, start.1 - center.1)).sqrt();
+    let angle = |p: Point| (p.1 - center.1).atan2(p.0 - center.0);
+    let (a0, am, a1) = (angle(start), angle(mid), angle(end));
+    let tau = std::f64::consts::TAU;
+    let mut sweep = (a1 - a0).rem_euclid(tau);
+    //the arc goes the other way when the mid point is not in the sweep.
+    if (am - a0).rem_euclid(tau) > sweep {
+        sweep -= tau;
+    }
+    let steps = 16;
+    (0..=steps)
+        .map(|i| {
+            let a = a0 + sweep * i as f64 / steps as f64;
+            (center.0 + radius * a.cos(), center.1 + radius * a.sin())
+        })
+        .collect()
+}
+
+fn circle(center: Point, radius: f64) -> Vec<Point> {
+    let steps = 32;
+    (0..=steps)
+        .map(|i| {
+            let a = std::f64::consts::TAU * i as f64 / steps as f64;
+            (center.0 + radius * a.cos(), center.1 + radius * a.sin())
+        })
+        .collect()
+}
+
+fn point(node: &Sexp, name: &str) -> Option<Point> {
+    let values = numbers(node.query(name).next()?);
+    Some((*values.first()?, *values.get(1)?))
+}
+
+pub(crate) fn numbers(node: &Sexp) -> Vec<f64> {
+    <Sexp as SexpValuesQuery<Vec<String>>>::values(node)
+        .iter()
+        .filter_map(|v| v.parse::<f64>().ok())
+        .collect()
+}
+
+fn number(node: &Sexp, name: &str) -> Option<f64> {
+    numbers(node.query(name).next()?).first().copied()
+}
+
+///A field of the footprint, the `property` or the `fp_text` before KiCad 8.
+pub(crate) fn footprint_field(node: &Sexp, property: &str, text: &str) -> Option<String> {
+    node.query(el::PROPERTY)
+        .find(|p| <Sexp as SexpValueQuery<String>>::get(*p, 0).as_deref() == Some(property))
+        .and_then(|p| p.get(1))
+        .or_else(|| {
+            node.query(el::FP_TEXT)
+                .find(|t| <Sexp as SexpValueQuery<String>>::get(*t, 0).as_deref() == Some(text))
+                .and_then(|t| t.get(1))
+        })
+}
+
+fn uuid(node: &Sexp) -> String {
+    <Sexp as SexpValueQuery<String>>::value(node, "uuid")
+        .or_else(|| <Sexp as SexpValueQuery<String>>::value(node, "tstamp"))
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Track,
+    Via,
+    Pad,
+    Zone,
+}
+
+///A copper item of the board.
+#[derive(Debug)]
+struct Copper {
+    kind: Kind,
+    net: usize,
+    layers: Vec<String>,
+    outline: Outline,
+    description: String,
+    pos: Point,
+    uuid: String,
+    ///The reference and the number of a pad.
+    pad: Option<String>,
+}
+
+impl Copper {
+    fn shares_layer(&self, other: &Copper) -> bool {
+        self.layers.iter().any(|l| other.layers.contains(l))
+    }
+
+    fn item(&self) -> DrcItems {
+        DrcItems {
+            description: self.description.clone(),
+            pos: self.pos,
+            uuid: self.uuid.clone(),
+        }
+    }
+}
+
+///A drilled hole.
+#[derive(Debug)]
+struct Hole {
+    outline: Outline,
+    description: String,
+    pos: Point,
+    uuid: String,
+}
+
+///The items of the board for the checks.
+#[derive(Debug, Default)]
+struct Board {
+    nets: HashMap<usize, String>,
+    copper: Vec<Copper>,
+    holes: Vec<Hole>,
+    edges: Vec<Outline>,
+    ///The track widths with the index of the copper item.
+    widths: Vec<(usize, f64)>,
+    ///The annular ring with the index of the copper item.
+    annular: Vec<(usize, f64)>,
+}
+
+impl Board {
+    fn from(root: &Sexp) -> Self {
+        let mut board = Board::default();
+        for net in root.query("net") {
+            let values: Vec<String> = net.values();
+            if let (Some(id), Some(name)) = (values.first(), values.get(1)) {
+                if let Ok(id) = id.parse::<usize>() {
+                    board.nets.insert(id, name.to_string());
+                }
+            }
+        }
+        let mut copper_layers: Vec<String> = Vec::new();
+        if let Some(layers) = root.query("layers").next() {
+            for layer in layers.nodes() {
+                let values: Vec<String> = layer.values();
+                if let Some(name) = values.first().filter(|n| n.ends_with(".Cu")) {
+                    copper_layers.push(name.to_string());
+                }
+            }
+        }
+        if copper_layers.is_empty() {
+            copper_layers = vec![String::from("F.Cu"), String::from("B.Cu")];
+        }
+        let layers = |node: &Sexp| -> Vec<String> {
+            let mut names: Vec<String> = node
+                .query("layers")
+                .flat_map(<Sexp as SexpValuesQuery<Vec<String>>>::values)
+                .collect();
+            names.extend(
+                node.query(el::LAYER)
+                    .flat_map(<Sexp as SexpValuesQuery<Vec<String>>>::values),
+            );
+            let mut result: Vec<String> = Vec::new();
+            for name in names {
+                if name == "*.Cu" {
+                    result.extend(copper_layers.iter().cloned());
+                } else if name == "F&B.Cu" {
+                    result.extend([String::from("F.Cu"), String::from("B.Cu")]);
+                } else if copper_layers.contains(&name) {
+                    result.push(name);
+                }
+            }
+            result
+        };
+        for node in root.nodes() {
+            match node.name.as_str() {
+                el::SEGMENT | el::ARC => board.track(node, layers(node)),
+                el::VIA => board.via(node, copper_layers.clone()),
+                el::FOOTPRINT => board.footprint(node, &layers),
+                el::ZONE => board.zone(node),
+                el::GR_LINE | "gr_rect" | el::GR_CIRCLE | "gr_arc" | el::GR_POLY => {
+                    board.edge(node, (0.0, 0.0), 0.0)
+                }
+                _ => {}
+            }
+        }
+        board
+    }
+
+    fn net(&self, id: usize) -> &str {
+        self.nets.get(&id).map(|n| n.as_str()).unwrap_or("")
+    }
+
+    fn net_id(node: &Sexp) -> usize {
+        node.query("net")
+            .next()
+            .and_then(|n| {
+                <Sexp as SexpValuesQuery<Vec<String>>>::values(n)
+                    .first()
+                    .cloned()
+            })
+            .and_then(|n| n.parse::<usize>().ok())
+            .unwrap_or(0)
+    }
+
+    fn track(&mut self, node: &Sexp, layers: Vec<String>) {
+        let (Some(start), Some(end), Some(width)) = (
+            point(node, el::START),
+            point(node, el::END),
+            number(node, el::WIDTH),
+        ) else {
+            return;
+        };
+        let pts = match point(node, "mid") {
+            Some(mid) => arc(start, mid, end),
+            None => vec![start, end],
+        };
+        let net = Board::net_id(node);
+        self.widths.push((self.copper.len(), width));
+        self.copper.push(Copper {
+            kind: Kind::Track,
+            net,
+            description: format!("Track [{}] on {}", self.net(net), layers.join(", ")),
+            layers,
+            outline: Outline::line(pts, width / 2.0),
+            pos: ((start.0 + end.0) / 2.0, (start.1 + end.1) / 2.0),
+            uuid: uuid(node),
+            pad: None,
+        });
+    }
+
+    fn via(&mut self, node: &Sexp, layers: Vec<String>) {
+        let (Some(at), Some(size)) = (point(node, el::AT), number(node, el::SIZE)) else {
+            return;
+        };
+        let net = Board::net_id(node);
+        if let Some(drill) = number(node, el::DRILL) {
+            self.annular.push((self.copper.len(), (size - drill) / 2.0));
+            self.holes.push(Hole {
+                outline: Outline::point(at, drill / 2.0),
+                description: format!("Via [{}]", self.net(net)),
+                pos: at,
+                uuid: uuid(node),
+            });
+        }
+        self.copper.push(Copper {
+            kind: Kind::Via,
+            net,
+            layers,
+            outline: Outline::point(at, size / 2.0),
+            description: format!("Via [{}]", self.net(net)),
+            pos: at,
+            uuid: uuid(node),
+            pad: None,
+        });
+    }
+
+    fn footprint(&mut self, node: &Sexp, layers: &dyn Fn(&Sexp) -> Vec<String>) {
+        let Some(at) = node.query(el::AT).next().map(numbers) else {
+            return;
+        };
+        let origin = (at[0], at[1]);
+        let angle = at.get(2).copied().unwrap_or(0.0);
+        let reference =
+            footprint_field(node, el::PROPERTY_REFERENCE, "reference").unwrap_or_default();
+        for child in node.nodes() {
+            match child.name.as_str() {
+                el::PAD => self.pad(child, origin, angle, &reference, layers(child)),
+                el::FP_LINE | "fp_rect" | el::FP_CIRCLE | el::FP_ARC | el::FP_POLY => {
+                    self.edge(child, origin, angle)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn pad(
+        &mut self,
+        node: &Sexp,
+        origin: Point,
+        angle: f64,
+        reference: &str,
+        layers: Vec<String>,
+    ) {
+        let values: Vec<String> = node.values();
+        let (Some(pad_number), Some(pad_type), Some(shape)) =
+            (values.first(), values.get(1), values.get(2))
+        else {
+            return;
+        };
+        let Some(at) = node.query(el::AT).next().map(numbers) else {
+            return;
+        };
+        let Some(size) = point(node, el::SIZE) else {
+            return;
+        };
+        let offset = rotate((at[0], at[1]), angle);
+        let pos = (origin.0 + offset.0, origin.1 + offset.1);
+        //the angle of the pad is the absolute angle.
+        let pad_angle = at.get(2).copied().unwrap_or(0.0);
+        let place = |pts: Vec<Point>| -> Vec<Point> {
+            pts.into_iter()
+                .map(|p| {
+                    let p = rotate(p, pad_angle);
+                    (pos.0 + p.0, pos.1 + p.1)
+                })
+                .collect()
+        };
+        let net = Board::net_id(node);
+        let description = format!("Pad {} [{}] of {}", pad_number, self.net(net), reference);
+        let drill = node.query(el::DRILL).next().map(|d| {
+            let values: Vec<String> = d.values();
+            let numbers: Vec<f64> = values
+                .iter()
+                .filter_map(|v| v.parse::<f64>().ok())
+                .collect();
+            let width = numbers.first().copied().unwrap_or(0.0);
+            (width, numbers.get(1).copied().unwrap_or(width))
+        });
+        if let Some((width, height)) = drill.filter(|d| d.0 > 0.0) {
+            let outline = if width == height {
+                Outline::point(pos, width / 2.0)
+            } else if width > height {
+                let dx = (width - height) / 2.0;
+                Outline::line(place(vec![(-dx, 0.0), (dx, 0.0)]), height / 2.0)
+            } else {
+                let dy = (height - width) / 2.0;
+                Outline::line(place(vec![(0.0, -dy), (0.0, dy)]), width / 2.0)
+            };
+            self.holes.push(Hole {
+                outline,
+                description: description.clone(),
+                pos,
+                uuid: uuid(node),
+            });
+            if pad_type == "thru_hole" {
+                let ring = ((size.0 - width) / 2.0).min((size.1 - height) / 2.0);
+                self.annular.push((self.copper.len(), ring));
+            }
+        }
+        if pad_type == "np_thru_hole" || layers.is_empty() {
+            return;
+        }
+        let (w, h) = (size.0 / 2.0, size.1 / 2.0);
+        let outline = match shape.as_str() {
+            "circle" => Outline::point(pos, w),
+            "oval" if w > h => Outline::line(place(vec![(-w + h, 0.0), (w - h, 0.0)]), h),
+            "oval" => Outline::line(place(vec![(0.0, -h + w), (0.0, h - w)]), w),
+            "roundrect" => {
+                let radius = number(node, "roundrect_rratio").unwrap_or(0.25) * 2.0 * w.min(h);
+                let (w, h) = (w - radius, h - radius);
+                Outline::polygon(place(vec![(-w, -h), (w, -h), (w, h), (-w, h)]), radius)
+            }
+            _ => Outline::polygon(place(vec![(-w, -h), (w, -h), (w, h), (-w, h)]), 0.0),
+        };
+        self.copper.push(Copper {
+            kind: Kind::Pad,
+            net,
+            layers,
+            outline,
+            description,
+            pos,
+            uuid: uuid(node),
+            pad: Some(format!("{}:{}", reference, pad_number)),
+        });
+    }
+
+    fn zone(&mut self, node: &Sexp) {
+        let net = Board::net_id(node);
+        for polygon in node.query(el::FILLED_POLYGON) {
+            let Some(layer) = <Sexp as SexpValueQuery<String>>::value(polygon, el::LAYER) else {
+                continue;
+            };
+            let pts: Vec<Point> = polygon
+                .query(el::PTS)
+                .flat_map(|p| p.query(el::XY))
+                .map(numbers)
+                .filter(|xy| xy.len() == 2)
+                .map(|xy| (xy[0], xy[1]))
+                .collect();
+            if pts.len() < 3 {
+                continue;
+            }
+            self.copper.push(Copper {
+                kind: Kind::Zone,
+                net,
+                description: format!("Zone [{}] on {}", self.net(net), layer),
+                layers: vec![layer],
+                pos: pts[0],
+                outline: Outline::polygon(pts, 0.0),
+                uuid: uuid(node),
+                pad: None,
+            });
+        }
+    }
+
+    fn edge(&mut self, node: &Sexp, origin: Point, angle: f64) {
+        if <Sexp as SexpValueQuery<String>>::value(node, el::LAYER).as_deref() != Some("Edge.Cuts")
+        {
+            return;
+        }
+        let start = point(node, el::START);
+        let end = point(node, el::END);
+        let pts = match (node.name.as_str(), start, end) {
+            (el::GR_LINE | el::FP_LINE, Some(start), Some(end)) => vec![start, end],
+            ("gr_rect" | "fp_rect", Some(start), Some(end)) => {
+                vec![start, (end.0, start.1), end, (start.0, end.1), start]
+            }
+            ("gr_arc" | el::FP_ARC, Some(start), Some(end)) => match point(node, "mid") {
+                Some(mid) => arc(start, mid, end),
+                None => vec![start, end],
+            },
+            (el::GR_CIRCLE | el::FP_CIRCLE, _, Some(end)) => {
+                let Some(center) = point(node, el::CENTER) else {
+                    return;
                 };
-                if let Some(unconnected_items) = obj.get("unconnected_items") {
-                    match unconnected_items {
-                        json::JsonValue::Array(arr) => {
-                            get_items(ErrType::Unconnected, arr, &mut drc_result.errors);
-                        },
-                        _ => todo!(),
-                    }
+                circle(
+                    center,
+                    ((end.0 - center.0).powi(2) + (end.1 - center.1).powi(2)).sqrt(),
+                )
+            }
+            (el::GR_POLY | el::FP_POLY, _, _) => {
+                let mut pts: Vec<Point> = node
+                    .query(el::PTS)
+                    .flat_map(|p| p.query(el::XY))
+                    .map(numbers)
+                    .filter(|xy| xy.len() == 2)
+                    .map(|xy| (xy[0], xy[1]))
+                    .collect();
+                if let Some(first) = pts.first().copied() {
+                    pts.push(first);
                 }
-                if let Some(violations) = obj.get("violations") {
-                    match violations {
-                        json::JsonValue::Array(arr) => {
-                            get_items(ErrType::Violation, arr, &mut drc_result.errors);
+                pts
+            }
+            _ => return,
+        };
+        let pts = pts
+            .into_iter()
+            .map(|p| {
+                let p = rotate(p, angle);
+                (origin.0 + p.0, origin.1 + p.1)
+            })
+            .collect();
+        self.edges.push(Outline::line(pts, 0.0));
+    }
+}
+
+fn violation(drc_type: &str, description: String, items: Vec<DrcItems>) -> DrcItem {
+    DrcItem {
+        error_type: ErrType::Violation,
+        drc_type: drc_type.to_string(),
+        description,
+        severity: String::from("error"),
+        items,
+    }
+}
+
+///Check the minimal track width.
+fn track_width(board: &Board, settings: &DrcSettings) -> Vec<DrcItem> {
+    board
+        .widths
+        .iter()
+        .filter(|(_, width)| *width < settings.min_track_width - EPSILON)
+        .map(|(index, width)| {
+            violation(
+                "track_width",
+                format!(
+                    "Track width (board setup constraints min width {:.4} mm; actual {:.4} mm)",
+                    settings.min_track_width, width
+                ),
+                vec![board.copper[*index].item()],
+            )
+        })
+        .collect()
+}
+
+///Check the annular ring of the vias and the plated holes.
+fn annular_width(board: &Board, settings: &DrcSettings) -> Vec<DrcItem> {
+    board
+        .annular
+        .iter()
+        .filter(|(_, ring)| *ring < settings.min_annular_width - EPSILON)
+        .map(|(index, ring)| {
+            violation(
+                "annular_width",
+                format!(
+                    "Annular width (board setup constraints min annular width {:.4} mm; actual {:.4} mm)",
+                    settings.min_annular_width, ring
+                ),
+                vec![board.copper[*index].item()],
+            )
+        })
+        .collect()
+}
+
+///Check the clearance between copper items of different nets.
+fn clearance(board: &Board, settings: &DrcSettings) -> Vec<DrcItem> {
+    let mut results = Vec::new();
+    let items: Vec<&Copper> = board
+        .copper
+        .iter()
+        .filter(|c| c.kind != Kind::Zone)
+        .collect();
+    for (index, a) in items.iter().enumerate() {
+        for b in &items[index + 1..] {
+            if (a.net == b.net && a.net != 0) || !a.shares_layer(b) {
+                continue;
+            }
+            //pads with the same number in a footprint are connected.
+            if a.pad.is_some() && a.pad == b.pad {
+                continue;
+            }
+            let required = settings
+                .clearance(board.net(a.net))
+                .max(settings.clearance(board.net(b.net)));
+            if a.outline.is_apart(&b.outline, required) {
+                continue;
+            }
+            let gap = a.outline.gap(&b.outline);
+            if gap < required - EPSILON {
+                results.push(violation(
+                    "clearance",
+                    format!(
+                        "Clearance violation (clearance {:.4} mm; actual {:.4} mm)",
+                        required,
+                        gap.max(0.0)
+                    ),
+                    vec![a.item(), b.item()],
+                ));
+            }
+        }
+    }
+    results
+}
+
+///Check the distance between the drilled holes.
+fn hole_to_hole(board: &Board, settings: &DrcSettings) -> Vec<DrcItem> {
+    let mut results = Vec::new();
+    for (index, a) in board.holes.iter().enumerate() {
+        for b in &board.holes[index + 1..] {
+            if a.outline.is_apart(&b.outline, settings.min_hole_to_hole) {
+                continue;
+            }
+            let gap = a.outline.gap(&b.outline);
+            if gap < settings.min_hole_to_hole - EPSILON {
+                results.push(violation(
+                    "hole_to_hole",
+                    format!(
+                        "Drilled holes too close together (board setup constraints hole to hole {:.4} mm; actual {:.4} mm)",
+                        settings.min_hole_to_hole,
+                        gap.max(0.0)
+                    ),
+                    vec![
+                        DrcItems {
+                            description: a.description.clone(),
+                            pos: a.pos,
+                            uuid: a.uuid.clone(),
                         },
-                        _ => todo!(),
-                    }
-                }
-                if let Some(parity) = obj.get("schematic_parity") {
-                    match parity {
-                        json::JsonValue::Array(arr) => {
-                            get_items(ErrType::Parity, arr, &mut drc_result.errors);
+                        DrcItems {
+                            description: b.description.clone(),
+                            pos: b.pos,
+                            uuid: b.uuid.clone(),
                         },
-                        _ => todo!(),
-                    }
+                    ],
+                ));
+            }
+        }
+    }
+    results
+}
+
+///Check the clearance of the copper to the board edge.
+fn edge_clearance(board: &Board, settings: &DrcSettings) -> Vec<DrcItem> {
+    let mut results = Vec::new();
+    for item in board.copper.iter().filter(|c| c.kind != Kind::Zone) {
+        let gap = board
+            .edges
+            .iter()
+            .filter(|e| !item.outline.is_apart(e, settings.min_copper_edge_clearance))
+            .map(|e| item.outline.gap(e))
+            .fold(f64::MAX, f64::min);
+        if gap < settings.min_copper_edge_clearance - EPSILON {
+            results.push(violation(
+                "copper_edge_clearance",
+                format!(
+                    "Board edge clearance violation (board setup constraints edge clearance {:.4} mm; actual {:.4} mm)",
+                    settings.min_copper_edge_clearance,
+                    gap.max(0.0)
+                ),
+                vec![item.item()],
+            ));
+        }
+    }
+    results
+}
+
+///Check that the items of a net are connected.
+///
+///The unconnected parts of a net are joined to the nearest part like the
+///ratsnest, every missing connection is reported.
+fn unconnected(board: &Board) -> Vec<DrcItem> {
+    let mut nets: HashMap<usize, Vec<&Copper>> = HashMap::new();
+    for item in &board.copper {
+        if item.net != 0 {
+            nets.entry(item.net).or_default().push(item);
+        }
+    }
+    let mut results = Vec::new();
+    let mut ids: Vec<&usize> = nets.keys().collect();
+    ids.sort();
+    for id in ids {
+        let items = &nets[id];
+        let mut cluster: Vec<usize> = (0..items.len()).collect();
+        fn find(cluster: &mut Vec<usize>, i: usize) -> usize {
+            if cluster[i] != i {
+                let root = find(cluster, cluster[i]);
+                cluster[i] = root;
+            }
+            cluster[i]
+        }
+        for a in 0..items.len() {
+            for b in a + 1..items.len() {
+                let same_pad = items[a].pad.is_some() && items[a].pad == items[b].pad;
+                if same_pad
+                    || items[a].shares_layer(items[b])
+                        && !items[a].outline.is_apart(&items[b].outline, EPSILON)
+                        && items[a].outline.gap(&items[b].outline) <= EPSILON
+                {
+                    let (ra, rb) = (find(&mut cluster, a), find(&mut cluster, b));
+                    cluster[ra] = rb;
                 }
-            },
-            json::JsonValue::Array(_) => todo!(),
+            }
+        }
+        let mut connected: Vec<usize> = Vec::new();
+        let mut open: Vec<usize> = (0..items.len()).collect();
+        let root = find(&mut cluster, 0);
+        open.retain(|i| {
+            if find(&mut cluster, *i) == root {
+                connected.push(*i);
+                false
+            } else {
+                true
+            }
+        });
+        while !open.is_empty() {
+            let distance = |a: &Copper, b: &Copper| {
+                ((a.pos.0 - b.pos.0).powi(2) + (a.pos.1 - b.pos.1).powi(2)).sqrt()
+            };
+            let Some((from, to)) = connected
+                .iter()
+                .flat_map(|c| open.iter().map(move |o| (*c, *o)))
+                .filter(|(_, o)| items[*o].kind != Kind::Zone)
+                .min_by(|a, b| {
+                    distance(items[a.0], items[a.1]).total_cmp(&distance(items[b.0], items[b.1]))
+                })
+            else {
+                break;
+            };
+            results.push(DrcItem {
+                error_type: ErrType::Unconnected,
+                drc_type: String::from("unconnected_items"),
+                description: format!("Missing connection between items [{}]", board.net(*id)),
+                severity: String::from("error"),
+                items: vec![items[from].item(), items[to].item()],
+            });
+            let joined = find(&mut cluster, to);
+            open.retain(|i| {
+                if find(&mut cluster, *i) == joined {
+                    connected.push(*i);
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+    }
+    results
+}
+
+///A pad of a board footprint with the net name.
+struct ParityPad {
+    number: String,
+    net: String,
+    pos: Point,
+    uuid: String,
+}
+
+///A footprint of the board for the parity check.
+struct ParityFootprint {
+    reference: String,
+    value: String,
+    footprint: String,
+    pos: Point,
+    uuid: String,
+    pads: Vec<ParityPad>,
+}
+
+impl ParityFootprint {
+    fn item(&self) -> DrcItems {
+        DrcItems {
+            description: format!("Footprint {}", self.reference),
+            pos: self.pos,
+            uuid: self.uuid.clone(),
+        }
+    }
+}
+
+///Collect the footprints of the board, the board only footprints are skipped.
+fn parity_footprints(root: &Sexp) -> Vec<ParityFootprint> {
+    let mut footprints = Vec::new();
+    for node in root.query(el::FOOTPRINT) {
+        let board_only = node.query("attr").any(|a| {
+            <Sexp as SexpValuesQuery<Vec<String>>>::values(a).contains(&String::from("board_only"))
+        });
+        let Some(reference) = footprint_field(node, el::PROPERTY_REFERENCE, "reference") else {
+            continue;
+        };
+        if board_only || reference.is_empty() || reference.starts_with('#') {
+            continue;
+        }
+        let at = node.query(el::AT).next().map(numbers).unwrap_or_default();
+        let origin = (
+            at.first().copied().unwrap_or(0.0),
+            at.get(1).copied().unwrap_or(0.0),
+        );
+        let angle = at.get(2).copied().unwrap_or(0.0);
+        let pads = node
+            .query(el::PAD)
+            .filter_map(|pad| {
+                let number: String = pad.get(0)?;
+                let net = pad
+                    .query("net")
+                    .next()
+                    .and_then(|n| <Sexp as SexpValueQuery<String>>::get(n, 1))
+                    .unwrap_or_default();
+                let offset = point(pad, el::AT)
+                    .map(|p| rotate(p, angle))
+                    .unwrap_or((0.0, 0.0));
+                Some(ParityPad {
+                    number,
+                    net,
+                    pos: (origin.0 + offset.0, origin.1 + offset.1),
+                    uuid: uuid(pad),
+                })
+            })
+            .collect();
+        footprints.push(ParityFootprint {
+            reference,
+            value: footprint_field(node, el::PROPERTY_VALUE, "value").unwrap_or_default(),
+            footprint: node.get(0).unwrap_or_default(),
+            pos: origin,
+            uuid: uuid(node),
+            pads,
+        });
+    }
+    footprints
+}
+
+fn parity_item(drc_type: &str, description: String, items: Vec<DrcItems>) -> DrcItem {
+    DrcItem {
+        error_type: ErrType::Parity,
+        drc_type: drc_type.to_string(),
+        description,
+        severity: String::from("error"),
+        items,
+    }
+}
+
+/// Compare the schematic with the board.
+///
+/// The footprints are matched by reference. The nets are compared by the
+/// connections and not by the names: the pads of a schematic net must be on
+/// the same board net and every board net may only be used by one schematic
+/// net.
+///
+/// # Arguments
+///
+/// * `schema`   - The root schematic filename.
+/// * `document` - A PCB filename.
+/// * `return`   - The differences as `Parity` items.
+///
+pub fn parity(schema: &Path, document: &str) -> Result<Vec<DrcItem>, Error> {
+    let project = Project::load(schema)
+        .map_err(|err| Error::IoError(schema.display().to_string(), err.to_string()))?;
+    let connectivity = project
+        .connectivity()
+        .map_err(|err| Error::IoError(schema.display().to_string(), err.to_string()))?;
+    let doc = match SexpParser::load(document) {
+        Ok(doc) => doc,
+        Err(err) => return Err(Error::IoError(document.to_string(), err.to_string())),
+    };
+    let tree = SexpTree::from(doc.iter())?;
+    Ok(parity_from(&connectivity, tree.root()?))
+}
+
+/// Compare the connectivity of the schematic with the board.
+pub fn parity_from(connectivity: &Connectivity, board: &Sexp) -> Vec<DrcItem> {
+    let mut results = Vec::new();
+    let footprints = parity_footprints(board);
+    let by_reference: HashMap<&str, &ParityFootprint> = footprints
+        .iter()
+        .map(|f| (f.reference.as_str(), f))
+        .collect();
+    for component in &connectivity.components {
+        let Some(footprint) = by_reference.get(component.reference.as_str()) else {
+            results.push(parity_item(
+                "missing_footprint",
+                format!(
+                    "Missing footprint {} ({})",
+                    component.reference, component.value
+                ),
+                vec![DrcItems {
+                    description: format!("Symbol {}", component.reference),
+                    pos: (0.0, 0.0),
+                    uuid: component.uuid.clone(),
+                }],
+            ));
+            continue;
+        };
+        if !component.footprint.is_empty() && component.footprint != footprint.footprint {
+            results.push(parity_item(
+                "footprint_mismatch",
+                format!(
+                    "Footprint {} doesn't match the schematic ({})",
+                    footprint.footprint, component.footprint
+                ),
+                vec![footprint.item()],
+            ));
+        }
+        if component.value != footprint.value {
+            results.push(parity_item(
+                "value_mismatch",
+                format!(
+                    "Value {} doesn't match the schematic ({})",
+                    footprint.value, component.value
+                ),
+                vec![footprint.item()],
+            ));
+        }
+    }
+    for footprint in &footprints {
+        if !connectivity
+            .components
+            .iter()
+            .any(|c| c.reference == footprint.reference)
+        {
+            results.push(parity_item(
+                "extra_footprint",
+                format!(
+                    "Footprint {} not found in the schematic",
+                    footprint.reference
+                ),
+                vec![footprint.item()],
+            ));
+        }
+    }
+
+    //the board net of a schematic net is the net used by most of the pads.
+    let pad = |reference: &str, number: &str| {
+        by_reference
+            .get(reference)
+            .and_then(|f| f.pads.iter().find(|p| p.number == number))
+    };
+    let mut primary: Vec<(&Net, String)> = Vec::new();
+    for net in &connectivity.nets {
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for pad in net.nodes.iter().filter_map(|n| pad(&n.reference, &n.pin)) {
+            match counts.iter_mut().find(|(n, _)| *n == pad.net) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((&pad.net, 1)),
+            }
+        }
+        if let Some((board_net, _)) = counts.iter().rev().max_by_key(|(_, count)| *count) {
+            primary.push((net, board_net.to_string()));
+        }
+    }
+
+    //a board net belongs to the schematic net with the same name or to the first one.
+    let mut owners: HashMap<&str, &str> = HashMap::new();
+    for (net, board_net) in &primary {
+        if net.name.trim_start_matches('/') == board_net.trim_start_matches('/') {
+            owners.insert(board_net, &net.name);
+        }
+    }
+    for (net, board_net) in &primary {
+        if !board_net.is_empty() {
+            owners.entry(board_net).or_insert(&net.name);
+        }
+    }
+
+    for (net, board_net) in &primary {
+        let owner = owners
+            .get(board_net.as_str())
+            .filter(|owner| **owner != net.name);
+        for node in &net.nodes {
+            let Some(pad) = pad(&node.reference, &node.pin) else {
+                continue;
+            };
+            let description = if pad.net != *board_net {
+                format!(
+                    "Pad {} of {} is on net {}, the schematic net {} is on {}",
+                    pad.number, node.reference, pad.net, net.name, board_net
+                )
+            } else if let Some(owner) = owner {
+                format!(
+                    "Pad {} of {} connects the schematic nets {} and {} on net {}",
+                    pad.number, node.reference, owner, net.name, pad.net
+                )
+            } else {
+                continue;
+            };
+            results.push(parity_item(
+                "net_conflict",
+                description,
+                vec![DrcItems {
+                    description: format!("Pad {} [{}] of {}", pad.number, pad.net, node.reference),
+                    pos: pad.pos,
+                    uuid: pad.uuid.clone(),
+                }],
+            ));
         }
     }
+    results
+}
+
+/// Run the DRC schecks of KiCad for the board.
+///
+/// Needs `kicad-cli` in the path.
+///
+/// # Arguments
+///
+/// * `document` - A PCB filename.
+/// * `return`   - DrcResult with the errors.
+///
+pub fn kicad_drc(document: &str) -> Result<DrcResult, Error> {
+    let mut rng = rand::thread_rng();
+    let num: u32 = rng.gen();
+    let output = temp_dir().join(format!("{}.json", num));
+    let output = output.to_str().unwrap().to_string();
 
-    fs::remove_file(output).unwrap();
+    let result = Command::new("kicad-cli")
+        .arg("pcb")
+        .arg("drc")
+        .arg("--severity-all")
+        .arg("--format")
+        .arg("json")
+        .arg("--output")
+        .arg(output.clone())
+        .arg(document)
+        .output()
+        .map_err(|err| Error::IoError(String::from("kicad-cli"), err.to_string()))?;
+
+    if result.status.code() != Some(0) {
+        return Err(Error::IoError(
+            output.to_string(),
+            format!("failed to generate drc report from {}", document),
+        ));
+    }
+
+    let mut file = match File::open(output.clone()) {
+        Ok(file) => file,
+        Err(err) => return Err(Error::NetlistFileError(output.to_string(), err.to_string())),
+    };
+    let mut data = String::new();
+    file.read_to_string(&mut data)
+        .map_err(|err| Error::IoError(output.to_string(), err.to_string()))?;
+    fs::remove_file(&output).map_err(|err| Error::IoError(output.to_string(), err.to_string()))?;
+
+    let js =
+        json::parse(&data).map_err(|err| Error::IoError(output.to_string(), err.to_string()))?;
+    let json::JsonValue::Object(obj) = js else {
+        return Err(Error::IoError(
+            output.to_string(),
+            String::from("drc report is not a json object"),
+        ));
+    };
+    let mut drc_result = DrcResult {
+        coordinate_units: obj
+            .get("coordinate_units")
+            .map_or(String::from("NONE"), |m| m.to_string()),
+        date: obj
+            .get("date")
+            .map_or(String::from("NONE"), |m| m.to_string()),
+        kicad_version: obj
+            .get("kicad_version")
+            .map_or(String::from("NONE"), |m| m.to_string()),
+        source: obj
+            .get("source")
+            .map_or(String::from("NONE"), |m| m.to_string()),
+        errors: Vec::new(),
+    };
+    for (key, error_type) in [
+        ("unconnected_items", ErrType::Unconnected),
+        ("violations", ErrType::Violation),
+        ("schematic_parity", ErrType::Parity),
+    ] {
+        if let Some(json::JsonValue::Array(arr)) = obj.get(key) {
+            get_items(error_type, arr, &mut drc_result.errors);
+        }
+    }
 
     Ok(drc_result)
 }
 
-fn get_items(error_type: ErrType,values: &Vec<json::JsonValue>, result: &mut Vec<DrcItem>) {
+fn get_items(error_type: ErrType, values: &Vec<json::JsonValue>, result: &mut Vec<DrcItem>) {
     for obj in values {
-        match obj {
-            json::JsonValue::Object(obj) => {
-                let mut items: Vec<DrcItems> = Vec::new();
-                for item in obj.get("items").unwrap().members() {
-                    if let json::JsonValue::Object(item) = item {
-                        items.push(DrcItems {
-                            description: item.get("description").unwrap().to_string(),
-                            pos: if let json::JsonValue::Object(pos) = item.get("pos").unwrap() {
-                                (pos.get("x").unwrap().as_f64().unwrap(),
-                                 pos.get("y").unwrap().as_f64().unwrap())
-                            } else { (0.0, 0.0) },
-                            uuid: item.get("uuid").unwrap().to_string(),
-                        });
-                    }
-                }
-                result.push(DrcItem {
-                    error_type: error_type.clone(),
-                    drc_type: obj.get("type").map_or(String::from("NONE"), |m| m.to_string()),
-                    description: obj.get("description").map_or(String::from("NONE"), |m| m.to_string()),
-                    severity: obj.get("severity").map_or(String::from("NONE"), |m| m.to_string()),
-                    items,
-                });
-            },
-            _ => todo!(),
+        let json::JsonValue::Object(obj) = obj else {
+            continue;
+        };
+        let mut items: Vec<DrcItems> = Vec::new();
+        for item in obj["items"].members() {
+            items.push(DrcItems {
+                description: item["description"].to_string(),
+                pos: (
+                    item["pos"]["x"].as_f64().unwrap_or(0.0),
+                    item["pos"]["y"].as_f64().unwrap_or(0.0),
+                ),
+                uuid: item["uuid"].to_string(),
+            });
         }
+        result.push(DrcItem {
+            error_type: error_type.clone(),
+            drc_type: obj
+                .get("type")
+                .map_or(String::from("NONE"), |m| m.to_string()),
+            description: obj
+                .get("description")
+                .map_or(String::from("NONE"), |m| m.to_string()),
+            severity: obj
+                .get("severity")
+                .map_or(String::from("NONE"), |m| m.to_string()),
+            items,
+        });
     }
 }
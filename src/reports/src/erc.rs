@@ -1,5 +1,21 @@
 //! Run the ERC schecks for the schema.
 //!
+//! The sheets of a hierarchical schema are loaded and checked.
+//!
+//! Besides the pins and the units, the schema is checked for labels and wire
+//! ends which are not connected, global labels that differ only in case,
+//! power nets without a `PWR_FLAG`, duplicate references and overlapping
+//! symbols.
+//!
+//! The severity of the rules and the excluded violations are loaded from the
+//! `erc` section of the KiCad project file next to the schema, the
+//! `rule_severities`, the `pin_map` and the `erc_exclusions`. KiCad stores
+//! the exclusions with the rule name, the position and the uuids of the
+//! items, the pin or the symbol. The exclusions can also be the rule name and
+//! the reference with the pin number, like `pin_not_connected|U1:8`. The
+//! pins of the units after the first are reported with the unit letter,
+//! like `U1b:8`, the exclusion `U1:8` matches the pin of every unit.
+//!
 //! # Example:
 //!
 //! use elektron::sexp::Schema;
@@ -7,16 +23,23 @@
 //!
 //! let schema = Schema::load("files/summe/summe.kicad_sch").unwrap();
 //! let results = erc(&schema).unwrap();
-use std::{collections::HashMap, fmt, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::Path,
+};
 
 use itertools::Itertools;
-use ndarray::{arr1, Array1};
+use ndarray::{arr1, Array1, Array2};
 
-use crate::Error;
-use sexp::math::{Shape, Transform};
-use simulation::{Netlist, Point};
+use crate::{
+    pinmap::{PinConflict, PinMatrix, PinType},
+    Error,
+};
+use sexp::math::{Bounds, Shape, Transform};
+use simulation::{Netlist, NodePositions, Point, Project};
 
-use sexp::{el, utils, Sexp, SexpParser, SexpProperty, SexpTree, SexpValueQuery};
+use sexp::{el, utils, Sexp, SexpProperty, SexpTree, SexpValueQuery};
 #[derive(Debug, Clone)]
 /// ERC error types.
 pub enum ErcType {
@@ -30,6 +53,67 @@ pub enum ErcType {
     NotAllParts,
     ///Pin is not connected.
     PinNotConnected,
+    ///More than one label on the net.
+    ConflictingLabels,
+    ///The pin types on the net are a warning in the pin matrix.
+    PinToPinWarning,
+    ///The pin types on the net are an error in the pin matrix.
+    PinToPinError,
+    ///Input pin is not driven by an output.
+    PinNotDriven,
+    ///Power input pin is not driven by a power output, the nets of the power
+    ///symbols are reported with [`ErcType::MissingPowerFlag`].
+    PowerPinNotDriven,
+    ///Label is not connected to a wire or a pin.
+    LabelDangling,
+    ///Global labels which differ only in case.
+    SimilarLabels,
+    ///Power net without a power flag or a power output.
+    MissingPowerFlag,
+    ///The reference is used more than once.
+    DuplicateReference,
+    ///The symbols overlap.
+    OverlappingSymbols,
+    ///The end of the wire is not connected.
+    WireDangling,
+    ///The library symbol is not found in the schema.
+    MissingLibrarySymbol,
+}
+
+impl ErcType {
+    ///The name of the rule in the KiCad project file.
+    pub fn rule(&self) -> &'static str {
+        match self {
+            ErcType::NoReference => "unannotated",
+            ErcType::ValuesDiffer => "different_unit_value",
+            ErcType::Netlist => "netlist",
+            ErcType::NotAllParts => "missing_unit",
+            ErcType::PinNotConnected => "pin_not_connected",
+            ErcType::ConflictingLabels => "multiple_net_names",
+            ErcType::PinToPinWarning | ErcType::PinToPinError => "pin_to_pin",
+            ErcType::PinNotDriven => "pin_not_driven",
+            ErcType::PowerPinNotDriven => "power_pin_not_driven",
+            ErcType::LabelDangling => "label_dangling",
+            ErcType::SimilarLabels => "similar_labels",
+            ErcType::MissingPowerFlag => "missing_power_flag",
+            ErcType::DuplicateReference => "duplicate_reference",
+            ErcType::OverlappingSymbols => "overlapping_symbols",
+            ErcType::WireDangling => "wire_dangling",
+            ErcType::MissingLibrarySymbol => "lib_symbol_issues",
+        }
+    }
+
+    ///The severity when the rule is not configured.
+    pub fn severity(&self) -> Severity {
+        match self {
+            ErcType::NotAllParts
+            | ErcType::ConflictingLabels
+            | ErcType::PinToPinWarning
+            | ErcType::SimilarLabels
+            | ErcType::OverlappingSymbols => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
 }
 
 impl fmt::Display for ErcType {
@@ -40,6 +124,48 @@ impl fmt::Display for ErcType {
             ErcType::Netlist => write!(f, "Netlist"),
             ErcType::NotAllParts => write!(f, "NotAllParts"),
             ErcType::PinNotConnected => write!(f, "PinNotConnected"),
+            ErcType::ConflictingLabels => write!(f, "ConflictingLabels"),
+            ErcType::PinToPinWarning => write!(f, "PinToPinWarning"),
+            ErcType::PinToPinError => write!(f, "PinToPinError"),
+            ErcType::PinNotDriven => write!(f, "PinNotDriven"),
+            ErcType::PowerPinNotDriven => write!(f, "PowerPinNotDriven"),
+            ErcType::LabelDangling => write!(f, "LabelDangling"),
+            ErcType::SimilarLabels => write!(f, "SimilarLabels"),
+            ErcType::MissingPowerFlag => write!(f, "MissingPowerFlag"),
+            ErcType::DuplicateReference => write!(f, "DuplicateReference"),
+            ErcType::OverlappingSymbols => write!(f, "OverlappingSymbols"),
+            ErcType::WireDangling => write!(f, "WireDangling"),
+            ErcType::MissingLibrarySymbol => write!(f, "MissingLibrarySymbol"),
+        }
+    }
+}
+
+/// The severity of an ERC rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Ignore,
+}
+
+impl Severity {
+    ///Get the severity by name, like `warning`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "error" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            "ignore" => Some(Severity::Ignore),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Ignore => write!(f, "ignore"),
         }
     }
 }
@@ -52,53 +178,207 @@ pub struct ErcItem {
     pub reference: String,
     pub at: Array1<f64>,
     pub description: String,
+    pub severity: Severity,
+    ///The uuid of the item, the pin or the symbol. Empty when the item has
+    ///no uuid.
+    pub uuid: String,
+    ///The file of the sheet with the item, empty when it is not known.
+    pub sheet: String,
 }
 
 impl ErcItem {
     pub fn from(id: ErcType, reference: &str, at: Array1<f64>, description: String) -> Self {
         Self {
+            severity: id.severity(),
             id,
             reference: reference.to_string(),
             at: at.clone(),
             description,
+            uuid: String::new(),
+            sheet: String::new(),
+        }
+    }
+
+    ///Set the uuid of the item.
+    pub fn with_uuid(mut self, uuid: &str) -> Self {
+        self.uuid = uuid.to_string();
+        self
+    }
+
+    ///Set the file of the sheet.
+    pub fn with_sheet(mut self, sheet: &str) -> Self {
+        self.sheet = sheet.to_string();
+        self
+    }
+}
+
+///The reference without the unit letter, `U1b:8` is `U1:8`.
+fn symbol_reference(reference: &str) -> String {
+    let Some((symbol, pin)) = reference.split_once(':') else {
+        return reference.to_string();
+    };
+    let mut chars = symbol.chars().rev();
+    match (chars.next(), chars.next()) {
+        (Some(unit), Some(digit)) if unit.is_ascii_lowercase() && digit.is_ascii_digit() => {
+            format!("{}:{}", &symbol[..symbol.len() - 1], pin)
         }
+        _ => reference.to_string(),
+    }
+}
+
+/// The ERC settings.
+#[derive(Debug, Clone, Default)]
+pub struct ErcSettings {
+    ///The pin conflict matrix.
+    pub pin_map: PinMatrix,
+    ///The configured severities by rule name, like `pin_not_connected`.
+    pub severities: HashMap<String, Severity>,
+    ///The excluded violations separated by `|`, the rule name, the position
+    ///and the uuids of the items like KiCad stores them, or the rule name and
+    ///the reference with the pin number.
+    pub exclusions: Vec<String>,
+}
+
+impl ErcSettings {
+    ///Load the settings from the `erc` section of a KiCad project file.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let error = |msg: String| Error::ProjectError(path.display().to_string(), msg);
+        let content = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let project = json::parse(&content).map_err(|e| error(e.to_string()))?;
+        let erc = &project["erc"];
+        let mut settings = Self::default();
+        for (rule, severity) in erc["rule_severities"].entries() {
+            let Some(severity) = severity.as_str().and_then(Severity::from_name) else {
+                return Err(error(format!("invalid severity for {}", rule)));
+            };
+            settings.severities.insert(rule.to_string(), severity);
+        }
+        if erc["pin_map"].is_array() {
+            let pin_map: Vec<Vec<u8>> = erc["pin_map"]
+                .members()
+                .map(|row| {
+                    row.members()
+                        .map(|v| v.as_u8().unwrap_or(u8::MAX))
+                        .collect()
+                })
+                .collect();
+            settings.pin_map = PinMatrix::from_pin_map(&pin_map)?;
+        }
+        for exclusion in erc["erc_exclusions"].members() {
+            //newer KiCad versions store the exclusion with a comment.
+            let exclusion = if exclusion.is_array() {
+                &exclusion[0]
+            } else {
+                exclusion
+            };
+            if let Some(exclusion) = exclusion.as_str() {
+                settings.exclusions.push(exclusion.to_string());
+            }
+        }
+        Ok(settings)
+    }
+
+    ///The severity of the ERC type.
+    ///
+    ///The `pin_to_pin` rule configures the warnings of the pin matrix, the
+    ///errors can only be ignored.
+    pub fn severity(&self, id: &ErcType) -> Severity {
+        match (id, self.severities.get(id.rule())) {
+            (ErcType::PinToPinError, Some(Severity::Ignore)) => Severity::Ignore,
+            (ErcType::PinToPinError, _) => Severity::Error,
+            (_, Some(severity)) => *severity,
+            (_, None) => id.severity(),
+        }
+    }
+
+    ///Test if the item is excluded.
+    ///
+    ///The KiCad exclusions are matched with the uuid of the item, the other
+    ///exclusions with the reference, with or without the unit letter.
+    pub fn is_excluded(&self, item: &ErcItem) -> bool {
+        self.exclusions.iter().any(|exclusion| {
+            let parts: Vec<&str> = exclusion.split('|').collect();
+            match parts.as_slice() {
+                [rule, reference] => {
+                    *rule == item.id.rule()
+                        && (*reference == item.reference
+                            || *reference == symbol_reference(&item.reference))
+                }
+                [rule, _, _, uuids @ ..] => {
+                    *rule == item.id.rule()
+                        && !item.uuid.is_empty()
+                        && uuids.contains(&item.uuid.as_str())
+                }
+                _ => false,
+            }
+        })
+    }
+
+    ///Set the severities and remove the ignored and excluded items.
+    fn apply(&self, items: Vec<ErcItem>) -> Vec<ErcItem> {
+        items
+            .into_iter()
+            .filter_map(|mut item| {
+                item.severity = self.severity(&item.id);
+                if item.severity == Severity::Ignore || self.is_excluded(&item) {
+                    None
+                } else {
+                    Some(item)
+                }
+            })
+            .collect()
     }
 }
 
 /// Run the ERC schecks for the schema.
 ///
+/// The settings are loaded from the KiCad project file with the same name
+/// as the schema, when it exists.
+///
 /// # Arguments
 ///
 /// * `input`    - Filename of the shema.
 /// * `return`   - Vec<ErcItem> with the errors.
 pub fn erc(input: &Path) -> Result<Vec<ErcItem>, Error> {
+    let project = input.with_extension("kicad_pro");
+    let settings = if project.exists() {
+        ErcSettings::load(&project)?
+    } else {
+        ErcSettings::default()
+    };
+    erc_with(input, &settings)
+}
+
+/// Run the ERC schecks for the schema with the settings.
+///
+/// # Arguments
+///
+/// * `input`    - Filename of the shema.
+/// * `settings` - The ERC settings.
+/// * `return`   - Vec<ErcItem> with the errors.
+pub fn erc_with(input: &Path, settings: &ErcSettings) -> Result<Vec<ErcItem>, Error> {
     let mut results = Vec::new();
-    let doc = match SexpParser::load(input.to_str().unwrap()) {
-        Ok(doc) => doc,
+    let project = match Project::load(input) {
+        Ok(project) => project,
         Err(err) => return Err(Error::IoError(input.to_str().unwrap().to_string(), err.to_string())),
     };
-    let document = match SexpTree::from(doc.iter()) {
-        Ok(doc) => doc,
-        Err(err) => return Err(Error::SexpError(err.to_string())),
-    };
 
-    let elements = symbols(&document);
-    results.append(&mut references(&document, &elements));
-    results.append(&mut values(&elements));
-    let netlist = Netlist::from(&document);
-    match netlist {
-        Ok(netlist) => {
-            results.append(&mut pins(&document, &elements, &netlist));
-        }
-        Err(netlist) => {
-            results.push(ErcItem::from(
-                ErcType::Netlist,
-                &netlist.to_string(),
-                arr1(&[]),
-                String::from("netlist not found"),
-            ));
+    //check every sheet file once, the sheets can be used more than once.
+    let mut files = Vec::new();
+    for sheet in &project.sheets {
+        if files.contains(&sheet.file) {
+            continue;
         }
+        files.push(sheet.file.clone());
+        let file = sheet.file.display().to_string();
+        let items = sheet_checks(&sheet.tree)?
+            .into_iter()
+            .map(|item| item.with_sheet(&file))
+            .collect();
+        results.append(&mut settings.apply(items));
     }
+    results.append(&mut settings.apply(sheet_references(&project)));
+    results.append(&mut settings.apply(project_checks(&project, settings)));
     Ok(results)
 }
 
@@ -109,14 +389,40 @@ pub fn erc(input: &Path) -> Result<Vec<ErcItem>, Error> {
 /// * `input`    - Filename of the shema.
 /// * `return`   - Vec<ErcItem> with the errors.
 pub fn erc_from_tree(document: &SexpTree) -> Result<Vec<ErcItem>, Error> {
+    erc_from_tree_with(document, &ErcSettings::default())
+}
+
+/// Run the ERC schecks for the schema with the settings.
+///
+/// # Arguments
+///
+/// * `document` - The schema.
+/// * `settings` - The ERC settings.
+/// * `return`   - Vec<ErcItem> with the errors.
+pub fn erc_from_tree_with(
+    document: &SexpTree,
+    settings: &ErcSettings,
+) -> Result<Vec<ErcItem>, Error> {
+    let project = Project::from(document.clone());
+    let mut results = sheet_checks(document)?;
+    results.append(&mut project_checks(&project, settings));
+    Ok(settings.apply(results))
+}
+
+///Run the checks of a single sheet.
+fn sheet_checks(document: &SexpTree) -> Result<Vec<ErcItem>, Error> {
     let mut results = Vec::new();
     let elements = symbols(document);
     results.append(&mut references(document, &elements));
     results.append(&mut values(&elements));
+    results.append(&mut duplicates(&elements));
+    results.append(&mut overlaps(document, &elements));
     let netlist = Netlist::from(document);
     match netlist {
         Ok(netlist) => {
             results.append(&mut pins(document, &elements, &netlist));
+            results.append(&mut labels(&netlist));
+            results.append(&mut dangling(document)?);
         }
         Err(netlist) => {
             results.push(ErcItem::from(
@@ -130,6 +436,44 @@ pub fn erc_from_tree(document: &SexpTree) -> Result<Vec<ErcItem>, Error> {
     Ok(results)
 }
 
+///Run the checks on the global labels and the project-wide nets.
+///
+///The nets of the sheets are connected by the labels, the power symbols and
+///the sheet pins. The netlist errors are reported by the sheet checks.
+fn project_checks(project: &Project, settings: &ErcSettings) -> Vec<ErcItem> {
+    let mut results = similar_labels(project);
+    let Ok(netlists) = project.netlists() else {
+        return results;
+    };
+    let names = project.net_names(&netlists);
+    let nets = net_pins(project, &netlists, &names);
+    results.append(&mut pin_types(&nets, &settings.pin_map));
+    results.append(&mut power_flags(&nets));
+    results
+}
+
+///The uuid of the symbol.
+fn uuid(symbol: &Sexp) -> String {
+    symbol
+        .query("uuid")
+        .next()
+        .and_then(|u| u.get(0))
+        .unwrap_or_default()
+}
+
+///The uuid of the symbol pin.
+///
+///The pin uuids are stored in the symbol, the uuid of the symbol is used
+///when the pin is not found.
+fn pin_uuid(symbol: &Sexp, number: &str) -> String {
+    symbol
+        .query(el::PIN)
+        .find(|p| SexpValueQuery::<String>::get(*p, 0).as_deref() == Some(number))
+        .and_then(|p| p.query("uuid").next())
+        .and_then(|u| u.get(0))
+        .unwrap_or_else(|| uuid(symbol))
+}
+
 //Colllect all the symbols in the schematic.
 pub fn symbols(document: &SexpTree) -> HashMap<String, Vec<&Sexp>> {
     let mut elements: HashMap<String, Vec<&Sexp>> = HashMap::new();
@@ -167,40 +511,46 @@ fn pins(
                         if netlist.node_name(&Point::new(point[0], point[1])).is_none() {
                             if let Some(pin_type) = <Sexp as SexpValueQuery<String>>::get(pin, 0) {
                                 if pin_type == "no_connect" {
-                                    break;
+                                    continue;
                                 }
                             };
                             if unit > 1 {
-                                results.push(ErcItem::from(
-                                    ErcType::PinNotConnected,
-                                    &format!(
-                                        "{}{}:{}",
-                                        <Sexp as SexpProperty::<String>>::property(
-                                            symbol,
-                                            el::PROPERTY_REFERENCE
-                                        )
-                                        .unwrap(),
-                                        alphabet[unit - 1],
-                                        number,
-                                    ),
-                                    point,
-                                    format!("Pin {} not connected", number),
-                                ));
+                                results.push(
+                                    ErcItem::from(
+                                        ErcType::PinNotConnected,
+                                        &format!(
+                                            "{}{}:{}",
+                                            <Sexp as SexpProperty::<String>>::property(
+                                                symbol,
+                                                el::PROPERTY_REFERENCE
+                                            )
+                                            .unwrap(),
+                                            alphabet[unit - 1],
+                                            number,
+                                        ),
+                                        point,
+                                        format!("Pin {} not connected", number),
+                                    )
+                                    .with_uuid(&pin_uuid(symbol, &number)),
+                                );
                             } else {
-                                results.push(ErcItem::from(
-                                    ErcType::PinNotConnected,
-                                    &format!(
-                                        "{}:{}",
-                                        <Sexp as SexpProperty::<String>>::property(
-                                            symbol,
-                                            el::PROPERTY_REFERENCE
-                                        )
-                                        .unwrap(),
-                                        number
-                                    ),
-                                    point,
-                                    format!("Pin {} not connected", number),
-                                ));
+                                results.push(
+                                    ErcItem::from(
+                                        ErcType::PinNotConnected,
+                                        &format!(
+                                            "{}:{}",
+                                            <Sexp as SexpProperty::<String>>::property(
+                                                symbol,
+                                                el::PROPERTY_REFERENCE
+                                            )
+                                            .unwrap(),
+                                            number
+                                        ),
+                                        point,
+                                        format!("Pin {} not connected", number),
+                                    )
+                                    .with_uuid(&pin_uuid(symbol, &number)),
+                                );
                             }
                         }
                     }
@@ -211,6 +561,478 @@ fn pins(
     results
 }
 
+///A symbol pin on a net.
+struct NetPin {
+    ///The reference with the pin number, like `U1:8`.
+    name: String,
+    ///The uuid of the pin.
+    uuid: String,
+    pin_type: PinType,
+    at: Array1<f64>,
+    ///The file of the sheet.
+    sheet: String,
+}
+
+///The symbol pins by net name.
+struct Nets {
+    pins: HashMap<String, Vec<NetPin>>,
+    ///The nets with a hierarchical label or a sheet pin of a sheet which is
+    ///checked without the hierarchy.
+    open: HashSet<String>,
+    ///The nets of the power symbols with the first power input pin.
+    power: Vec<(String, NetPin)>,
+}
+
+///Collect the symbol pins by net name.
+///
+///The nodes of the sheets are named with the project-wide net names, the
+///node identifier is used when the name is not found. The power symbols
+///name the net, only the pins of the power flags are used. The power inputs
+///of the power symbols are collected for the power flag check.
+fn net_pins(
+    project: &Project,
+    netlists: &[Netlist],
+    names: &HashMap<(usize, String), String>,
+) -> Nets {
+    let mut nets = Nets {
+        pins: HashMap::new(),
+        open: HashSet::new(),
+        power: Vec::new(),
+    };
+    for (index, netlist) in netlists.iter().enumerate() {
+        let Ok(root) = project.sheets[index].tree.root() else {
+            continue;
+        };
+        let file = project.sheets[index].file.display().to_string();
+        let is_power = |symbol: &Sexp| {
+            let lib_id: String = symbol.value(el::LIB_ID).unwrap();
+            lib_id.starts_with("power:")
+        };
+        let symbols = root
+            .query(el::SYMBOL)
+            .filter(|s| {
+                let lib_id: String = s.value(el::LIB_ID).unwrap();
+                !lib_id.starts_with("Mechanical:")
+            })
+            .filter_map(|s| project.reference(index, s).map(|r| (r, s)))
+            .sorted_by(|a, b| is_power(a.1).cmp(&is_power(b.1)).then(a.0.cmp(&b.0)));
+        for (reference, symbol) in symbols {
+            let lib_id: String = symbol.value(el::LIB_ID).unwrap();
+            let unit: usize = symbol.value(el::SYMBOL_UNIT).unwrap();
+            let Some(libsymbol) = utils::get_library(root, &lib_id) else {
+                continue;
+            };
+            let Ok(pins) = utils::pins(libsymbol, unit) else {
+                continue;
+            };
+            for pin in pins {
+                let Some(pin_type) = <Sexp as SexpValueQuery<String>>::get(pin, 0)
+                    .and_then(|t| PinType::from_name(&t))
+                else {
+                    continue;
+                };
+                let at = utils::at(pin).unwrap();
+                let point: Array1<f64> = Shape::transform(symbol, &at);
+                let Some(node) = netlist
+                    .node_name(&Point::new(point[0], point[1]))
+                    .filter(|n| n != "NC")
+                else {
+                    continue;
+                };
+                let net = names
+                    .get(&(index, node.clone()))
+                    .cloned()
+                    .unwrap_or_else(|| node.clone());
+                //without the hierarchy the other sheets are not known.
+                if project.sheets.len() == 1 && netlist.is_external(&node) {
+                    nets.open.insert(net.clone());
+                }
+                let number: String = pin.value(el::PIN_NUMBER).unwrap();
+                let net_pin = NetPin {
+                    name: format!("{}:{}", reference, number),
+                    uuid: pin_uuid(symbol, &number),
+                    pin_type,
+                    at: point,
+                    sheet: file.clone(),
+                };
+                if is_power(symbol) {
+                    if pin_type == PinType::PowerIn && !nets.power.iter().any(|(n, _)| *n == net) {
+                        nets.power.push((net, net_pin));
+                        continue;
+                    }
+                    if pin_type != PinType::PowerOut {
+                        continue;
+                    }
+                }
+                let pins = nets.pins.entry(net).or_default();
+                //the common pins of the units are used once.
+                if !pins.iter().any(|p| p.name == net_pin.name) {
+                    pins.push(net_pin);
+                }
+            }
+        }
+    }
+    nets
+}
+
+///Check the pin types on the nets with the pin conflict matrix.
+///
+///The inputs must be driven, unless the net is connected to a sheet which
+///is not checked.
+fn pin_types(nets: &Nets, matrix: &PinMatrix) -> Vec<ErcItem> {
+    let mut results = Vec::new();
+    for net in nets.pins.keys().sorted() {
+        let pins = &nets.pins[net];
+        for (index, pin) in pins.iter().enumerate() {
+            for other in &pins[index + 1..] {
+                let id = match matrix.get(pin.pin_type, other.pin_type) {
+                    PinConflict::Ok => continue,
+                    PinConflict::Warning => ErcType::PinToPinWarning,
+                    PinConflict::Error => ErcType::PinToPinError,
+                };
+                results.push(
+                    ErcItem::from(
+                        id,
+                        &pin.name,
+                        pin.at.clone(),
+                        format!(
+                            "Pin {} ({}) is connected to {} ({}) on net {}",
+                            pin.name, pin.pin_type, other.name, other.pin_type, net
+                        ),
+                    )
+                    .with_uuid(&pin.uuid)
+                    .with_sheet(&pin.sheet),
+                );
+            }
+        }
+        if nets.open.contains(net) {
+            continue;
+        }
+        if !pins.iter().any(|p| p.pin_type.is_driver()) {
+            if let Some(pin) = pins.iter().find(|p| p.pin_type == PinType::Input) {
+                results.push(
+                    ErcItem::from(
+                        ErcType::PinNotDriven,
+                        &pin.name,
+                        pin.at.clone(),
+                        format!("Input pin {} on net {} is not driven", pin.name, net),
+                    )
+                    .with_uuid(&pin.uuid)
+                    .with_sheet(&pin.sheet),
+                );
+            }
+        }
+        //the nets of the power symbols are reported by the power flag check.
+        let power = nets.power.iter().any(|(n, _)| n == net);
+        if !power && !pins.iter().any(|p| p.pin_type == PinType::PowerOut) {
+            if let Some(pin) = pins.iter().find(|p| p.pin_type == PinType::PowerIn) {
+                results.push(
+                    ErcItem::from(
+                        ErcType::PowerPinNotDriven,
+                        &pin.name,
+                        pin.at.clone(),
+                        format!(
+                            "Power input pin {} on net {} is not driven by a power output",
+                            pin.name, net
+                        ),
+                    )
+                    .with_uuid(&pin.uuid)
+                    .with_sheet(&pin.sheet),
+                );
+            }
+        }
+    }
+    results
+}
+
+///Check that the nets of the power symbols have a power flag or a power output.
+fn power_flags(nets: &Nets) -> Vec<ErcItem> {
+    nets.power
+        .iter()
+        .filter(|(net, _)| {
+            !nets.open.contains(net)
+                && !nets
+                    .pins
+                    .get(net)
+                    .is_some_and(|pins| pins.iter().any(|p| p.pin_type == PinType::PowerOut))
+        })
+        .map(|(net, pin)| {
+            ErcItem::from(
+                ErcType::MissingPowerFlag,
+                net,
+                pin.at.clone(),
+                format!(
+                    "Power net {} is not driven by a power flag or a power output",
+                    net
+                ),
+            )
+            .with_uuid(&pin.uuid)
+            .with_sheet(&pin.sheet)
+        })
+        .collect()
+}
+
+///Test if the point is on the line from a to b.
+fn on_line(point: &Point, a: &Point, b: &Point) -> bool {
+    let cross = (b.x - a.x) * (point.y - a.y) - (b.y - a.y) * (point.x - a.x);
+    cross.abs() < 1e-6
+        && point.x >= a.x.min(b.x)
+        && point.x <= a.x.max(b.x)
+        && point.y >= a.y.min(b.y)
+        && point.y <= a.y.max(b.y)
+}
+
+///Check for labels and wire ends that are not connected.
+///
+///The labels have the uuid of the label for the exclusions.
+fn dangling(document: &SexpTree) -> Result<Vec<ErcItem>, Error> {
+    let positions = Netlist::positions(document.root()?)?;
+    let wires: Vec<(Point, Point)> = positions
+        .iter()
+        .filter_map(|(_, p)| match p {
+            NodePositions::Wire(a, b) => Some((*a, *b)),
+            _ => None,
+        })
+        .collect();
+    let on_bus = |point: &Point| {
+        positions.iter().any(|(_, p)| match p {
+            NodePositions::Bus(a, b) => on_line(point, a, b),
+            NodePositions::BusEntry(a, b) => a == point || b == point,
+            _ => false,
+        })
+    };
+    let on_pin = |point: &Point| {
+        positions.iter().any(|(p, position)| {
+            p == point
+                && matches!(
+                    position,
+                    NodePositions::Pin(..) | NodePositions::SheetPin(..)
+                )
+        })
+    };
+    let mut results = Vec::new();
+    for (point, position) in &positions {
+        let label = match position {
+            NodePositions::Label(_, l)
+            | NodePositions::GlobalLabel(_, l)
+            | NodePositions::HierarchicalLabel(_, l) => l,
+            _ => continue,
+        };
+        if !wires.iter().any(|(a, b)| on_line(point, a, b)) && !on_bus(point) && !on_pin(point) {
+            let name: String = label.get(0).unwrap();
+            let uuid: String = label.value("uuid").unwrap_or_default();
+            results.push(
+                ErcItem::from(
+                    ErcType::LabelDangling,
+                    &name,
+                    arr1(&[point.x, point.y]),
+                    format!("Label {} is not connected", name),
+                )
+                .with_uuid(&uuid),
+            );
+        }
+    }
+    for (index, (start, end)) in wires.iter().enumerate() {
+        for point in [start, end] {
+            let connected = wires
+                .iter()
+                .enumerate()
+                .any(|(i, (a, b))| i != index && on_line(point, a, b))
+                || on_bus(point)
+                || positions.iter().any(|(p, position)| {
+                    p == point
+                        && !matches!(position, NodePositions::Wire(..) | NodePositions::Bus(..))
+                });
+            if !connected {
+                results.push(ErcItem::from(
+                    ErcType::WireDangling,
+                    &format!("{}", point),
+                    arr1(&[point.x, point.y]),
+                    String::from("Wire end is not connected"),
+                ));
+            }
+        }
+    }
+    Ok(results)
+}
+
+///Check for global labels in all sheets which differ only in case.
+fn similar_labels(project: &Project) -> Vec<ErcItem> {
+    let mut names: HashMap<String, Vec<(String, Array1<f64>, String)>> = HashMap::new();
+    for sheet in &project.sheets {
+        let Ok(root) = sheet.tree.root() else {
+            continue;
+        };
+        for label in root.query(el::GLOBAL_LABEL) {
+            let name: String = label.get(0).unwrap();
+            let entry = names.entry(name.to_lowercase()).or_default();
+            if !entry.iter().any(|(n, _, _)| *n == name) {
+                entry.push((
+                    name,
+                    utils::at(label).unwrap(),
+                    sheet.file.display().to_string(),
+                ));
+            }
+        }
+    }
+    names
+        .into_iter()
+        .sorted_by(|a, b| a.0.cmp(&b.0))
+        .filter(|(_, labels)| labels.len() > 1)
+        .map(|(_, labels)| {
+            let names = labels.iter().map(|(n, _, _)| n.to_string()).join(", ");
+            ErcItem::from(
+                ErcType::SimilarLabels,
+                &labels[0].0,
+                labels[0].1.clone(),
+                format!("Global labels differ only in case: {}", names),
+            )
+            .with_sheet(&labels[0].2)
+        })
+        .collect()
+}
+
+///Check for references which are used more than once for the same unit.
+fn duplicates(elements: &HashMap<String, Vec<&Sexp>>) -> Vec<ErcItem> {
+    let mut results = Vec::new();
+    for reference in elements.keys().sorted() {
+        if reference.contains('?') {
+            continue;
+        }
+        let mut units: Vec<usize> = Vec::new();
+        for symbol in &elements[reference] {
+            let unit: usize = symbol.value(el::SYMBOL_UNIT).unwrap();
+            if units.contains(&unit) {
+                results.push(
+                    ErcItem::from(
+                        ErcType::DuplicateReference,
+                        reference,
+                        utils::at(symbol).unwrap(),
+                        format!("Reference {} is used more than once", reference),
+                    )
+                    .with_uuid(&uuid(symbol)),
+                );
+            } else {
+                units.push(unit);
+            }
+        }
+    }
+    results
+}
+
+///Check for references which are used in more than one sheet.
+fn sheet_references(project: &Project) -> Vec<ErcItem> {
+    let mut results = Vec::new();
+    let mut used: HashMap<(String, usize), usize> = HashMap::new();
+    for (index, sheet) in project.sheets.iter().enumerate() {
+        let Ok(root) = sheet.tree.root() else {
+            continue;
+        };
+        for symbol in root.query(el::SYMBOL) {
+            let lib_id: String = symbol.value(el::LIB_ID).unwrap();
+            if lib_id.starts_with("power:") {
+                continue;
+            }
+            let Some(reference) = project.reference(index, symbol) else {
+                continue;
+            };
+            if reference.contains('?') || reference.starts_with('#') {
+                continue;
+            }
+            let unit: usize = symbol.value(el::SYMBOL_UNIT).unwrap();
+            match used.get(&(reference.clone(), unit)) {
+                //the duplicates on the same sheet are found by the sheet checks.
+                Some(other) if *other != index => {
+                    results.push(
+                        ErcItem::from(
+                            ErcType::DuplicateReference,
+                            &reference,
+                            utils::at(symbol).unwrap(),
+                            format!(
+                                "Reference {} is used in {} and {}",
+                                reference, project.sheets[*other].path, sheet.path
+                            ),
+                        )
+                        .with_uuid(&uuid(symbol))
+                        .with_sheet(&sheet.file.display().to_string()),
+                    );
+                }
+                Some(_) => {}
+                None => {
+                    used.insert((reference, unit), index);
+                }
+            }
+        }
+    }
+    results
+}
+
+///Check for symbols with overlapping outlines.
+fn overlaps(document: &SexpTree, elements: &HashMap<String, Vec<&Sexp>>) -> Vec<ErcItem> {
+    let root = document.root().unwrap();
+    let mut outlines: Vec<(String, &Sexp, Array2<f64>)> = Vec::new();
+    for reference in elements.keys().sorted() {
+        for symbol in &elements[reference] {
+            let lib_id: String = symbol.value(el::LIB_ID).unwrap();
+            let Some(libsymbol) = utils::get_library(root, &lib_id) else {
+                continue;
+            };
+            let Ok(bounds) = symbol.bounds(libsymbol) else {
+                continue;
+            };
+            if bounds.nrows() != 2 {
+                continue;
+            }
+            let bounds: Array2<f64> = Shape::transform(*symbol, &bounds);
+            outlines.push((reference.to_string(), symbol, bounds));
+        }
+    }
+    let range = |b: &Array2<f64>, axis: usize| {
+        (
+            b[[0, axis]].min(b[[1, axis]]),
+            b[[0, axis]].max(b[[1, axis]]),
+        )
+    };
+    let mut results = Vec::new();
+    for (index, (reference, symbol, bounds)) in outlines.iter().enumerate() {
+        for (other, _, other_bounds) in &outlines[index + 1..] {
+            let overlap = (0..2).all(|axis| {
+                let (a_min, a_max) = range(bounds, axis);
+                let (b_min, b_max) = range(other_bounds, axis);
+                a_min < b_max && b_min < a_max
+            });
+            if overlap {
+                results.push(
+                    ErcItem::from(
+                        ErcType::OverlappingSymbols,
+                        reference,
+                        utils::at(symbol).unwrap(),
+                        format!("Symbol {} overlaps {}", reference, other),
+                    )
+                    .with_uuid(&uuid(symbol)),
+                );
+            }
+        }
+    }
+    results
+}
+
+///Check for nets with more than one label.
+fn labels(netlist: &Netlist) -> Vec<ErcItem> {
+    netlist
+        .label_conflicts()
+        .into_iter()
+        .map(|(name, names, point)| {
+            ErcItem::from(
+                ErcType::ConflictingLabels,
+                &name,
+                arr1(&[point.x, point.y]),
+                format!("Net has the labels {}, {} is used", names.join(", "), name),
+            )
+        })
+        .collect()
+}
+
 ///Check if all Values are the same for all symbols units.
 fn values(elements: &HashMap<String, Vec<&Sexp>>) -> Vec<ErcItem> {
     let mut results = Vec::new();
@@ -221,18 +1043,21 @@ fn values(elements: &HashMap<String, Vec<&Sexp>>) -> Vec<ErcItem> {
                 != <Sexp as SexpProperty<String>>::property(symbol, el::PROPERTY_VALUE).unwrap()
             {
                 let at = utils::at(symbols.first().unwrap()).unwrap();
-                results.push(ErcItem::from(
-                    ErcType::ValuesDiffer,
-                    &<Sexp as SexpProperty<String>>::property(symbol, el::PROPERTY_REFERENCE)
-                        .unwrap(),
-                    at.clone(),
-                    format!(
-                        "Symbol values differ: {}:{}",
-                        value,
-                        <Sexp as SexpProperty<String>>::property(symbol, el::PROPERTY_VALUE)
-                            .unwrap()
-                    ),
-                ));
+                results.push(
+                    ErcItem::from(
+                        ErcType::ValuesDiffer,
+                        &<Sexp as SexpProperty<String>>::property(symbol, el::PROPERTY_REFERENCE)
+                            .unwrap(),
+                        at.clone(),
+                        format!(
+                            "Symbol values differ: {}:{}",
+                            value,
+                            <Sexp as SexpProperty<String>>::property(symbol, el::PROPERTY_VALUE)
+                                .unwrap()
+                        ),
+                    )
+                    .with_uuid(&uuid(symbol)),
+                );
             }
         }
     }
@@ -246,16 +1071,27 @@ fn references(document: &SexpTree, elements: &HashMap<String, Vec<&Sexp>>) -> Ve
         let at = utils::at(symbols.first().unwrap()).unwrap();
         let lib_id: String = symbols.first().unwrap().value(el::LIB_ID).unwrap();
         if reference.contains('?') {
-            results.push(ErcItem::from(
-                ErcType::NoReference,
-                reference,
-                at.clone(),
-                String::from("no reference for symbol"),
-            ));
+            results.push(
+                ErcItem::from(
+                    ErcType::NoReference,
+                    reference,
+                    at.clone(),
+                    String::from("no reference for symbol"),
+                )
+                .with_uuid(&uuid(symbols.first().unwrap())),
+            );
         }
         let Some(libsymbol) = utils::get_library(document.root().unwrap(), &lib_id) else {
-            //TODO Library Symbol not found
-            break;
+            results.push(
+                ErcItem::from(
+                    ErcType::MissingLibrarySymbol,
+                    reference,
+                    at.clone(),
+                    format!("library symbol {} not found", lib_id),
+                )
+                .with_uuid(&uuid(symbols.first().unwrap())),
+            );
+            continue;
         };
         let parts: usize = libsymbol
             .query(el::SYMBOL)
@@ -273,12 +1109,15 @@ fn references(document: &SexpTree, elements: &HashMap<String, Vec<&Sexp>>) -> Ve
             .count();
         if parts != symbols.len() {
             //not all parts on schema
-            results.push(ErcItem::from(
-                ErcType::NotAllParts,
-                reference,
-                at.clone(),
-                String::from("not all unit for symbol in schema"),
-            ));
+            results.push(
+                ErcItem::from(
+                    ErcType::NotAllParts,
+                    reference,
+                    at.clone(),
+                    String::from("not all unit for symbol in schema"),
+                )
+                .with_uuid(&uuid(symbols.first().unwrap())),
+            );
         }
     }
     results
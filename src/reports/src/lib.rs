@@ -3,6 +3,10 @@ pub mod bom;
 pub mod drc;
 pub mod erc;
 pub mod mouser;
+pub mod pinmap;
+pub mod position;
+pub mod report;
+pub mod variant;
 
 #[derive(thiserror::Error, Debug, Clone)]
 pub enum Error {
@@ -16,6 +20,12 @@ pub enum Error {
     YamlError(String, String),
     #[error("Unable to load partlist: {0} ({1}).")]
     PartlistError(String, String),
+    #[error("Can not load project file: {0} ({1}).")]
+    ProjectError(String, String),
+    #[error("Invalid pin map: {0}.")]
+    PinMap(String),
+    #[error("Can not create the netlist: {0}.")]
+    Netlist(String),
 }
 
 impl std::convert::From<sexp::Error> for Error {
@@ -23,3 +33,9 @@ impl std::convert::From<sexp::Error> for Error {
         Error::SexpError(err.to_string())
     }
 }
+
+impl std::convert::From<simulation::Error> for Error {
+    fn from(err: simulation::Error) -> Self {
+        Error::Netlist(err.to_string())
+    }
+}
@@ -0,0 +1,164 @@
+//! The pin conflict matrix for the ERC.
+//!
+//! The matrix defines which electrical pin types can be connected to the same
+//! net, like in KiCad: two outputs on one net are an error, an output and an
+//! unspecified pin a warning. The defaults are the same as the KiCad defaults
+//! and can be changed for single pairs or loaded from the `pin_map` of a
+//! KiCad project file.
+//!
+//! # Example:
+//!
+//! use reports::pinmap::{PinConflict, PinMatrix, PinType};
+//!
+//! let mut matrix = PinMatrix::default();
+//! matrix.set(PinType::Output, PinType::TriState, PinConflict::Error);
+//! assert_eq!(PinConflict::Error, matrix.get(PinType::TriState, PinType::Output));
+use std::fmt;
+
+use crate::Error;
+
+///The electrical type of a symbol pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PinType {
+    Input,
+    Output,
+    Bidirectional,
+    TriState,
+    Passive,
+    Free,
+    Unspecified,
+    PowerIn,
+    PowerOut,
+    OpenCollector,
+    OpenEmitter,
+    NoConnect,
+}
+
+///The pin types in the order of the KiCad pin map.
+const PIN_TYPES: [(PinType, &str); 12] = [
+    (PinType::Input, "input"),
+    (PinType::Output, "output"),
+    (PinType::Bidirectional, "bidirectional"),
+    (PinType::TriState, "tri_state"),
+    (PinType::Passive, "passive"),
+    (PinType::Free, "free"),
+    (PinType::Unspecified, "unspecified"),
+    (PinType::PowerIn, "power_in"),
+    (PinType::PowerOut, "power_out"),
+    (PinType::OpenCollector, "open_collector"),
+    (PinType::OpenEmitter, "open_emitter"),
+    (PinType::NoConnect, "no_connect"),
+];
+
+impl PinType {
+    ///The name of the pin type in the schema file.
+    pub fn name(&self) -> &'static str {
+        PIN_TYPES[self.index()].1
+    }
+
+    ///Find the pin type by name, like `power_in`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        PIN_TYPES.iter().find(|(_, n)| *n == name).map(|(t, _)| *t)
+    }
+
+    ///The pin can drive an input pin.
+    pub fn is_driver(&self) -> bool {
+        matches!(
+            self,
+            PinType::Output
+                | PinType::Bidirectional
+                | PinType::TriState
+                | PinType::Passive
+                | PinType::PowerOut
+                | PinType::OpenCollector
+                | PinType::OpenEmitter
+        )
+    }
+
+    fn index(&self) -> usize {
+        PIN_TYPES.iter().position(|(t, _)| t == self).unwrap()
+    }
+}
+
+impl fmt::Display for PinType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+///The result when two pin types are connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinConflict {
+    Ok,
+    Warning,
+    Error,
+}
+
+use PinConflict::{Error as E, Ok as O, Warning as W};
+
+///The KiCad default pin map.
+const DEFAULT_MAP: [[PinConflict; 12]; 12] = [
+    //I  O  Bi 3S Pas Free UnS PwrI PwrO OC OE NC
+    [O, O, O, O, O, O, W, O, O, O, O, E], //Input
+    [O, E, O, W, O, O, W, O, E, E, E, E], //Output
+    [O, O, O, O, O, O, W, O, W, O, W, E], //Bidirectional
+    [O, W, O, O, O, O, W, W, E, W, W, E], //TriState
+    [O, O, O, O, O, O, W, O, O, O, O, E], //Passive
+    [O, O, O, O, O, O, O, O, O, O, O, E], //Free
+    [W, W, W, W, W, O, W, W, W, W, W, E], //Unspecified
+    [O, O, O, W, O, O, W, O, O, O, O, E], //PowerIn
+    [O, E, W, E, O, O, W, O, E, E, E, E], //PowerOut
+    [O, E, O, W, O, O, W, O, E, O, O, E], //OpenCollector
+    [O, E, W, W, O, O, W, O, E, O, O, E], //OpenEmitter
+    [E, E, E, E, E, E, E, E, E, E, E, E], //NoConnect
+];
+
+///The pin conflict matrix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PinMatrix {
+    map: [[PinConflict; 12]; 12],
+}
+
+impl Default for PinMatrix {
+    fn default() -> Self {
+        Self { map: DEFAULT_MAP }
+    }
+}
+
+impl PinMatrix {
+    ///The conflict when the two pin types are on the same net.
+    pub fn get(&self, a: PinType, b: PinType) -> PinConflict {
+        self.map[a.index()][b.index()]
+    }
+
+    ///Change the conflict for two pin types, the matrix is symmetric.
+    pub fn set(&mut self, a: PinType, b: PinType, conflict: PinConflict) {
+        self.map[a.index()][b.index()] = conflict;
+        self.map[b.index()][a.index()] = conflict;
+    }
+
+    ///Load the matrix from the KiCad `pin_map`.
+    ///
+    ///The values are 0 for no conflict, 1 for a warning and 2 for an error.
+    pub fn from_pin_map(pin_map: &[Vec<u8>]) -> Result<Self, Error> {
+        if pin_map.len() != PIN_TYPES.len() || pin_map.iter().any(|r| r.len() != PIN_TYPES.len()) {
+            return Err(Error::PinMap(format!(
+                "{} rows with {} values expected",
+                PIN_TYPES.len(),
+                PIN_TYPES.len()
+            )));
+        }
+        let mut matrix = Self::default();
+        for (a, row) in pin_map.iter().enumerate() {
+            for (b, value) in row.iter().enumerate() {
+                matrix.map[a][b] = match value {
+                    0 => PinConflict::Ok,
+                    1 => PinConflict::Warning,
+                    2 => PinConflict::Error,
+                    _ => return Err(Error::PinMap(format!("invalid value {}", value))),
+                };
+            }
+        }
+        Ok(matrix)
+    }
+}
@@ -0,0 +1,147 @@
+//! Create the pick and place file for the board.
+//!
+//! The positions of the footprints are relative to the auxiliary origin of
+//! the board, with the y axis upwards like the KiCad position files. The
+//! footprints with the `exclude_from_pos_files` or the `board_only`
+//! attribute are skipped. With an assembly [`Variant`] the parts that are
+//! not populated are skipped and the `Value` and `Footprint` substitutes
+//! are used.
+//!
+//! # Example:
+//!
+//! use reports::position::{positions, to_csv};
+//!
+//! let positions = positions("files/summe/summe.kicad_pcb", None).unwrap();
+//! println!("{}", to_csv(&positions));
+use sexp::{el, Sexp, SexpParser, SexpTree, SexpValueQuery, SexpValuesQuery};
+use simulation::natural_cmp;
+
+use crate::{
+    drc::{footprint_field, numbers},
+    variant::{Assembly, Variant},
+    Error,
+};
+
+///The position of a footprint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub reference: String,
+    pub value: String,
+    ///The footprint name without the library.
+    pub package: String,
+    ///The position in mm relative to the auxiliary origin.
+    pub x: f64,
+    pub y: f64,
+    pub rotation: f64,
+    ///The footprint is on the bottom layer.
+    pub bottom: bool,
+}
+
+/// Get the positions of the footprints.
+///
+/// # Arguments
+///
+/// * `document` - The path to the board file.
+/// * `variant`  - The assembly variant.
+/// * `return`   - The positions sorted by reference.
+pub fn positions(document: &str, variant: Option<&Variant>) -> Result<Vec<Position>, Error> {
+    let doc = match SexpParser::load(document) {
+        Ok(doc) => doc,
+        Err(err) => return Err(Error::IoError(document.to_string(), err.to_string())),
+    };
+    let tree = SexpTree::from(doc.iter())?;
+    Ok(positions_from(tree.root()?, variant))
+}
+
+/// Get the positions of the footprints of the board.
+pub fn positions_from(board: &Sexp, variant: Option<&Variant>) -> Vec<Position> {
+    let origin = board
+        .query("setup")
+        .next()
+        .and_then(|setup| setup.query("aux_axis_origin").next())
+        .map(numbers)
+        .unwrap_or_default();
+    let (ox, oy) = (
+        origin.first().copied().unwrap_or(0.0),
+        origin.get(1).copied().unwrap_or(0.0),
+    );
+    let mut positions: Vec<Position> = Vec::new();
+    for footprint in board.query(el::FOOTPRINT) {
+        let attributes: Vec<String> = footprint
+            .query("attr")
+            .next()
+            .map(|attr| attr.values())
+            .unwrap_or_default();
+        if attributes
+            .iter()
+            .any(|a| a == "exclude_from_pos_files" || a == "board_only")
+        {
+            continue;
+        }
+        let reference =
+            footprint_field(footprint, el::PROPERTY_REFERENCE, "reference").unwrap_or_default();
+        if reference.is_empty() || reference.starts_with('#') {
+            continue;
+        }
+        let Some(at) = footprint.query(el::AT).next().map(numbers) else {
+            continue;
+        };
+        let lib_id: String = footprint.get(0).unwrap_or_default();
+        let layer: String = footprint.value(el::LAYER).unwrap_or_default();
+        let mut position = Position {
+            value: footprint_field(footprint, el::PROPERTY_VALUE, "value").unwrap_or_default(),
+            package: match lib_id.split_once(':') {
+                Some((_, name)) => name.to_string(),
+                None => lib_id,
+            },
+            x: at.first().copied().unwrap_or(0.0) - ox,
+            y: oy - at.get(1).copied().unwrap_or(0.0),
+            rotation: at.get(2).copied().unwrap_or(0.0),
+            bottom: layer == "B.Cu",
+            reference,
+        };
+        if let Some(variant) = variant {
+            let field = footprint_field(footprint, &variant.field(), &variant.field());
+            let dnp = attributes.iter().any(|a| a == "dnp");
+            match variant.assembly(&position.reference, field.as_deref(), dnp) {
+                Assembly::Dnp => continue,
+                Assembly::Fitted => {}
+                Assembly::Substituted(fields) => {
+                    for (name, value) in fields {
+                        match name.as_str() {
+                            el::PROPERTY_VALUE => position.value = value,
+                            "Footprint" => {
+                                position.package = match value.split_once(':') {
+                                    Some((_, name)) => name.to_string(),
+                                    None => value,
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        positions.push(position);
+    }
+    positions.sort_by(|a, b| natural_cmp(&a.reference, &b.reference));
+    positions
+}
+
+///Write the positions as CSV, like the KiCad position file.
+pub fn to_csv(positions: &[Position]) -> String {
+    let mut csv = String::from("Ref,Val,Package,PosX,PosY,Rot,Side\n");
+    for position in positions {
+        csv.push_str(&format!(
+            "\"{}\",\"{}\",\"{}\",{:.4},{:.4},{:.4},{}\n",
+            position.reference,
+            position.value,
+            position.package,
+            position.x,
+            position.y,
+            position.rotation,
+            if position.bottom { "bottom" } else { "top" }
+        ));
+    }
+    csv
+}
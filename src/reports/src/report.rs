@@ -0,0 +1,396 @@
+//! A common report for the findings of the ERC, the DRC and the BOM.
+//!
+//! The results of the checks are converted to a [`Report`] with the rule,
+//! the severity, the file and the locations of every finding. The report is
+//! written as JSON with a stable schema, as JUnit XML or as SARIF, the CI
+//! servers use the last two to annotate the merge requests.
+//!
+//! The coordinates are in mm on the schematic sheet or on the board, SARIF
+//! has no regions for them and gets them as properties of the result.
+//!
+//! # Example:
+//!
+//! use std::path::Path;
+//! use reports::{erc::erc, report::Report};
+//!
+//! let input = Path::new("files/summe/summe.kicad_sch");
+//! let report = Report::erc(input, &erc(input).unwrap());
+//! println!("{}", report.to_sarif());
+use std::{fmt, path::Path};
+
+use crate::{
+    bom::BomItem,
+    drc::{DrcItems, DrcResult},
+    erc::{ErcItem, Severity},
+};
+
+///The version of the JSON schema.
+pub const SCHEMA_VERSION: usize = 1;
+
+///The SARIF version of the output.
+const SARIF_VERSION: &str = "2.1.0";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+///The check that created the findings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    Erc,
+    Drc,
+    Bom,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Check::Erc => write!(f, "erc"),
+            Check::Drc => write!(f, "drc"),
+            Check::Bom => write!(f, "bom"),
+        }
+    }
+}
+
+///A location of a finding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub description: String,
+    ///The position in mm, when available.
+    pub position: Option<(f64, f64)>,
+    ///The uuid of the item, empty when not available.
+    pub uuid: String,
+}
+
+impl From<&DrcItems> for Location {
+    fn from(item: &DrcItems) -> Self {
+        Location {
+            description: item.description.clone(),
+            position: Some(item.pos),
+            uuid: item.uuid.clone(),
+        }
+    }
+}
+
+///A single finding of a check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    ///The rule name, like `pin_not_connected`.
+    pub rule: String,
+    pub severity: Severity,
+    pub message: String,
+    ///The checked file.
+    pub file: String,
+    ///The symbol or footprint references, empty when not available.
+    pub reference: String,
+    pub locations: Vec<Location>,
+}
+
+///The findings of a check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub check: Check,
+    ///The checked file.
+    pub source: String,
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    ///Create the report from the ERC results, ignored items are skipped.
+    ///
+    ///The file of a finding is the sheet with the item, the source when the
+    ///sheet is not known.
+    pub fn erc(source: &Path, items: &[ErcItem]) -> Self {
+        let source = source.display().to_string();
+        let findings = items
+            .iter()
+            .filter(|item| item.severity != Severity::Ignore)
+            .map(|item| Finding {
+                rule: item.id.rule().to_string(),
+                severity: item.severity,
+                message: item.description.clone(),
+                file: if item.sheet.is_empty() {
+                    source.clone()
+                } else {
+                    item.sheet.clone()
+                },
+                reference: item.reference.clone(),
+                locations: vec![Location {
+                    description: item.reference.clone(),
+                    position: match item.at.as_slice() {
+                        Some([x, y, ..]) => Some((*x, *y)),
+                        _ => None,
+                    },
+                    uuid: item.uuid.clone(),
+                }],
+            })
+            .collect();
+        Self {
+            check: Check::Erc,
+            source,
+            findings,
+        }
+    }
+
+    ///Create the report from the DRC results.
+    pub fn drc(result: &DrcResult) -> Self {
+        let findings = result
+            .errors
+            .iter()
+            .filter(|item| item.severity != "ignore")
+            .map(|item| Finding {
+                rule: item.drc_type.clone(),
+                severity: Severity::from_name(&item.severity).unwrap_or(Severity::Error),
+                message: item.description.clone(),
+                file: result.source.clone(),
+                reference: String::new(),
+                locations: item.items.iter().map(Location::from).collect(),
+            })
+            .collect();
+        Self {
+            check: Check::Drc,
+            source: result.source.clone(),
+            findings,
+        }
+    }
+
+    ///Create the report for the BOM items not found in the partlist.
+    pub fn bom(source: &Path, missing: &[BomItem]) -> Self {
+        let source = source.display().to_string();
+        let findings = missing
+            .iter()
+            .map(|item| Finding {
+                rule: String::from("missing_part"),
+                severity: Severity::Warning,
+                message: format!(
+                    "{} ({}) not found in the partlist",
+                    item.value, item.footprint
+                ),
+                file: source.clone(),
+                reference: item.references.join(" "),
+                locations: Vec::new(),
+            })
+            .collect();
+        Self {
+            check: Check::Bom,
+            source,
+            findings,
+        }
+    }
+
+    ///Count the findings with the severity.
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .count()
+    }
+
+    ///Write the report as JSON.
+    ///
+    ///The schema is versioned with `version`, fields are only added within
+    ///a version.
+    pub fn to_json(&self) -> String {
+        let mut findings = json::JsonValue::new_array();
+        for finding in &self.findings {
+            let mut locations = json::JsonValue::new_array();
+            for location in &finding.locations {
+                let (x, y) = match location.position {
+                    Some((x, y)) => (json::JsonValue::from(x), json::JsonValue::from(y)),
+                    None => (json::JsonValue::Null, json::JsonValue::Null),
+                };
+                locations
+                    .push(json::object! {
+                        description: location.description.clone(),
+                        x: x,
+                        y: y,
+                        uuid: location.uuid.clone(),
+                    })
+                    .unwrap();
+            }
+            findings
+                .push(json::object! {
+                    rule: finding.rule.clone(),
+                    severity: finding.severity.to_string(),
+                    message: finding.message.clone(),
+                    file: finding.file.clone(),
+                    reference: finding.reference.clone(),
+                    locations: locations,
+                })
+                .unwrap();
+        }
+        json::object! {
+            version: SCHEMA_VERSION,
+            check: self.check.to_string(),
+            source: self.source.clone(),
+            errors: self.count(Severity::Error),
+            warnings: self.count(Severity::Warning),
+            findings: findings,
+        }
+        .pretty(2)
+    }
+
+    ///Write the report as JUnit XML.
+    ///
+    ///Every finding is a test case, the errors are failures and the
+    ///warnings are written to the output of the test case. A report
+    ///without findings has a single passing test case.
+    pub fn to_junit(&self) -> String {
+        let errors = self.count(Severity::Error);
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+            self.check,
+            self.findings.len().max(1),
+            errors
+        ));
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"0\">\n",
+            escape(&format!("{} {}", self.check, self.source)),
+            self.findings.len().max(1),
+            errors
+        ));
+        if self.findings.is_empty() {
+            xml.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}\" file=\"{}\"/>\n",
+                self.check,
+                self.check,
+                escape(&self.source)
+            ));
+        }
+        for finding in &self.findings {
+            let name = if finding.reference.is_empty() {
+                finding.rule.clone()
+            } else {
+                format!("{} {}", finding.rule, finding.reference)
+            };
+            xml.push_str(&format!(
+                "    <testcase classname=\"{}.{}\" name=\"{}\" file=\"{}\">\n",
+                self.check,
+                escape(&finding.rule),
+                escape(&name),
+                escape(&finding.file)
+            ));
+            let text = std::iter::once(finding.message.clone())
+                .chain(finding.locations.iter().map(location))
+                .collect::<Vec<String>>()
+                .join("\n");
+            if finding.severity == Severity::Error {
+                xml.push_str(&format!(
+                    "      <failure type=\"{}\" message=\"{}\">{}</failure>\n",
+                    finding.severity,
+                    escape(&finding.message),
+                    escape(&text)
+                ));
+            } else {
+                xml.push_str(&format!(
+                    "      <system-out>{}: {}</system-out>\n",
+                    finding.severity,
+                    escape(&text)
+                ));
+            }
+            xml.push_str("    </testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+
+    ///Write the report as SARIF.
+    pub fn to_sarif(&self) -> String {
+        let mut rules = json::JsonValue::new_array();
+        let mut rule_ids: Vec<&str> = Vec::new();
+        for finding in &self.findings {
+            if !rule_ids.contains(&finding.rule.as_str()) {
+                rule_ids.push(&finding.rule);
+                rules
+                    .push(json::object! {
+                        id: finding.rule.clone(),
+                    })
+                    .unwrap();
+            }
+        }
+        let mut results = json::JsonValue::new_array();
+        for finding in &self.findings {
+            let mut logical = json::JsonValue::new_array();
+            if !finding.reference.is_empty() {
+                logical
+                    .push(json::object! { name: finding.reference.clone() })
+                    .unwrap();
+            }
+            let mut positions = json::JsonValue::new_array();
+            for location in &finding.locations {
+                let position = match location.position {
+                    Some((x, y)) => json::array![x, y],
+                    None => json::JsonValue::Null,
+                };
+                positions
+                    .push(json::object! {
+                        description: location.description.clone(),
+                        position: position,
+                        uuid: location.uuid.clone(),
+                    })
+                    .unwrap();
+            }
+            let mut result = json::object! {
+                ruleId: finding.rule.clone(),
+                ruleIndex: rule_ids.iter().position(|r| *r == finding.rule).unwrap(),
+                level: match finding.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                    Severity::Ignore => "none",
+                },
+                message: json::object! { text: finding.message.clone() },
+                locations: json::array![json::object! {
+                    physicalLocation: json::object! {
+                        artifactLocation: json::object! { uri: finding.file.clone() },
+                    },
+                }],
+                properties: json::object! { items: positions },
+            };
+            if !logical.is_empty() {
+                result["locations"][0]["logicalLocations"] = logical;
+            }
+            results.push(result).unwrap();
+        }
+        let driver = json::object! {
+            name: format!("elektron {}", self.check),
+            rules: rules,
+        };
+        let run = json::object! {
+            tool: json::object! { driver: driver },
+            results: results,
+        };
+        json::object! {
+            "$schema": SARIF_SCHEMA,
+            version: SARIF_VERSION,
+            runs: json::array![run],
+        }
+        .pretty(2)
+    }
+}
+
+///Format the location for the text output.
+fn location(location: &Location) -> String {
+    let mut text = location.description.clone();
+    if let Some((x, y)) = location.position {
+        text.push_str(&format!(" ({}, {})", x, y));
+    }
+    if !location.uuid.is_empty() {
+        text.push_str(&format!(" [{}]", location.uuid));
+    }
+    text
+}
+
+///Escape the text for XML attributes and content.
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            '\n' => result.push_str("&#10;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
@@ -0,0 +1,186 @@
+//! Assembly variants of a board.
+//!
+//! The same board is often built in several variants: some parts are not
+//! populated or are replaced with other values. A [`Variant`] marks the parts
+//! as fitted, not populated or substituted. The variants are defined in the
+//! `variants` section of the KiCad project file:
+//!
+//! "variants": {
+//!   "lite": {
+//!     "dnp": ["R2", "C3"],
+//!     "fitted": ["R5"],
+//!     "substitutes": { "R1": { "Value": "10k", "MPN": "RC0805FR-0710KL" } }
+//!   }
+//! }
+//!
+//! or with a symbol or footprint field named `Variant.<name>`. The field
+//! value is `DNP`, `Fitted` or the substituted fields, like
+//! `Value=10k; MPN=RC0805FR-0710KL`, a text without `=` is the new value.
+//! The project file has precedence over the fields. Parts without an entry
+//! keep their `dnp` attribute.
+//!
+//! # Example:
+//!
+//! use std::path::Path;
+//! use reports::variant::{Assembly, Variant};
+//!
+//! let variant = Variant::load(Path::new("files/summe/summe.kicad_pro"), "lite").unwrap();
+//! if variant.assembly("R2", None, false) == Assembly::Dnp {
+//!     println!("R2 is not populated in {}", variant.name);
+//! }
+use std::{collections::HashMap, fs, path::Path};
+
+use sexp::{el, utils::is_dnp, SexpProperty};
+use simulation::Project;
+
+use crate::Error;
+
+///The prefix of the variant fields.
+pub const FIELD_PREFIX: &str = "Variant.";
+
+///The assembly of a part in a variant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Assembly {
+    Fitted,
+    Dnp,
+    ///The part is fitted with the fields replaced, like `Value`.
+    Substituted(Vec<(String, String)>),
+}
+
+impl Assembly {
+    ///The part is not populated.
+    pub fn is_dnp(&self) -> bool {
+        matches!(self, Assembly::Dnp)
+    }
+}
+
+///An assembly variant.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Variant {
+    pub name: String,
+    ///The references of the parts that are not populated.
+    pub dnp: Vec<String>,
+    ///The references of the parts that are fitted.
+    pub fitted: Vec<String>,
+    ///The substituted fields by reference.
+    pub substitutes: HashMap<String, Vec<(String, String)>>,
+}
+
+impl Variant {
+    ///Create a variant that is only defined by the symbol fields.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    ///Load the variant from the `variants` of a KiCad project file.
+    ///
+    ///A variant that is not in the project file is only defined by the
+    ///symbol fields.
+    pub fn load(path: &Path, name: &str) -> Result<Self, Error> {
+        let error = |msg: String| Error::ProjectError(path.display().to_string(), msg);
+        let content = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let project = json::parse(&content).map_err(|e| error(e.to_string()))?;
+        let entry = &project["variants"][name];
+        let mut variant = Self::new(name);
+        if entry.is_null() {
+            return Ok(variant);
+        }
+        let references = |key: &str| -> Result<Vec<String>, Error> {
+            entry[key]
+                .members()
+                .map(|r| {
+                    r.as_str()
+                        .map(|r| r.to_string())
+                        .ok_or_else(|| error(format!("invalid reference in '{}.{}'", name, key)))
+                })
+                .collect()
+        };
+        variant.dnp = references("dnp")?;
+        variant.fitted = references("fitted")?;
+        for (reference, fields) in entry["substitutes"].entries() {
+            let mut substitute = Vec::new();
+            for (field, value) in fields.entries() {
+                let Some(value) = value.as_str() else {
+                    return Err(error(format!(
+                        "invalid substitute '{}' for {}",
+                        field, reference
+                    )));
+                };
+                substitute.push((field.to_string(), value.to_string()));
+            }
+            variant
+                .substitutes
+                .insert(reference.to_string(), substitute);
+        }
+        Ok(variant)
+    }
+
+    ///The name of the symbol field for this variant.
+    pub fn field(&self) -> String {
+        format!("{}{}", FIELD_PREFIX, self.name)
+    }
+
+    ///Get the assembly of a part.
+    ///
+    ///# Arguments
+    ///
+    ///* `reference` - The reference of the part.
+    ///* `field`     - The value of the variant field of the part.
+    ///* `dnp`       - The `dnp` attribute of the part.
+    pub fn assembly(&self, reference: &str, field: Option<&str>, dnp: bool) -> Assembly {
+        let reference = reference.to_string();
+        if self.dnp.contains(&reference) {
+            return Assembly::Dnp;
+        }
+        if let Some(substitute) = self.substitutes.get(&reference) {
+            return Assembly::Substituted(substitute.clone());
+        }
+        if self.fitted.contains(&reference) {
+            return Assembly::Fitted;
+        }
+        match field.map(|f| f.trim()) {
+            Some(f) if f.eq_ignore_ascii_case("dnp") => Assembly::Dnp,
+            Some(f) if f.eq_ignore_ascii_case("fitted") => Assembly::Fitted,
+            Some(f) if !f.is_empty() => Assembly::Substituted(substitute(f)),
+            _ if dnp => Assembly::Dnp,
+            _ => Assembly::Fitted,
+        }
+    }
+
+    ///The references of the parts in the project that are not populated.
+    pub fn dnp_references(&self, project: &Project) -> Result<Vec<String>, Error> {
+        let mut references = Vec::new();
+        for (index, sheet) in project.sheets.iter().enumerate() {
+            for symbol in sheet.tree.root()?.query(el::SYMBOL) {
+                let Some(reference) = project.reference(index, symbol) else {
+                    continue;
+                };
+                let field: Option<String> = symbol.property(&self.field());
+                if self
+                    .assembly(&reference, field.as_deref(), is_dnp(symbol))
+                    .is_dnp()
+                    && !references.contains(&reference)
+                {
+                    references.push(reference);
+                }
+            }
+        }
+        references.sort();
+        Ok(references)
+    }
+}
+
+///Parse the substituted fields of a variant field.
+fn substitute(text: &str) -> Vec<(String, String)> {
+    text.split(';')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| match item.split_once('=') {
+            Some((name, value)) => (name.trim().to_string(), value.trim().to_string()),
+            None => (el::PROPERTY_VALUE.to_string(), item.to_string()),
+        })
+        .collect()
+}
@@ -1,8 +1,11 @@
 mod reports {
     mod tests {
         mod bom {
-            use reports::{bom, bom::BomItem};
+            use itertools::Itertools;
+            use reports::{bom, bom::BomItem, variant::Variant};
             use sexp::{SexpParser, SexpTree};
+            use simulation::Project;
+            use std::path::Path;
             use yaml_rust::{Yaml, YamlLoader};
             #[test]
             fn test_bom() {
@@ -31,7 +34,8 @@ mod reports {
                                 ),
                                 datasheet: String::from("http://datasheet.com/capacitor.pdf"),
                                 description: String::from("Multilayer Ceramic Capacitors MLCC"),
-                                mouser_nr: String::from("asd")
+                                mouser_nr: String::from("asd"),
+                                ..Default::default()
                             },
                             item
                         );
@@ -56,7 +60,8 @@ mod reports {
                         ),
                         datasheet: String::from("http://datasheet.com/capacitor.pdf"),
                         description: String::from("Multilayer Ceramic Capacitors MLCC"),
-                        mouser_nr: String::from("asd")
+                        mouser_nr: String::from("asd"),
+                        ..Default::default()
                     },
                     partlist.first().unwrap()
                 );
@@ -88,6 +93,7 @@ mod reports {
                         datasheet: String::new(),
                         description: String::from("Audio Jack"),
                         mouser_nr: String::new(),
+                        ..Default::default()
                     },
                     *item.unwrap()
                 );
@@ -105,6 +111,7 @@ mod reports {
                     datasheet: String::new(),
                     description: String::new(),
                     mouser_nr: String::new(),
+                    ..Default::default()
                 };
                 let partlist = bom::get_partlist("tests/partlist.yaml").unwrap();
                 let item = bom::search_part(
@@ -123,17 +130,208 @@ mod reports {
                         ),
                         datasheet: String::from("http://datasheet.com/capacitor.pdf"),
                         description: String::from("Multilayer Ceramic Capacitors MLCC"),
-                        mouser_nr: String::from("asd")
+                        mouser_nr: String::from("asd"),
+                        ..Default::default()
                     },
                     item
                 );
             }
+            #[test]
+            fn bom_settings() {
+                let settings = bom::BomSettings::load(Path::new("tests/bom.kicad_pro")).unwrap();
+                assert!(settings.group);
+                assert!(settings.exclude_dnp);
+                assert_eq!(vec!["Value", "Footprint", "MPN"], settings.group_by);
+                assert_eq!(
+                    vec!["Reference", "Value", "Part Number", "Qty"],
+                    settings
+                        .columns
+                        .iter()
+                        .map(|c| c.label.as_str())
+                        .collect::<Vec<&str>>()
+                );
+
+                let doc = SexpParser::load("tests/bom.kicad_sch").unwrap();
+                let tree = SexpTree::from(doc.iter()).unwrap();
+                let (items, _) = bom::bom_with(&tree, &settings, None).unwrap();
+                let rows: Vec<Vec<String>> = items
+                    .iter()
+                    .map(|item| {
+                        settings
+                            .columns
+                            .iter()
+                            .map(|c| item.field(&c.name))
+                            .collect()
+                    })
+                    .collect();
+                assert_eq!(
+                    vec![
+                        vec!["R1 R3", "4.7k", "RC0805FR-074K7L", "2"],
+                        vec!["R2", "4.7k", "ERJ-6ENF4701V", "1"],
+                    ],
+                    rows
+                );
+            }
+            #[test]
+            fn bom_dnp() {
+                let doc = SexpParser::load("tests/bom.kicad_sch").unwrap();
+                let tree = SexpTree::from(doc.iter()).unwrap();
+                let (items, _) = bom::bom(&tree, true, None).unwrap();
+                assert_eq!(2, items.len());
+                assert_eq!("3", items[1].field(bom::QUANTITY));
+                assert_eq!("DNP", items[0].field(bom::DNP));
+
+                let settings = bom::BomSettings {
+                    exclude_dnp: true,
+                    ..Default::default()
+                }
+                .with_columns(&[String::from("Reference"), String::from("MPN")]);
+                let (items, _) = bom::bom_with(&tree, &settings, None).unwrap();
+                assert_eq!(3, items.len());
+                assert_eq!("RC0805FR-074K7L", items[0].field("MPN"));
+                assert_eq!("MPN", settings.columns[1].label);
+            }
+            #[test]
+            fn bom_hierarchy() {
+                let project =
+                    Project::load(Path::new("../simulation/tests/hierarchy/root.kicad_sch"))
+                        .unwrap();
+                let settings = bom::BomSettings::default();
+                let (items, _) = bom::bom_project(&project, &settings, None).unwrap();
+                assert_eq!(
+                    vec!["C1", "C2", "R1", "R2", "R3"],
+                    items
+                        .iter()
+                        .map(|i| i.references.join(" "))
+                        .collect::<Vec<String>>()
+                );
+
+                let settings = bom::BomSettings {
+                    group: true,
+                    ..Default::default()
+                };
+                let (items, _) = bom::bom_project(&project, &settings, None).unwrap();
+                assert_eq!(
+                    vec!["2 C1 C2", "2 R1 R2", "1 R3"],
+                    items
+                        .iter()
+                        .map(|i| format!("{} {}", i.amount, i.field("Reference")))
+                        .collect::<Vec<String>>()
+                );
+            }
+            #[test]
+            fn bom_kit() {
+                let project =
+                    Project::load(Path::new("../simulation/tests/hierarchy/root.kicad_sch"))
+                        .unwrap();
+                let boards = vec![
+                    bom::BomBoard {
+                        name: String::from("a"),
+                        project: &project,
+                        count: 2,
+                        variant: None,
+                    },
+                    bom::BomBoard {
+                        name: String::from("b"),
+                        project: &project,
+                        count: 1,
+                        variant: None,
+                    },
+                ];
+                let settings = bom::BomSettings {
+                    group: true,
+                    ..Default::default()
+                };
+                let (items, _) = bom::bom_boards(&boards, &settings, None).unwrap();
+                let capacitors = items.iter().find(|i| i.value == "47n").unwrap();
+                assert_eq!(6, capacitors.amount);
+                assert_eq!(
+                    vec!["a:C1", "a:C2", "b:C1", "b:C2"],
+                    capacitors
+                        .references
+                        .iter()
+                        .sorted()
+                        .collect::<Vec<&String>>()
+                );
+                assert_eq!(3, items.iter().find(|i| i.value == "1k").unwrap().amount);
+            }
+            #[test]
+            fn bom_kit_variant() {
+                let project =
+                    Project::load(Path::new("../simulation/tests/hierarchy/root.kicad_sch"))
+                        .unwrap();
+                let boards = vec![
+                    bom::BomBoard {
+                        name: String::from("a"),
+                        project: &project,
+                        count: 1,
+                        variant: Some(Variant {
+                            dnp: vec![String::from("R2")],
+                            ..Variant::new("lite")
+                        }),
+                    },
+                    bom::BomBoard {
+                        name: String::from("b"),
+                        project: &project,
+                        count: 1,
+                        variant: None,
+                    },
+                ];
+                let (items, _) =
+                    bom::bom_boards(&boards, &bom::BomSettings::default(), None).unwrap();
+                let references: Vec<&String> = items
+                    .iter()
+                    .flat_map(|i| i.references.iter())
+                    .filter(|r| r.ends_with(":R2"))
+                    .collect();
+                assert_eq!(vec!["b:R2"], references);
+                assert!(items
+                    .iter()
+                    .any(|i| i.references.contains(&String::from("a:R1"))));
+            }
+            #[test]
+            fn bom_variant() {
+                let doc = SexpParser::load("tests/bom.kicad_sch").unwrap();
+                let tree = SexpTree::from(doc.iter()).unwrap();
+                let settings = bom::BomSettings {
+                    variant: Some(Variant::load(Path::new("tests/bom.kicad_pro"), "lite").unwrap()),
+                    ..Default::default()
+                };
+                let (items, _) = bom::bom_with(&tree, &settings, None).unwrap();
+                assert_eq!(
+                    vec!["C1 47n", "R1 4.7k", "R2 10k"],
+                    items
+                        .iter()
+                        .map(|i| format!("{} {}", i.field("Reference"), i.value))
+                        .collect::<Vec<String>>()
+                );
+                assert!(!items[0].dnp);
+
+                let settings = bom::BomSettings {
+                    variant: Some(Variant::new("full")),
+                    ..Default::default()
+                };
+                let (items, _) = bom::bom_with(&tree, &settings, None).unwrap();
+                assert_eq!(
+                    vec!["R1", "R2", "R3"],
+                    items
+                        .iter()
+                        .map(|i| i.field("Reference"))
+                        .collect::<Vec<String>>()
+                );
+                assert_eq!("10k", items[0].value);
+                assert_eq!("RC0805FR-0710KL", items[0].field("MPN"));
+            }
         }
         mod erc {
             use std::path::Path;
 
             use itertools::Itertools;
-            use reports::{erc, erc::symbols};
+            use reports::{
+                erc,
+                erc::symbols,
+                pinmap::{PinConflict, PinMatrix, PinType},
+            };
             use sexp::{SexpParser, SexpTree};
             #[test]
             fn collect_symbols() {
@@ -168,13 +366,433 @@ mod reports {
             #[test]
             fn check_unconnected_pin() {
                 let erc = erc::erc(Path::new("tests/low_pass_filter_unconnected.kicad_sch")).unwrap();
-                assert_eq!(10, erc.len());
+                assert_eq!(12, erc.len());
+                let items: Vec<String> = erc
+                    .iter()
+                    .map(|e| format!("{} {}", e.id, e.reference))
+                    .sorted()
+                    .collect();
+                assert_eq!(
+                    vec![
+                        "MissingPowerFlag GND",
+                        "NoReference R?",
+                        "NotAllParts U1",
+                        "PinNotConnected R?:1",
+                        "PinNotConnected R?:2",
+                        "PinNotConnected U1:1",
+                        "PinNotConnected U1:2",
+                        "PinNotConnected U1:3",
+                        "PinNotConnected U1b:5",
+                        "PinNotConnected U1b:6",
+                        "PinNotConnected U1b:7",
+                        "WireDangling 55.88:83.82",
+                    ],
+                    items
+                );
+            }
+            #[test]
+            fn unit_exclusions() {
+                let settings = erc::ErcSettings {
+                    exclusions: vec![
+                        String::from("pin_not_connected|U1:5"),
+                        String::from("pin_not_connected|U1b:6"),
+                        String::from("pin_not_connected|U1a:1"),
+                    ],
+                    ..Default::default()
+                };
+                let erc = erc::erc_with(
+                    Path::new("tests/low_pass_filter_unconnected.kicad_sch"),
+                    &settings,
+                )
+                .unwrap();
+                let pins: Vec<&str> = erc
+                    .iter()
+                    .filter(|e| matches!(e.id, erc::ErcType::PinNotConnected))
+                    .map(|e| e.reference.as_str())
+                    .sorted()
+                    .collect();
+                assert_eq!(vec!["R?:1", "R?:2", "U1:1", "U1:2", "U1:3", "U1b:7"], pins);
             }
             #[test]
             fn all_units() {
                 let erc = erc::erc(Path::new("tests/3280.kicad_sch")).unwrap();
                 assert_eq!(0, erc.len());
             }
+            #[test]
+            fn conflicting_labels() {
+                let erc = erc::erc(Path::new("../simulation/tests/labels.kicad_sch")).unwrap();
+                assert_eq!(1, erc.len());
+                assert!(matches!(erc[0].id, erc::ErcType::ConflictingLabels));
+                assert_eq!("A", erc[0].reference);
+            }
+            #[test]
+            fn pin_types() {
+                let erc = erc::erc(Path::new("tests/pin_types.kicad_sch")).unwrap();
+                let ids: Vec<String> = erc.iter().map(|e| e.id.to_string()).sorted().collect();
+                assert_eq!(
+                    vec![
+                        "MissingPowerFlag",
+                        "PinNotDriven",
+                        "PinNotDriven",
+                        "PinToPinError"
+                    ],
+                    ids
+                );
+                //the power pin on the net without a power flag is not reported twice.
+                let power = erc
+                    .iter()
+                    .find(|e| matches!(e.id, erc::ErcType::MissingPowerFlag))
+                    .unwrap();
+                assert_eq!("GND", power.reference);
+            }
+            #[test]
+            fn hierarchy_drivers() {
+                let driven = |e: &&erc::ErcItem| {
+                    matches!(
+                        e.id,
+                        erc::ErcType::PinNotDriven | erc::ErcType::PowerPinNotDriven
+                    )
+                };
+                let erc = erc::erc(Path::new("tests/drivers/root.kicad_sch")).unwrap();
+                assert_eq!(0, erc.iter().filter(driven).count());
+
+                let doc = SexpParser::load("tests/drivers/amp.kicad_sch").unwrap();
+                let schema = SexpTree::from(doc.iter()).unwrap();
+                let erc = erc::erc_from_tree(&schema).unwrap();
+                let refs: Vec<&str> = erc
+                    .iter()
+                    .filter(driven)
+                    .map(|e| e.reference.as_str())
+                    .sorted()
+                    .collect();
+                assert_eq!(vec!["U2:2", "U2:8"], refs);
+            }
+            #[test]
+            fn hierarchy_labels() {
+                let erc = erc::erc(Path::new("tests/drivers/root.kicad_sch")).unwrap();
+                let find = |rule: &str| {
+                    erc.iter()
+                        .filter(|e| e.id.rule() == rule)
+                        .map(|e| e.reference.as_str())
+                        .collect::<Vec<&str>>()
+                };
+                assert!(find("missing_power_flag").is_empty());
+                assert_eq!(vec!["Clk"], find("similar_labels"));
+            }
+            #[test]
+            fn severities() {
+                let erc = erc::erc(Path::new("tests/pin_types.kicad_sch")).unwrap();
+                assert!(erc.iter().all(|e| e.severity == erc::Severity::Error));
+
+                let mut settings = erc::ErcSettings::default();
+                settings
+                    .severities
+                    .insert(String::from("pin_not_driven"), erc::Severity::Ignore);
+                settings
+                    .severities
+                    .insert(String::from("pin_to_pin"), erc::Severity::Warning);
+                let erc = erc::erc_with(Path::new("tests/pin_types.kicad_sch"), &settings).unwrap();
+                let ids: Vec<String> = erc.iter().map(|e| e.id.to_string()).sorted().collect();
+                assert_eq!(vec!["MissingPowerFlag", "PinToPinError"], ids);
+            }
+            #[test]
+            fn exclusions() {
+                let erc = erc::erc(Path::new("tests/exclusions.kicad_sch")).unwrap();
+                assert_eq!(2, erc.len());
+                assert!(erc
+                    .iter()
+                    .all(|e| matches!(e.id, erc::ErcType::PinNotDriven)
+                        && e.severity == erc::Severity::Warning));
+
+                let settings =
+                    erc::ErcSettings::load(Path::new("tests/exclusions.kicad_pro")).unwrap();
+                assert_eq!(3, settings.exclusions.len());
+                assert_eq!(
+                    erc::Severity::Error,
+                    settings.severity(&erc::ErcType::PinToPinError)
+                );
+
+                //the exclusion must name the pin, not the symbol.
+                let mut settings = settings;
+                settings.exclusions[2] = String::from(
+                    "power_pin_not_driven|1274600|1076200|00000000-0000-0000-0000-00000000101e|",
+                );
+                let erc =
+                    erc::erc_with(Path::new("tests/exclusions.kicad_sch"), &settings).unwrap();
+                assert!(erc
+                    .iter()
+                    .any(|e| matches!(e.id, erc::ErcType::PowerPinNotDriven)));
+            }
+            #[test]
+            fn schematic_checks() {
+                let erc = erc::erc(Path::new("tests/erc_checks.kicad_sch")).unwrap();
+                let find = |rule: &str| {
+                    erc.iter()
+                        .filter(|e| e.id.rule() == rule)
+                        .map(|e| e.reference.as_str())
+                        .collect::<Vec<&str>>()
+                };
+                assert_eq!(vec!["R1"], find("duplicate_reference"));
+                assert_eq!(vec!["R2"], find("overlapping_symbols"));
+                assert_eq!(vec!["Clk"], find("similar_labels"));
+                assert_eq!(vec!["GND"], find("missing_power_flag"));
+                assert_eq!(vec!["LOOSE"], find("label_dangling"));
+                assert_eq!(vec!["50:40"], find("wire_dangling"));
+            }
+            #[test]
+            fn pins_after_no_connect() {
+                let erc = erc::erc(Path::new("tests/no_connect_pin.kicad_sch")).unwrap();
+                let refs: Vec<&str> = erc.iter().map(|e| e.reference.as_str()).sorted().collect();
+                assert_eq!(vec!["U1:2", "U1:3"], refs);
+            }
+            #[test]
+            fn missing_library_symbol() {
+                let erc = erc::erc(Path::new("tests/missing_library.kicad_sch")).unwrap();
+                let missing: Vec<&str> = erc
+                    .iter()
+                    .filter(|e| matches!(e.id, erc::ErcType::MissingLibrarySymbol))
+                    .map(|e| e.reference.as_str())
+                    .sorted()
+                    .collect();
+                assert_eq!(vec!["X1", "X2"], missing);
+                assert!(erc
+                    .iter()
+                    .any(|e| matches!(e.id, erc::ErcType::NoReference) && e.reference == "R?"));
+            }
+            #[test]
+            fn pin_matrix() {
+                let mut settings = erc::ErcSettings::default();
+                settings
+                    .pin_map
+                    .set(PinType::Output, PinType::Output, PinConflict::Warning);
+                let erc = erc::erc_with(Path::new("tests/pin_types.kicad_sch"), &settings).unwrap();
+                assert!(erc
+                    .iter()
+                    .any(|e| matches!(e.id, erc::ErcType::PinToPinWarning)));
+                assert!(!erc
+                    .iter()
+                    .any(|e| matches!(e.id, erc::ErcType::PinToPinError)));
+
+                let mut pin_map = vec![vec![0; 12]; 12];
+                pin_map[1][1] = 2;
+                let matrix = PinMatrix::from_pin_map(&pin_map).unwrap();
+                assert_eq!(
+                    PinConflict::Error,
+                    matrix.get(PinType::Output, PinType::Output)
+                );
+                assert_eq!(
+                    PinConflict::Ok,
+                    matrix.get(PinType::Output, PinType::PowerOut)
+                );
+                assert!(PinMatrix::from_pin_map(&pin_map[1..]).is_err());
+            }
+        }
+        mod drc {
+            use itertools::Itertools;
+            use reports::drc;
+            #[test]
+            fn native_drc() {
+                let result = drc::drc("tests/drc.kicad_pcb").unwrap();
+                let types: Vec<&str> = result
+                    .errors
+                    .iter()
+                    .map(|e| e.drc_type.as_str())
+                    .sorted()
+                    .collect();
+                assert_eq!(
+                    vec![
+                        "annular_width",
+                        "clearance",
+                        "copper_edge_clearance",
+                        "hole_to_hole",
+                        "track_width",
+                        "unconnected_items"
+                    ],
+                    types
+                );
+                let unconnected = result
+                    .errors
+                    .iter()
+                    .find(|e| matches!(e.error_type, drc::ErrType::Unconnected))
+                    .unwrap();
+                assert_eq!(
+                    vec!["Pad 2 [GND] of R1", "Pad 2 [GND] of R2"],
+                    unconnected
+                        .items
+                        .iter()
+                        .map(|i| i.description.as_str())
+                        .collect::<Vec<&str>>()
+                );
+            }
+            #[test]
+            fn design_rules() {
+                let settings =
+                    drc::DrcSettings::load(std::path::Path::new("tests/drc.kicad_pro")).unwrap();
+                assert_eq!("Power", settings.netclass("VCC"));
+                assert_eq!(0.5, settings.clearance("VCC"));
+                assert_eq!(0.2, settings.clearance("GND"));
+
+                let mut settings = settings;
+                settings.min_track_width = 0.05;
+                settings.min_copper_edge_clearance = 0.0;
+                let result = drc::drc_with("tests/drc.kicad_pcb", &settings).unwrap();
+                assert!(result
+                    .errors
+                    .iter()
+                    .all(|e| e.drc_type != "track_width" && e.drc_type != "copper_edge_clearance"));
+            }
+            #[test]
+            fn schematic_parity() {
+                let result = drc::parity(
+                    std::path::Path::new("tests/parity.kicad_sch"),
+                    "tests/parity.kicad_pcb",
+                )
+                .unwrap();
+                assert!(result
+                    .iter()
+                    .all(|e| matches!(e.error_type, drc::ErrType::Parity)));
+                let types: Vec<&str> = result
+                    .iter()
+                    .map(|e| e.drc_type.as_str())
+                    .sorted()
+                    .collect();
+                assert_eq!(
+                    vec![
+                        "extra_footprint",
+                        "footprint_mismatch",
+                        "missing_footprint",
+                        "net_conflict",
+                        "value_mismatch"
+                    ],
+                    types
+                );
+                let conflict = result
+                    .iter()
+                    .find(|e| e.drc_type == "net_conflict")
+                    .unwrap();
+                assert_eq!("Pad 2 [/IN] of C1", conflict.items[0].description);
+                //R3 is not on the board.
+                let missing = result
+                    .iter()
+                    .find(|e| e.drc_type == "missing_footprint")
+                    .unwrap();
+                assert_eq!("Symbol R2", missing.items[0].description);
+            }
+        }
+        mod position {
+            use reports::{
+                position::{positions, to_csv},
+                variant::Variant,
+            };
+
+            #[test]
+            fn pick_and_place() {
+                let result = positions("tests/position.kicad_pcb", None).unwrap();
+                assert_eq!(
+                    vec!["C1", "C2", "C10", "J1", "R1"],
+                    result
+                        .iter()
+                        .map(|p| p.reference.as_str())
+                        .collect::<Vec<&str>>()
+                );
+                assert_eq!(
+                    "Ref,Val,Package,PosX,PosY,Rot,Side\n\
+                     \"C1\",\"100n\",\"C_0805_2012Metric\",20.0000,5.0000,180.0000,bottom\n\
+                     \"C2\",\"10u\",\"C_0805_2012Metric\",45.0000,15.0000,0.0000,top\n\
+                     \"C10\",\"10u\",\"C_0805_2012Metric\",40.0000,15.0000,0.0000,top\n\
+                     \"J1\",\"Conn_01x02\",\"PinHeader_1x02_P2.54mm_Vertical\",30.0000,20.0000,90.0000,top\n\
+                     \"R1\",\"4k7\",\"R_0805_2012Metric\",10.0000,10.0000,0.0000,top\n",
+                    to_csv(&result)
+                );
+            }
+            #[test]
+            fn pick_and_place_variant() {
+                let variant = Variant::new("lite");
+                let result = positions("tests/position.kicad_pcb", Some(&variant)).unwrap();
+                assert_eq!(1, result.len());
+                assert_eq!("R1", result[0].reference);
+                assert_eq!("10k", result[0].value);
+            }
+        }
+        mod report {
+            use reports::{bom::BomItem, drc, erc, report::Report};
+            use std::path::Path;
+            #[test]
+            fn drc_report() {
+                let result = drc::drc("tests/drc.kicad_pcb").unwrap();
+                let report = Report::drc(&result);
+                assert_eq!(6, report.findings.len());
+
+                let json = json::parse(&report.to_json()).unwrap();
+                assert_eq!(1, json["version"]);
+                assert_eq!("drc", json["check"]);
+                assert_eq!("tests/drc.kicad_pcb", json["findings"][0]["file"]);
+                assert!(json["findings"][0]["locations"][0]["x"].is_number());
+
+                let sarif = json::parse(&report.to_sarif()).unwrap();
+                assert_eq!("2.1.0", sarif["version"]);
+                assert_eq!(6, sarif["runs"][0]["results"].len());
+                assert_eq!(
+                    "tests/drc.kicad_pcb",
+                    sarif["runs"][0]["results"][0]["locations"][0]["physicalLocation"]
+                        ["artifactLocation"]["uri"]
+                );
+
+                let junit = report.to_junit();
+                assert!(junit.contains("<testsuites name=\"drc\" tests=\"6\""));
+                assert_eq!(
+                    report.count(erc::Severity::Error),
+                    junit.matches("<failure ").count()
+                );
+            }
+            #[test]
+            fn erc_report() {
+                let input = Path::new("tests/low_pass_filter_unconnected.kicad_sch");
+                let items = erc::erc(input).unwrap();
+                let report = Report::erc(input, &items);
+                assert_eq!(items.len(), report.findings.len());
+                let json = json::parse(&report.to_json()).unwrap();
+                assert_eq!("erc", json["check"]);
+                assert_eq!(items.len(), json["findings"].len());
+            }
+            #[test]
+            fn erc_report_sheets() {
+                let input = Path::new("tests/drivers/root.kicad_sch");
+                let report = Report::erc(input, &erc::erc(input).unwrap());
+                let file = |reference: &str| {
+                    report
+                        .findings
+                        .iter()
+                        .find(|f| f.reference == reference)
+                        .map(|f| f.file.as_str())
+                        .unwrap()
+                };
+                assert_eq!("tests/drivers/root.kicad_sch", file("Clk"));
+                assert_eq!("tests/drivers/amp.kicad_sch", file("CLK"));
+            }
+            #[test]
+            fn bom_report() {
+                let missing = vec![BomItem {
+                    amount: 1,
+                    references: vec![String::from("R1")],
+                    value: String::from("<1k>"),
+                    footprint: String::from("R_0805"),
+                    datasheet: String::new(),
+                    description: String::new(),
+                    mouser_nr: String::new(),
+                    ..Default::default()
+                }];
+                let report = Report::bom(Path::new("tests/summe.kicad_sch"), &missing);
+                let junit = report.to_junit();
+                assert!(junit.contains("name=\"missing_part R1\""));
+                assert!(junit.contains("&lt;1k&gt; (R_0805) not found in the partlist"));
+                assert!(!junit.contains("<failure"));
+
+                let empty = Report::bom(Path::new("tests/summe.kicad_sch"), &[]);
+                assert!(empty
+                    .to_junit()
+                    .contains("<testcase classname=\"bom\" name=\"bom\""));
+            }
         }
     }
 }
@@ -970,7 +970,7 @@ impl<'a> Iterator for SexpIter<'a> {
 
 ///Utility methods to access some common nodes.
 pub mod utils {
-    use super::{el, Sexp, SexpAtom, SexpParser, SexpTree, SexpValueQuery};
+    use super::{el, Sexp, SexpAtom, SexpParser, SexpProperty, SexpTree, SexpValueQuery};
     use crate::Error;
     use lazy_static::lazy_static;
     use ndarray::{s, Array1};
@@ -1052,6 +1052,14 @@ pub mod utils {
         }
     }
 
+    ///the symbol is not populated, by the `dnp` attribute or a `DNP` property.
+    pub fn is_dnp(symbol: &Sexp) -> bool {
+        let dnp: bool = symbol.value("dnp").unwrap_or(false);
+        dnp || <Sexp as SexpProperty<String>>::property(symbol, "DNP")
+            .map(|value| !matches!(value.to_lowercase().as_str(), "" | "0" | "no" | "false"))
+            .unwrap_or(false)
+    }
+
     /// load a library
     ///
     /// # Arguments
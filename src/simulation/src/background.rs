@@ -0,0 +1,242 @@
+//! Run ngspice simulations in the background thread.
+//!
+//! The commands are sent with the `bg_` prefix, ngspice reports the status
+//! and the values of each simulation point with the callbacks.
+//!
+//! The shared library has a global state, a [`Session`] is held while a
+//! simulation uses it. Other simulations with the shared backend fail while
+//! the background simulation is active.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use ngspice::{Callbacks, NgSpice, NgSpiceError, PlotInit, VecValue};
+
+use crate::{
+    diagnostics::parse_diagnostics,
+    error::Error,
+    simulation::{collect_plots, Plots},
+};
+
+lazy_static! {
+    static ref RE_PERCENT: regex::Regex = Regex::new(r"(\d+(\.\d+)?)%").unwrap();
+}
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+///The shared library is used by a simulation.
+static SESSION: AtomicBool = AtomicBool::new(false);
+
+///Exclusive use of the shared ngspice library, released when dropped.
+pub(crate) struct Session;
+
+impl Session {
+    ///Acquire the shared library, fails when it is already in use.
+    pub(crate) fn acquire() -> Result<Self, Error> {
+        SESSION
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .map(|_| Session)
+            .map_err(|_| {
+                Error::NgSpiceError(String::from(
+                    "the shared library is in use by a background simulation",
+                ))
+            })
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        SESSION.store(false, Ordering::SeqCst);
+    }
+}
+
+///The progress of a background simulation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Progress {
+    ///The last status message from ngspice.
+    pub status: String,
+    ///Percentage of the running analysis.
+    pub percent: f64,
+    ///The background thread is running.
+    pub running: bool,
+    ///The name of the current plot.
+    pub plot: String,
+    ///The vectors of the current plot received so far.
+    pub vectors: IndexMap<String, Vec<f64>>,
+}
+
+///Callbacks for the background simulation.
+struct BgCb {
+    strs: Vec<String>,
+    progress: Arc<Mutex<Progress>>,
+}
+
+impl Callbacks for BgCb {
+    fn send_char(&mut self, s: &str) {
+        self.strs.push(s.to_string())
+    }
+    fn send_stat(&mut self, s: &str) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.status = s.to_string();
+        if let Some(cap) = RE_PERCENT.captures(s) {
+            progress.percent = cap[1].parse::<f64>().unwrap_or(progress.percent);
+        }
+    }
+    fn send_init_data(&mut self, plot: PlotInit) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.plot = plot.name;
+        progress.percent = 0.0;
+        progress.vectors = plot
+            .vectors
+            .into_iter()
+            .map(|v| (v.name, Vec::new()))
+            .collect();
+    }
+    fn send_data(&mut self, values: Vec<VecValue>, _count: i32) {
+        let mut progress = self.progress.lock().unwrap();
+        for value in values {
+            progress
+                .vectors
+                .entry(value.name)
+                .or_default()
+                .push(value.real);
+        }
+    }
+    fn bg_thread_running(&mut self, running: bool) {
+        self.progress.lock().unwrap().running = running;
+    }
+}
+
+///The plots and the ngspice output of the simulation.
+type BgResult = Result<(Plots, Vec<String>), Error>;
+
+///A simulation running in the background.
+pub struct Background {
+    progress: Arc<Mutex<Progress>>,
+    halt: Arc<AtomicBool>,
+    handle: Option<JoinHandle<BgResult>>,
+    ///Held until the results are collected.
+    session: Option<Session>,
+}
+
+impl Background {
+    ///Start the simulation of the netlist, the commands are executed one after the other.
+    ///
+    ///The session is held until the results are collected with [`Background::wait`].
+    pub(crate) fn start(session: Session, circuit: Vec<String>, commands: Vec<String>) -> Self {
+        let progress = Arc::new(Mutex::new(Progress {
+            running: true,
+            ..Default::default()
+        }));
+        let halt = Arc::new(AtomicBool::new(false));
+        let thread_progress = progress.clone();
+        let thread_halt = halt.clone();
+        let handle = thread::spawn(move || {
+            let result = Self::execute(circuit, commands, thread_progress.clone(), thread_halt);
+            thread_progress.lock().unwrap().running = false;
+            result
+        });
+        Self {
+            progress,
+            halt,
+            handle: Some(handle),
+            session: Some(session),
+        }
+    }
+
+    fn execute(
+        circuit: Vec<String>,
+        commands: Vec<String>,
+        progress: Arc<Mutex<Progress>>,
+        halt: Arc<AtomicBool>,
+    ) -> BgResult {
+        let mut cb = BgCb {
+            strs: Vec::new(),
+            progress,
+        };
+        let ng = NgSpice::new(&mut cb)?;
+        if let Err(err) = ng.circuit(circuit) {
+            let diagnostics = parse_diagnostics(&ng.callbacks.strs);
+            if !diagnostics.is_empty() {
+                return Err(Error::Diagnostics(diagnostics));
+            }
+            return Err(match err {
+                NgSpiceError::Unknown(code) => {
+                    NgSpiceError::Spice(code, ng.callbacks.strs.join("\n")).into()
+                }
+                _ => err.into(),
+            });
+        }
+        let commands = if commands.is_empty() {
+            vec![String::from("run")]
+        } else {
+            commands
+        };
+        for command in commands {
+            if halt.load(Ordering::SeqCst) {
+                break;
+            }
+            ng.command(format!("bg_{}", command).as_str())?;
+            thread::sleep(POLL_INTERVAL);
+            let mut halted = false;
+            while ng.is_running() {
+                if !halted && halt.load(Ordering::SeqCst) {
+                    ng.command("bg_halt")?;
+                    halted = true;
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+        let plots = collect_plots(&ng)?;
+        Ok((plots, ng.callbacks.strs.clone()))
+    }
+
+    ///The current progress of the simulation.
+    pub fn progress(&self) -> Progress {
+        self.progress.lock().unwrap().clone()
+    }
+
+    ///True until all commands are executed.
+    pub fn is_running(&self) -> bool {
+        self.handle.as_ref().is_some_and(|h| !h.is_finished())
+    }
+
+    ///Stop the simulation, the results received so far are kept.
+    pub fn halt(&self) {
+        self.halt.store(true, Ordering::SeqCst);
+    }
+
+    ///Wait for the simulation and return the results and the ngspice output.
+    pub fn wait(&mut self) -> BgResult {
+        let result = match self.handle.take() {
+            Some(handle) => handle
+                .join()
+                .unwrap_or(Err(Error::NgSpiceError(String::from(
+                    "background simulation panicked",
+                )))),
+            None => Err(NgSpiceError::NoResults.into()),
+        };
+        self.session.take();
+        result
+    }
+}
+
+impl Drop for Background {
+    ///Stop the simulation before the session is released.
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.halt();
+            let _ = handle.join();
+        }
+    }
+}
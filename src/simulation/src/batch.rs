@@ -0,0 +1,213 @@
+//! Run ngspice as an external process in batch mode.
+//!
+//! The netlist and the commands are written to a temporary directory and the
+//! results are collected from the rawfiles ngspice writes after each command.
+//! A crash of ngspice only fails the simulation and not the calling process.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use indexmap::IndexMap;
+
+use crate::{
+    diagnostics::parse_diagnostics,
+    error::Error,
+    rawfile::{self, PlotKeys},
+    xspice::{read_vcd, EVENTS},
+};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+///Temporary working directory, removed when dropped.
+struct WorkDir {
+    path: PathBuf,
+}
+
+impl WorkDir {
+    fn new() -> Result<Self, Error> {
+        let path = std::env::temp_dir().join(format!(
+            "elektron-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+    fn file(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+///The result of a batch run.
+pub struct BatchResult {
+    ///The console output of ngspice.
+    pub buffer: Vec<String>,
+    ///The plots by name.
+    pub plots: IndexMap<String, HashMap<String, Vec<f64>>>,
+}
+
+///Create the netlist with a control block that writes a rawfile after each command.
+///
+///The digital nodes are written to a value change dump at the end.
+fn script(
+    circuit: Vec<String>,
+    commands: &[String],
+    digital: &[String],
+    rawfile: &Path,
+) -> Vec<String> {
+    let mut lines = circuit;
+    lines.push(String::from(".control"));
+    lines.push(String::from("set filetype=ascii"));
+    for (i, command) in commands.iter().enumerate() {
+        lines.push(command.to_string());
+        lines.push(format!("write {}.{} all", rawfile.to_str().unwrap(), i));
+    }
+    if !digital.is_empty() {
+        lines.push(format!(
+            "eprvcd {} > {}.vcd",
+            digital.join(" "),
+            rawfile.to_str().unwrap()
+        ));
+    }
+    lines.push(String::from("quit"));
+    lines.push(String::from(".endc"));
+    lines.push(String::from(".end"));
+    lines
+}
+
+///Run the circuit with the ngspice executable.
+///
+/// # Arguments
+///
+/// * `executable` - The ngspice executable.
+/// * `circuit`    - The netlist without the `.end` line.
+/// * `commands`   - The commands to execute.
+/// * `digital`    - The digital nodes, they are returned in the `events` plot.
+/// * `return`     - The console output and the plots.
+pub fn run(
+    executable: &str,
+    circuit: Vec<String>,
+    commands: &[String],
+    digital: &[String],
+) -> Result<BatchResult, Error> {
+    let workdir = WorkDir::new()?;
+    let netlist = workdir.file("netlist.cir");
+    let rawfile = workdir.file("result.raw");
+    let mut out = File::create(&netlist)?;
+    for line in script(circuit, commands, digital, &rawfile) {
+        writeln!(out, "{}", line)?;
+    }
+    out.flush()?;
+
+    let output = match Command::new(executable).arg("-b").arg(&netlist).output() {
+        Ok(output) => output,
+        Err(err) => {
+            return Err(Error::NgSpiceProcess(format!(
+                "can not start '{}': {}",
+                executable, err
+            )))
+        }
+    };
+    let mut buffer: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|l| format!("stdout {}", l))
+        .collect();
+    buffer.extend(
+        String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .map(|l| format!("stderr {}", l)),
+    );
+    let diagnostics = parse_diagnostics(&buffer);
+    if !output.status.success() {
+        if !diagnostics.is_empty() {
+            return Err(Error::Diagnostics(diagnostics));
+        }
+        return Err(Error::NgSpiceProcess(format!(
+            "ngspice exited with {}:\n{}",
+            output.status,
+            buffer.join("\n")
+        )));
+    }
+
+    let mut plots = IndexMap::new();
+    let mut keys = PlotKeys::default();
+    let mut last: Option<String> = None;
+    for i in 0..commands.len() {
+        let filename = format!("{}.{}", rawfile.to_str().unwrap(), i);
+        let Ok(content) = fs::read_to_string(&filename) else {
+            continue;
+        };
+        //commands that do not create a plot rewrite the current plot.
+        if last.as_ref() == Some(&content) {
+            continue;
+        }
+        for plot in rawfile::read_ascii(&content)? {
+            plots.insert(keys.key(&plot.name), plot.vectors.into_iter().collect());
+        }
+        last = Some(content);
+    }
+    if let Ok(content) = fs::read_to_string(format!("{}.vcd", rawfile.to_str().unwrap())) {
+        let mut events: HashMap<String, Vec<f64>> = HashMap::new();
+        for (node, (time, levels)) in read_vcd(&content)? {
+            events.insert(format!("{}#time", node), time);
+            events.insert(node, levels);
+        }
+        plots.insert(EVENTS.to_string(), events);
+    }
+    if plots.is_empty() && !diagnostics.is_empty() {
+        return Err(Error::Diagnostics(diagnostics));
+    }
+    Ok(BatchResult { buffer, plots })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::script;
+    use std::path::Path;
+
+    #[test]
+    fn test_script() {
+        let lines = script(
+            vec![String::from(".title test"), String::from("R1 1 0 1k")],
+            &[String::from("op")],
+            &[],
+            Path::new("/tmp/result.raw"),
+        );
+        assert_eq!(
+            vec![
+                String::from(".title test"),
+                String::from("R1 1 0 1k"),
+                String::from(".control"),
+                String::from("set filetype=ascii"),
+                String::from("op"),
+                String::from("write /tmp/result.raw.0 all"),
+                String::from("quit"),
+                String::from(".endc"),
+                String::from(".end"),
+            ],
+            lines
+        );
+    }
+    #[test]
+    fn test_script_digital() {
+        let lines = script(
+            vec![String::from(".title test")],
+            &[String::from("tran 1u 1m")],
+            &[String::from("clk"), String::from("q")],
+            Path::new("/tmp/result.raw"),
+        );
+        assert_eq!("eprvcd clk q > /tmp/result.raw.vcd", lines[5]);
+    }
+}
@@ -0,0 +1,251 @@
+//! Bus names and the connectivity of the bus segments.
+//!
+//! KiCad knows two kinds of buses, vector buses like `D[0..7]` and group
+//! buses like `USB{DP DM}`. The members of a named group bus are prefixed
+//! with the group name (`USB.DP`), a group member can be a vector bus or a
+//! bus alias.
+//!
+//! # Examples
+//! ```
+//! use indexmap::IndexMap;
+//! use simulation::expand_bus;
+//!
+//! let aliases = IndexMap::new();
+//! assert_eq!(
+//!     Some(vec![String::from("D0"), String::from("D1")]),
+//!     expand_bus("D[0..1]", &aliases)
+//! );
+//! assert_eq!(
+//!     Some(vec![String::from("I2C.SDA"), String::from("I2C.SCL")]),
+//!     expand_bus("I2C{SDA SCL}", &aliases)
+//! );
+//! assert_eq!(None, expand_bus("GND", &aliases));
+//! ```
+
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::netlist::Point;
+
+lazy_static! {
+    static ref RE_VECTOR: Regex = Regex::new(r"^(.*)\[(\d+)\.\.(\d+)\]$").unwrap();
+    static ref RE_GROUP: Regex = Regex::new(r"^([^{}]*)\{([^{}]*)\}$").unwrap();
+}
+
+///The largest number of members of a vector bus.
+const MAX_MEMBERS: usize = 1024;
+
+///Expand the bus name to the member names.
+///
+///Returns `None` when the name is not a bus, when a bus alias contains
+///itself or when a vector bus has more than 1024 members.
+///
+/// # Arguments
+///
+/// * `name`    - The label name.
+/// * `aliases` - The bus aliases of the schema.
+pub fn expand_bus(name: &str, aliases: &IndexMap<String, Vec<String>>) -> Option<Vec<String>> {
+    expand(name, aliases, &mut Vec::new()).ok()?
+}
+
+///Expand the bus name, `Err` when the expansion fails.
+///
+///The names of the aliases that are expanded are kept in `stack`.
+fn expand<'a>(
+    name: &'a str,
+    aliases: &'a IndexMap<String, Vec<String>>,
+    stack: &mut Vec<&'a str>,
+) -> Result<Option<Vec<String>>, ()> {
+    if let Some(caps) = RE_VECTOR.captures(name) {
+        let prefix = caps.get(1).unwrap().as_str();
+        let (Ok(start), Ok(end)) = (caps[2].parse::<usize>(), caps[3].parse::<usize>()) else {
+            return Ok(None);
+        };
+        if start.abs_diff(end) >= MAX_MEMBERS {
+            return Err(());
+        }
+        let range: Vec<usize> = if start <= end {
+            (start..=end).collect()
+        } else {
+            (end..=start).rev().collect()
+        };
+        return Ok(Some(
+            range.iter().map(|i| format!("{}{}", prefix, i)).collect(),
+        ));
+    }
+    if let Some(caps) = RE_GROUP.captures(name) {
+        let prefix = caps.get(1).unwrap().as_str();
+        let mut members = Vec::new();
+        for member in caps
+            .get(2)
+            .unwrap()
+            .as_str()
+            .split(|c: char| c.is_whitespace() || c == ',')
+        {
+            if member.is_empty() {
+                continue;
+            }
+            let expanded = expand(member, aliases, stack)?.unwrap_or(vec![member.to_string()]);
+            for member in expanded {
+                if prefix.is_empty() {
+                    members.push(member);
+                } else {
+                    members.push(format!("{}.{}", prefix, member));
+                }
+            }
+        }
+        return Ok(Some(members));
+    }
+    if let Some((alias, members)) = aliases.get_key_value(name) {
+        if stack.contains(&alias.as_str()) {
+            return Err(());
+        }
+        stack.push(alias);
+        let mut result = Vec::new();
+        for member in members {
+            result.extend(expand(member, aliases, stack)?.unwrap_or(vec![member.to_string()]));
+        }
+        stack.pop();
+        return Ok(Some(result));
+    }
+    Ok(None)
+}
+
+///Test if the point is on the line from a to b.
+pub(crate) fn on_segment(point: &Point, a: &Point, b: &Point) -> bool {
+    const EPSILON: f64 = 1e-6;
+    let cross = (b.x - a.x) * (point.y - a.y) - (b.y - a.y) * (point.x - a.x);
+    cross.abs() < EPSILON
+        && point.x >= a.x.min(b.x) - EPSILON
+        && point.x <= a.x.max(b.x) + EPSILON
+        && point.y >= a.y.min(b.y) - EPSILON
+        && point.y <= a.y.max(b.y) + EPSILON
+}
+
+///A connected group of bus segments.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bus {
+    ///The bus segments.
+    pub segments: Vec<(Point, Point)>,
+    ///The names of the local labels on the bus.
+    pub labels: Vec<String>,
+    ///The names of the global labels on the bus.
+    pub global: Vec<String>,
+    ///The names of the hierarchical labels on the bus.
+    pub hierarchical: Vec<String>,
+    ///The sheet pins on the bus, the sheet uuid and the pin name.
+    pub sheet_pins: Vec<(String, String)>,
+}
+
+impl Bus {
+    ///Test if the point is on one of the bus segments.
+    pub fn contains(&self, point: &Point) -> bool {
+        self.segments.iter().any(|(a, b)| on_segment(point, a, b))
+    }
+
+    ///All the label names on the bus, the local labels first.
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.labels
+            .iter()
+            .chain(self.global.iter())
+            .chain(self.hierarchical.iter())
+    }
+
+    ///Collect the connected segments to buses.
+    pub(crate) fn connect(segments: Vec<(Point, Point)>) -> Vec<Bus> {
+        let mut buses: Vec<Bus> = Vec::new();
+        for segment in segments {
+            let (connected, mut others): (Vec<Bus>, Vec<Bus>) =
+                buses.into_iter().partition(|bus| {
+                    bus.contains(&segment.0)
+                        || bus.contains(&segment.1)
+                        || bus.segments.iter().any(|(a, b)| {
+                            on_segment(a, &segment.0, &segment.1)
+                                || on_segment(b, &segment.0, &segment.1)
+                        })
+                });
+            let mut bus = Bus {
+                segments: vec![segment],
+                ..Default::default()
+            };
+            for other in connected {
+                bus.segments.extend(other.segments);
+            }
+            others.push(bus);
+            buses = others;
+        }
+        buses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use super::{expand_bus, Bus};
+    use crate::netlist::Point;
+
+    #[test]
+    fn test_expand() {
+        let mut aliases = IndexMap::new();
+        aliases.insert(
+            String::from("CTRL"),
+            vec![String::from("EN"), String::from("A[1..0]")],
+        );
+        assert_eq!(
+            Some(vec![
+                String::from("A2"),
+                String::from("A1"),
+                String::from("A0")
+            ]),
+            expand_bus("A[2..0]", &aliases)
+        );
+        assert_eq!(
+            Some(vec![
+                String::from("SDA"),
+                String::from("SCL"),
+                String::from("EN"),
+                String::from("A1"),
+                String::from("A0")
+            ]),
+            expand_bus("{SDA, SCL CTRL}", &aliases)
+        );
+        assert_eq!(
+            Some(vec![
+                String::from("BUS.EN"),
+                String::from("BUS.A1"),
+                String::from("BUS.A0")
+            ]),
+            expand_bus("BUS{CTRL}", &aliases)
+        );
+        assert_eq!(None, expand_bus("A[x]", &aliases));
+    }
+    #[test]
+    fn test_expand_invalid() {
+        let mut aliases = IndexMap::new();
+        aliases.insert(
+            String::from("A"),
+            vec![String::from("A"), String::from("B")],
+        );
+        aliases.insert(String::from("C"), vec![String::from("BUS{D}")]);
+        aliases.insert(String::from("D"), vec![String::from("C")]);
+        assert_eq!(None, expand_bus("A", &aliases));
+        assert_eq!(None, expand_bus("BUS{A}", &aliases));
+        assert_eq!(None, expand_bus("C", &aliases));
+        assert_eq!(None, expand_bus("D[0..4000000000]", &aliases));
+        assert_eq!(1024, expand_bus("D[0..1023]", &aliases).unwrap().len());
+    }
+    #[test]
+    fn test_connect() {
+        let buses = Bus::connect(vec![
+            (Point::new(0.0, 0.0), Point::new(10.0, 0.0)),
+            (Point::new(20.0, 0.0), Point::new(30.0, 0.0)),
+            (Point::new(5.0, 0.0), Point::new(5.0, 10.0)),
+            (Point::new(10.0, 0.0), Point::new(20.0, 0.0)),
+        ]);
+        assert_eq!(1, buses.len());
+        assert_eq!(4, buses[0].segments.len());
+        assert!(buses[0].contains(&Point::new(25.0, 0.0)));
+    }
+}
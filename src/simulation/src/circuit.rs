@@ -11,32 +11,258 @@
 //! assert_eq!("kicad_sch", root.name);
 //! ```
 
-use crate::error::Error;
+use crate::{
+    error::Error,
+    import::{assignments, strip_params},
+    library::{Definition, Library},
+    source::{check_parameters, check_value, parse_value, spice_parameters, Source},
+    xspice::{is_analog, port_nodes, port_type, CodeModel},
+};
 
 use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::{
-    fs::{self, File},
-    io::Write,
-};
+use std::{fs::File, io::Write};
 
 lazy_static! {
-    pub static ref RE_SUBCKT: regex::Regex =
-        Regex::new(r"(?i:\.SUBCKT) ([a-zA-Z0-9]*) .*").unwrap();
-    pub static ref RE_MODEL: regex::Regex = Regex::new(r"(?i:\.model) ([a-zA-Z0-9]*) .*").unwrap();
-    pub static ref RE_INCLUDE: regex::Regex = Regex::new(r"(?i:\.include) (.*)").unwrap();
+    static ref RE_IDENTIFIER: Regex = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap();
 }
 
 #[derive(Debug, Clone, PartialEq)]
 enum CircuitItem {
     R(String, String, String, String),
     C(String, String, String, String),
+    L(String, String, String, String),
     D(String, String, String, String),
     J(String, String, String, String, String),
     Q(String, String, String, String, String),
+    M(String, String, String, String, String, String),
     X(String, Vec<String>, String),
-    V(String, String, String, String),
+    V(String, String, String, Source),
+    I(String, String, String, Source),
+    E(String, String, String, String, String, String),
+    G(String, String, String, String, String, String),
+    F(String, String, String, String, String),
+    H(String, String, String, String, String),
+    K(String, String, String, String),
+    S(String, String, String, String, String, String),
+    W(String, String, String, String, String),
+    T(String, String, String, String, String, String),
+    B(String, String, String, String),
+    Poly(char, String, String, String, String),
+    A(String, Vec<String>, String),
+}
+
+impl CircuitItem {
+    ///The element is found by the reference or the element name.
+    fn is(&self, reference: &str) -> bool {
+        let prefix = match self {
+            CircuitItem::R(..) => 'R',
+            CircuitItem::C(..) => 'C',
+            CircuitItem::L(..) => 'L',
+            CircuitItem::D(..) => 'D',
+            CircuitItem::J(..) => 'J',
+            CircuitItem::Q(..) => 'Q',
+            CircuitItem::M(..) => 'M',
+            CircuitItem::X(..) => 'X',
+            CircuitItem::V(..) => 'V',
+            CircuitItem::I(..) => 'I',
+            CircuitItem::E(..) => 'E',
+            CircuitItem::G(..) => 'G',
+            CircuitItem::F(..) => 'F',
+            CircuitItem::H(..) => 'H',
+            CircuitItem::K(..) => 'K',
+            CircuitItem::S(..) => 'S',
+            CircuitItem::W(..) => 'W',
+            CircuitItem::T(..) => 'T',
+            CircuitItem::B(..) => 'B',
+            CircuitItem::Poly(prefix, ..) => *prefix,
+            CircuitItem::A(..) => 'A',
+        };
+        self.reference() == reference || name(prefix, self.reference()) == reference
+    }
+
+    fn reference(&self) -> &String {
+        match self {
+            CircuitItem::R(r, ..)
+            | CircuitItem::C(r, ..)
+            | CircuitItem::L(r, ..)
+            | CircuitItem::D(r, ..)
+            | CircuitItem::J(r, ..)
+            | CircuitItem::Q(r, ..)
+            | CircuitItem::M(r, ..)
+            | CircuitItem::X(r, ..)
+            | CircuitItem::V(r, ..)
+            | CircuitItem::I(r, ..)
+            | CircuitItem::E(r, ..)
+            | CircuitItem::G(r, ..)
+            | CircuitItem::F(r, ..)
+            | CircuitItem::H(r, ..)
+            | CircuitItem::K(r, ..)
+            | CircuitItem::S(r, ..)
+            | CircuitItem::W(r, ..)
+            | CircuitItem::T(r, ..)
+            | CircuitItem::B(r, ..)
+            | CircuitItem::Poly(_, r, ..)
+            | CircuitItem::A(r, ..) => r,
+        }
+    }
+
+    ///The model or subcircuit name the element uses.
+    fn model(&self) -> Option<&str> {
+        match self {
+            CircuitItem::D(_, _, _, m)
+            | CircuitItem::J(_, _, _, _, m)
+            | CircuitItem::Q(_, _, _, _, m)
+            | CircuitItem::M(_, _, _, _, _, m)
+            | CircuitItem::X(_, _, m)
+            | CircuitItem::A(_, _, m)
+            | CircuitItem::S(_, _, _, _, _, m)
+            | CircuitItem::W(_, _, _, _, m) => m.split_whitespace().next(),
+            _ => None,
+        }
+    }
+}
+
+///The element name is the reference with the element type as the first letter.
+///
+///The voltage sources, the JFETs and the subcircuits are always prefixed, the
+///vectors keep their names, like `i(vv1)` for the source `V1`.
+fn name(prefix: char, reference: &str) -> String {
+    if !matches!(prefix, 'V' | 'J' | 'X') && reference.starts_with(prefix) {
+        reference.to_string()
+    } else {
+        format!("{}{}", prefix, reference)
+    }
+}
+
+///The reference of the element in a netlist, the element type is added again with [`name`].
+pub(crate) fn reference(prefix: char, element: &str) -> String {
+    if name(prefix, element) == element {
+        element.to_string()
+    } else {
+        element.chars().skip(1).collect()
+    }
+}
+
+///Nodes must not be empty or contain whitespace.
+fn check_nodes(reference: &str, nodes: &[&String]) -> Result<(), Error> {
+    for node in nodes {
+        if node.is_empty() || node.contains(char::is_whitespace) {
+            return Err(Error::InvalidElement(
+                reference.to_string(),
+                format!("invalid node name '{}'", node),
+            ));
+        }
+    }
+    Ok(())
+}
+
+///Check that the model name is set.
+fn check_model(reference: &str, model: &str) -> Result<(), Error> {
+    if model.trim().is_empty() {
+        Err(Error::InvalidElement(
+            reference.to_string(),
+            String::from("model name is not set"),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+///Check the parameters of a transmission line, like `Z0=50 TD=10n`.
+fn check_transmission_line(reference: &str, value: &str) -> Result<(), Error> {
+    let mut z0 = false;
+    for token in value.split_whitespace() {
+        let Some((key, v)) = token.split_once('=') else {
+            return Err(Error::InvalidValue(
+                reference.to_string(),
+                token.to_string(),
+            ));
+        };
+        check_value(reference, v)?;
+        z0 |= key.eq_ignore_ascii_case("Z0");
+    }
+    if !z0 {
+        return Err(Error::InvalidElement(
+            reference.to_string(),
+            String::from("parameter Z0 is not set"),
+        ));
+    }
+    Ok(())
+}
+
+///Check the expression of a behavioral source, like `V=v(in)*2`.
+fn check_behavioral(reference: &str, expression: &str) -> Result<(), Error> {
+    let valid = expression
+        .split_once('=')
+        .map(|(kind, expr)| {
+            (kind.trim().eq_ignore_ascii_case("V") || kind.trim().eq_ignore_ascii_case("I"))
+                && !expr.trim().is_empty()
+        })
+        .unwrap_or(false);
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidValue(
+            reference.to_string(),
+            expression.to_string(),
+        ))
+    }
+}
+
+///The number of nodes of the element type.
+///
+///Returns `None` for an unknown element and `Some(None)` for subcircuits,
+///they have any number of nodes.
+pub(crate) fn node_count(primitive: &str) -> Option<Option<usize>> {
+    match primitive {
+        "R" | "C" | "L" | "D" | "V" | "I" | "B" | "F" | "H" | "W" | "K" => Some(Some(2)),
+        "J" | "Q" => Some(Some(3)),
+        "M" | "E" | "G" | "S" | "T" => Some(Some(4)),
+        "X" | "A" => Some(None),
+        _ => None,
+    }
+}
+
+///Check the name of a parameter or function.
+fn check_identifier(name: &str) -> Result<(), Error> {
+    if RE_IDENTIFIER.is_match(name) {
+        Ok(())
+    } else {
+        Err(Error::InvalidValue(
+            String::from("parameter name"),
+            name.to_string(),
+        ))
+    }
+}
+
+///Check the parameters, the values can be numbers or expressions.
+fn check_params(reference: &str, params: &[(String, String)]) -> Result<(), Error> {
+    for (name, value) in params {
+        check_identifier(name)?;
+        if value.trim().is_empty() {
+            return Err(Error::InvalidValue(reference.to_string(), name.clone()));
+        }
+    }
+    Ok(())
+}
+
+///The `name=value` pairs of the parameters.
+fn format_params(params: &[(String, String)]) -> String {
+    params
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+///A subcircuit definition with the pins and the default parameters.
+#[derive(Debug, Clone, PartialEq)]
+struct Subcircuit {
+    pins: Vec<String>,
+    params: Vec<(String, String)>,
+    circuit: Circuit,
 }
 
 ///The Circuit struct represents a ngspice netlist.
@@ -45,7 +271,11 @@ pub struct Circuit {
     name: String,
     pathlist: Vec<String>,
     items: Vec<CircuitItem>,
-    subcircuits: IndexMap<String, (Vec<String>, Circuit)>,
+    subcircuits: IndexMap<String, Subcircuit>,
+    models: IndexMap<String, String>,
+    params: IndexMap<String, String>,
+    funcs: IndexMap<String, (Vec<String>, String)>,
+    directives: Vec<String>,
     pub controls: Vec<String>,
     pub options: IndexMap<String, String>,
 }
@@ -57,36 +287,205 @@ impl Circuit {
             pathlist,
             items: Vec::new(),
             subcircuits: IndexMap::new(),
+            models: IndexMap::new(),
+            params: IndexMap::new(),
+            funcs: IndexMap::new(),
+            directives: Vec::new(),
             controls: Vec::new(),
             options: IndexMap::new(),
         }
     }
 
-    //Add a resistor to the netlist.
-    pub fn resistor(&mut self, reference: String, n0: String, n1: String, value: String) {
+    ///Add an element by the spice primitive.
+    ///
+    ///This is used by the netlister and the importers, the nodes and the value
+    ///are checked for the element type.
+    ///
+    /// # Arguments
+    ///
+    /// * `primitive` - The element type, the first letter of the spice element.
+    /// * `reference` - The reference of the element.
+    /// * `nodes`     - The nodes, for `K` the names of the coupled inductors.
+    /// * `value`     - The value or model, for `F`, `H` and `W` preceded by the voltage source.
+    pub fn element(
+        &mut self,
+        primitive: &str,
+        reference: String,
+        nodes: Vec<String>,
+        value: String,
+    ) -> Result<(), Error> {
+        let primitive = primitive.to_uppercase();
+        let Some(expected) = node_count(&primitive) else {
+            return Err(Error::UnknownCircuitElement(format!(
+                "{}{}",
+                primitive, reference
+            )));
+        };
+        if let Some(expected) = expected {
+            if nodes.len() != expected {
+                return Err(Error::InvalidElement(
+                    reference,
+                    format!("{} nodes expected, found {}", expected, nodes.len()),
+                ));
+            }
+        }
+        if primitive == "X" {
+            return self.circuit(reference, nodes, value);
+        }
+        if primitive == "A" {
+            return self.xspice(reference, nodes, value);
+        }
+        let mut n = nodes.into_iter();
+        let mut next = || n.next().unwrap();
+        match primitive.as_str() {
+            "R" => self.resistor(reference, next(), next(), value),
+            "C" => self.capacitor(reference, next(), next(), value),
+            "L" => self.inductor(reference, next(), next(), value),
+            "D" => self.diode(reference, next(), next(), value),
+            "V" => self.voltage(reference, next(), next(), value),
+            "I" => self.current(reference, next(), next(), value),
+            "B" => self.behavioral(reference, next(), next(), value),
+            "J" => self.jfet(reference, next(), next(), next(), value),
+            "Q" => self.bjt(reference, next(), next(), next(), value),
+            "M" => self.mosfet(reference, next(), next(), next(), next(), value),
+            "E" => self.vcvs(reference, next(), next(), next(), next(), value),
+            "G" => self.vccs(reference, next(), next(), next(), next(), value),
+            "S" => self.switch(reference, next(), next(), next(), next(), value),
+            "T" => self.transmission_line(reference, next(), next(), next(), next(), value),
+            "K" => self.coupling(reference, next(), next(), value),
+            "F" | "H" | "W" => {
+                let Some((source, value)) = value.trim().split_once(char::is_whitespace) else {
+                    return Err(Error::InvalidElement(
+                        reference,
+                        String::from("controlling voltage source expected"),
+                    ));
+                };
+                let (source, value) = (source.to_string(), value.trim().to_string());
+                match primitive.as_str() {
+                    "F" => self.cccs(reference, next(), next(), source, value),
+                    "H" => self.ccvs(reference, next(), next(), source, value),
+                    _ => self.current_switch(reference, next(), next(), source, value),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    ///Add a resistor to the netlist.
+    pub fn resistor(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        value: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n0, &n1])?;
+        let value = spice_parameters(&value);
+        check_parameters(&reference, &value)?;
         self.items.push(CircuitItem::R(reference, n0, n1, value));
+        Ok(())
     }
 
-    //Add a capacitor to the netlist.
-    pub fn capacitor(&mut self, reference: String, n0: String, n1: String, value: String) {
+    ///Add a capacitor to the netlist.
+    pub fn capacitor(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        value: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n0, &n1])?;
+        let value = spice_parameters(&value);
+        check_parameters(&reference, &value)?;
         self.items.push(CircuitItem::C(reference, n0, n1, value));
+        Ok(())
+    }
+
+    ///Add an inductor to the netlist.
+    pub fn inductor(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        value: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n0, &n1])?;
+        let value = spice_parameters(&value);
+        check_parameters(&reference, &value)?;
+        self.items.push(CircuitItem::L(reference, n0, n1, value));
+        Ok(())
     }
 
-    //Add a diode to the netlist.
-    pub fn diode(&mut self, reference: String, n0: String, n1: String, value: String) {
+    ///Add a diode to the netlist.
+    pub fn diode(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        value: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n0, &n1])?;
+        check_model(&reference, &value)?;
         self.items.push(CircuitItem::D(reference, n0, n1, value));
+        Ok(())
     }
 
-    //Add a bjt transistor to the netlist.
-    pub fn bjt(&mut self, reference: String, n0: String, n1: String, n2: String, value: String) {
+    ///Add a bjt transistor to the netlist.
+    pub fn bjt(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        n2: String,
+        value: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n0, &n1, &n2])?;
+        check_model(&reference, &value)?;
         self.items
             .push(CircuitItem::Q(reference, n0, n1, n2, value));
+        Ok(())
     }
 
-    //Add a bjt transistor to the netlist.
-    pub fn jfet(&mut self, reference: String, n0: String, n1: String, n2: String, value: String) {
+    ///Add a jfet transistor to the netlist.
+    pub fn jfet(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        n2: String,
+        value: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n0, &n1, &n2])?;
+        check_model(&reference, &value)?;
         self.items
             .push(CircuitItem::J(reference, n0, n1, n2, value));
+        Ok(())
+    }
+
+    ///Add a mosfet to the netlist.
+    ///
+    ///The nodes are drain, gate, source and bulk, the value is the model
+    ///followed by the instance parameters like `NMOS1 W=10u L=1u`.
+    pub fn mosfet(
+        &mut self,
+        reference: String,
+        nd: String,
+        ng: String,
+        ns: String,
+        nb: String,
+        value: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&nd, &ng, &ns, &nb])?;
+        check_model(&reference, &value)?;
+        for token in value.split_whitespace().skip(1) {
+            match token.split_once('=') {
+                Some((_, v)) => check_value(&reference, v)?,
+                None => return Err(Error::InvalidValue(reference, token.to_string())),
+            }
+        }
+        self.items
+            .push(CircuitItem::M(reference, nd, ng, ns, nb, value));
+        Ok(())
     }
 
     pub fn circuit(
@@ -95,6 +494,8 @@ impl Circuit {
         n: Vec<String>,
         value: String,
     ) -> Result<(), Error> {
+        check_nodes(&reference, &n.iter().collect::<Vec<&String>>())?;
+        check_model(&reference, &value)?;
         self.items.push(CircuitItem::X(reference, n, value));
         Ok(())
     }
@@ -104,12 +505,498 @@ impl Circuit {
         n: Vec<String>,
         circuit: Circuit,
     ) -> Result<(), Error> {
-        self.subcircuits.insert(name, (n, circuit));
+        self.parametric_subcircuit(name, n, Vec::new(), circuit)
+    }
+
+    ///Add a subcircuit with parameters, the parameters are the defaults for the instances.
+    ///
+    ///The parameters are used as `{name}` in the values of the subcircuit,
+    ///the instances can override them like `circuit("X1", nodes, "filter rval=2k")`.
+    pub fn parametric_subcircuit(
+        &mut self,
+        name: String,
+        n: Vec<String>,
+        params: Vec<(String, String)>,
+        circuit: Circuit,
+    ) -> Result<(), Error> {
+        check_nodes(&name, &n.iter().collect::<Vec<&String>>())?;
+        check_params(&name, &params)?;
+        self.subcircuits.insert(
+            name,
+            Subcircuit {
+                pins: n,
+                params,
+                circuit,
+            },
+        );
+        Ok(())
+    }
+
+    ///Add a library file or directory to the search path.
+    pub fn library(&mut self, path: String) {
+        if !self.pathlist.contains(&path) {
+            self.pathlist.push(path);
+        }
+    }
+
+    ///Include a library file, with the section the file is loaded with `.lib`.
+    ///
+    ///The file is also added to the search path, the models defined in the
+    ///file are not included twice.
+    pub fn include(&mut self, path: String, section: Option<String>) {
+        let directive = match &section {
+            Some(section) => format!(".lib {} {}", path, section),
+            None => format!(".include {}", path),
+        };
+        if !self.directives.contains(&directive) {
+            self.directives.push(directive);
+        }
+        self.library(path);
+    }
+
+    ///Find the definition of a subcircuit or model in the library paths.
+    ///
+    ///The names defined in several `.lib` sections are resolved with the
+    ///sections of the included libraries.
+    pub fn definition(&self, name: &str) -> Result<Definition, Error> {
+        Library::new(&self.pathlist)?
+            .resolve_in(name, &self.sections())
+            .cloned()
+    }
+
+    ///The sections of the libraries included with `.lib file section`.
+    fn sections(&self) -> Vec<String> {
+        self.directives
+            .iter()
+            .filter_map(|d| d.strip_prefix(".lib "))
+            .filter_map(|d| d.rsplit_once(char::is_whitespace))
+            .map(|(_, section)| section.to_string())
+            .collect()
+    }
+
+    ///Add a `.model` statement, like `model("SW1", "SW(VT=1 VH=0.1)")`.
+    pub fn model(&mut self, name: String, definition: String) {
+        self.models.insert(name, definition);
+    }
+
+    ///Add a voltage source, the value is the source specification like `DC 5 AC 1`.
+    pub fn voltage(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        value: String,
+    ) -> Result<(), Error> {
+        let source = Source::parse_for(&reference, &value)?;
+        self.voltage_source(reference, n1, n2, source)
+    }
+
+    ///Add a voltage source with a typed source specification.
+    pub fn voltage_source(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        source: Source,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n1, &n2])?;
+        source.validate(&reference)?;
+        self.items.push(CircuitItem::V(reference, n1, n2, source));
+        Ok(())
+    }
+
+    ///Add a current source, the value is the source specification like `DC 1m`.
+    pub fn current(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        value: String,
+    ) -> Result<(), Error> {
+        let source = Source::parse_for(&reference, &value)?;
+        self.current_source(reference, n1, n2, source)
+    }
+
+    ///Add a current source with a typed source specification.
+    pub fn current_source(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        source: Source,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n1, &n2])?;
+        source.validate(&reference)?;
+        self.items.push(CircuitItem::I(reference, n1, n2, source));
+        Ok(())
+    }
+
+    ///Add a voltage controlled voltage source.
+    pub fn vcvs(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        nc1: String,
+        nc2: String,
+        gain: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n1, &n2, &nc1, &nc2])?;
+        check_value(&reference, &gain)?;
+        self.items
+            .push(CircuitItem::E(reference, n1, n2, nc1, nc2, gain));
+        Ok(())
+    }
+
+    ///Add a voltage controlled current source.
+    pub fn vccs(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        nc1: String,
+        nc2: String,
+        transconductance: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n1, &n2, &nc1, &nc2])?;
+        check_value(&reference, &transconductance)?;
+        self.items.push(CircuitItem::G(
+            reference,
+            n1,
+            n2,
+            nc1,
+            nc2,
+            transconductance,
+        ));
+        Ok(())
+    }
+
+    ///Add a current controlled current source, the current is measured in the voltage source.
+    pub fn cccs(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        source: String,
+        gain: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n1, &n2, &source])?;
+        check_value(&reference, &gain)?;
+        self.items
+            .push(CircuitItem::F(reference, n1, n2, source, gain));
         Ok(())
     }
 
-    pub fn voltage(&mut self, reference: String, n1: String, n2: String, value: String) {
-        self.items.push(CircuitItem::V(reference, n1, n2, value));
+    ///Add a current controlled voltage source, the current is measured in the voltage source.
+    pub fn ccvs(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        source: String,
+        transresistance: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n1, &n2, &source])?;
+        check_value(&reference, &transresistance)?;
+        self.items
+            .push(CircuitItem::H(reference, n1, n2, source, transresistance));
+        Ok(())
+    }
+
+    ///Add a coupling between two inductors.
+    pub fn coupling(
+        &mut self,
+        reference: String,
+        l1: String,
+        l2: String,
+        coupling: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&l1, &l2])?;
+        check_value(&reference, &coupling)?;
+        if let Some(k) = parse_value(&coupling) {
+            if k <= 0.0 || k > 1.0 {
+                return Err(Error::InvalidValue(reference, coupling));
+            }
+        }
+        self.items.push(CircuitItem::K(reference, l1, l2, coupling));
+        Ok(())
+    }
+
+    ///Add a voltage controlled switch.
+    pub fn switch(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        nc1: String,
+        nc2: String,
+        model: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n1, &n2, &nc1, &nc2])?;
+        check_model(&reference, &model)?;
+        self.items
+            .push(CircuitItem::S(reference, n1, n2, nc1, nc2, model));
+        Ok(())
+    }
+
+    ///Add a current controlled switch, the current is measured in the voltage source.
+    pub fn current_switch(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        source: String,
+        model: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n1, &n2, &source])?;
+        check_model(&reference, &model)?;
+        self.items
+            .push(CircuitItem::W(reference, n1, n2, source, model));
+        Ok(())
+    }
+
+    ///Add a lossless transmission line, the value contains the parameters like `Z0=50 TD=10n`.
+    pub fn transmission_line(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        n3: String,
+        n4: String,
+        value: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n1, &n2, &n3, &n4])?;
+        check_transmission_line(&reference, &value)?;
+        self.items
+            .push(CircuitItem::T(reference, n1, n2, n3, n4, value));
+        Ok(())
+    }
+
+    ///Add a behavioral source, the value is the expression like `V=v(in)*2`.
+    pub fn behavioral(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        expression: String,
+    ) -> Result<(), Error> {
+        check_nodes(&reference, &[&n1, &n2])?;
+        check_behavioral(&reference, &expression)?;
+        self.items
+            .push(CircuitItem::B(reference, n1, n2, expression));
+        Ok(())
+    }
+
+    ///Add a controlled source in the `POLY`, `VALUE` or `TABLE` form.
+    ///
+    ///The specification follows the output nodes, like `POLY(2) (3,0) (4,0) 0 .5 .5`.
+    pub fn polynomial(
+        &mut self,
+        primitive: &str,
+        reference: String,
+        n1: String,
+        n2: String,
+        specification: String,
+    ) -> Result<(), Error> {
+        let prefix = match primitive.to_uppercase().as_str() {
+            "E" => 'E',
+            "G" => 'G',
+            "F" => 'F',
+            "H" => 'H',
+            _ => return Err(Error::UnknownCircuitElement(reference)),
+        };
+        check_nodes(&reference, &[&n1, &n2])?;
+        check_model(&reference, &specification)?;
+        self.items
+            .push(CircuitItem::Poly(prefix, reference, n1, n2, specification));
+        Ok(())
+    }
+
+    ///Add a XSPICE code model element.
+    ///
+    ///The ports are nodes or vectors of nodes in brackets, like `[a b]`,
+    ///with an optional port type like `%vd (a b)`.
+    pub fn xspice(
+        &mut self,
+        reference: String,
+        ports: Vec<String>,
+        model: String,
+    ) -> Result<(), Error> {
+        for port in &ports {
+            let (_, port) = port_type(port);
+            let nodes = port.trim_start_matches('[').trim_end_matches(']');
+            if port.starts_with('[') != port.ends_with(']')
+                || nodes.trim().is_empty()
+                || nodes.contains(['[', ']'])
+            {
+                return Err(Error::InvalidElement(
+                    reference,
+                    format!("invalid port '{}'", port),
+                ));
+            }
+        }
+        check_model(&reference, &model)?;
+        self.items.push(CircuitItem::A(reference, ports, model));
+        Ok(())
+    }
+
+    ///Add a digital gate, the inputs are a vector port.
+    pub fn gate(
+        &mut self,
+        reference: String,
+        inputs: Vec<String>,
+        output: String,
+        model: String,
+    ) -> Result<(), Error> {
+        check_nodes(
+            &reference,
+            &inputs.iter().chain([&output]).collect::<Vec<&String>>(),
+        )?;
+        self.xspice(
+            reference,
+            vec![format!("[{}]", inputs.join(" ")), output],
+            model,
+        )
+    }
+
+    ///Add an `adc_bridge` or `dac_bridge`, the inputs and outputs are vector ports.
+    pub fn bridge(
+        &mut self,
+        reference: String,
+        inputs: Vec<String>,
+        outputs: Vec<String>,
+        model: String,
+    ) -> Result<(), Error> {
+        check_nodes(
+            &reference,
+            &inputs.iter().chain(&outputs).collect::<Vec<&String>>(),
+        )?;
+        if inputs.len() != outputs.len() {
+            return Err(Error::InvalidElement(
+                reference,
+                format!(
+                    "{} inputs and {} outputs are not bridged",
+                    inputs.len(),
+                    outputs.len()
+                ),
+            ));
+        }
+        self.xspice(
+            reference,
+            vec![
+                format!("[{}]", inputs.join(" ")),
+                format!("[{}]", outputs.join(" ")),
+            ],
+            model,
+        )
+    }
+
+    ///Add the `.model` statement of a XSPICE code model.
+    pub fn code_model(
+        &mut self,
+        name: String,
+        model: CodeModel,
+        params: Vec<(String, String)>,
+    ) -> Result<(), Error> {
+        check_params(&name, &params)?;
+        self.model(
+            name,
+            format!("{}({})", model.name(), format_params(&params)),
+        );
+        Ok(())
+    }
+
+    ///The digital nodes of the XSPICE elements with a code model in the circuit.
+    ///
+    ///The nodes in the subcircuit instances are named with the instance,
+    ///like `x1.q`, the subcircuit pins with the connected nodes.
+    pub fn digital_nodes(&self) -> Vec<String> {
+        let mut nodes: Vec<String> = Vec::new();
+        self.event_nodes(&[], &mut nodes);
+        nodes
+    }
+
+    ///Collect the digital nodes, the models and subcircuits are searched in
+    ///the circuit and then in the enclosing circuits.
+    fn event_nodes(&self, scopes: &[&Circuit], nodes: &mut Vec<String>) {
+        let scopes: Vec<&Circuit> = [self].into_iter().chain(scopes.iter().copied()).collect();
+        for item in &self.items {
+            match item {
+                CircuitItem::A(_, ports, model) => {
+                    let Some(kind) =
+                        scopes
+                            .iter()
+                            .find_map(|c| c.models.get(model))
+                            .and_then(|m| {
+                                CodeModel::from_name(m.split('(').next().unwrap_or("").trim())
+                            })
+                    else {
+                        continue;
+                    };
+                    for (index, port) in ports.iter().enumerate() {
+                        if !kind.is_digital(index) || is_analog(port) {
+                            continue;
+                        }
+                        for node in port_nodes(port) {
+                            if !nodes.contains(&node) {
+                                nodes.push(node);
+                            }
+                        }
+                    }
+                }
+                CircuitItem::X(reference, pins, value) => {
+                    let subcircuit = value.split_whitespace().next().unwrap_or("");
+                    let Some(subcircuit) = scopes.iter().find_map(|c| {
+                        c.subcircuits
+                            .iter()
+                            .find(|(key, _)| key.eq_ignore_ascii_case(subcircuit))
+                            .map(|(_, s)| s)
+                    }) else {
+                        continue;
+                    };
+                    let mut inner: Vec<String> = Vec::new();
+                    subcircuit.circuit.event_nodes(&scopes, &mut inner);
+                    for node in inner {
+                        let node = match subcircuit.pins.iter().position(|p| *p == node) {
+                            Some(index) => pins.get(index).cloned().unwrap_or(node),
+                            None => format!("{}.{}", name('X', reference).to_lowercase(), node),
+                        };
+                        if !nodes.contains(&node) {
+                            nodes.push(node);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    ///Add a `.param` statement, like `param("gain", "10")`.
+    ///
+    ///The value can be a number or an expression like `{rval*2}`, the parameters
+    ///are used in the element values like `{gain}`.
+    pub fn param(&mut self, name: String, value: String) -> Result<(), Error> {
+        check_params(&name, &[(name.clone(), value.clone())])?;
+        self.params.insert(name, value);
+        Ok(())
+    }
+
+    ///Add a `.func` statement, like `func("db", vec!["x"], "{20*log10(x)}")`.
+    pub fn func(
+        &mut self,
+        name: String,
+        args: Vec<String>,
+        expression: String,
+    ) -> Result<(), Error> {
+        check_identifier(&name)?;
+        for arg in &args {
+            check_identifier(arg)?;
+        }
+        if expression.trim().is_empty() {
+            return Err(Error::InvalidValue(name, expression));
+        }
+        self.funcs.insert(name, (args, expression));
+        Ok(())
     }
 
     pub fn option(&mut self, option: String, value: String) {
@@ -147,166 +1034,199 @@ impl Circuit {
         Ok(())
     }
 
+    ///Change the value of an element, the value is validated like when the element is added.
+    ///
+    ///The value of a `.param` is changed by the parameter name and the parameter of
+    ///a subcircuit instance by the reference and the name, like `X1.rval`.
     pub fn set_value(&mut self, reference: &str, value: &str) -> Result<(), Error> {
-        for item in &mut self.items.iter_mut() {
-            match item {
-                CircuitItem::R(r, _, _, ref mut v) => {
-                    if reference == r {
-                        *v = value.to_string();
-                        return Ok(());
-                    }
-                }
-                CircuitItem::C(r, _, _, ref mut v) => {
-                    if reference == r {
-                        *v = value.to_string();
-                        return Ok(());
-                    }
-                }
-                CircuitItem::D(r, _, _, ref mut v) => {
-                    if reference == r {
-                        *v = value.to_string();
-                        return Ok(());
-                    }
-                }
-                CircuitItem::J(_, _, _, _, _) => {}
-                CircuitItem::Q(_, _, _, _, _) => {}
-                CircuitItem::X(_, _, _) => {}
-                CircuitItem::V(r, _, _, ref mut v) => {
-                    if reference == r {
-                        *v = value.to_string();
-                        return Ok(());
-                    }
-                }
+        let Some(item) = self.items.iter_mut().find(|i| i.is(reference)) else {
+            if let Some(param) = self.params.get_mut(reference) {
+                check_params(reference, &[(reference.to_string(), value.to_string())])?;
+                *param = value.to_string();
+                return Ok(());
+            }
+            if let Some((instance, name)) = reference.split_once('.') {
+                return self.set_instance_param(instance, name, value);
+            }
+            return Err(Error::UnknownCircuitElement(reference.to_string()));
+        };
+        match item {
+            CircuitItem::R(_, _, _, v)
+            | CircuitItem::C(_, _, _, v)
+            | CircuitItem::L(_, _, _, v) => {
+                let value = spice_parameters(value);
+                check_parameters(reference, &value)?;
+                *v = value;
+            }
+            CircuitItem::V(_, _, _, source) | CircuitItem::I(_, _, _, source) => {
+                let new = Source::parse_for(reference, value)?;
+                new.validate(reference)?;
+                *source = new;
+            }
+            CircuitItem::E(_, _, _, _, _, v)
+            | CircuitItem::G(_, _, _, _, _, v)
+            | CircuitItem::F(_, _, _, _, v)
+            | CircuitItem::H(_, _, _, _, v)
+            | CircuitItem::K(_, _, _, v) => {
+                check_value(reference, value)?;
+                *v = value.to_string();
+            }
+            CircuitItem::D(_, _, _, v)
+            | CircuitItem::J(_, _, _, _, v)
+            | CircuitItem::Q(_, _, _, _, v)
+            | CircuitItem::M(_, _, _, _, _, v)
+            | CircuitItem::X(_, _, v)
+            | CircuitItem::A(_, _, v)
+            | CircuitItem::S(_, _, _, _, _, v)
+            | CircuitItem::W(_, _, _, _, v)
+            | CircuitItem::Poly(_, _, _, _, v) => {
+                check_model(reference, value)?;
+                *v = value.to_string();
+            }
+            CircuitItem::T(_, _, _, _, _, v) => {
+                check_transmission_line(reference, value)?;
+                *v = value.to_string();
+            }
+            CircuitItem::B(_, _, _, v) => {
+                check_behavioral(reference, value)?;
+                *v = value.to_string();
             }
         }
-        Err(Error::UnknownCircuitElement(reference.to_string()))
+        Ok(())
     }
 }
 
 impl Circuit {
+    ///Set the parameter of a subcircuit instance.
+    ///
+    ///The name must be a parameter of the subcircuit, the parameters of the
+    ///subcircuits in the libraries are not checked.
+    fn set_instance_param(&mut self, instance: &str, name: &str, value: &str) -> Result<(), Error> {
+        let unknown = || Error::UnknownCircuitElement(format!("{}.{}", instance, name));
+        let Some(CircuitItem::X(_, _, model)) = self.items.iter().find(|i| i.is(instance)) else {
+            return Err(unknown());
+        };
+        let (subcircuit, params) = model
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or((model.trim(), ""));
+        if let Some((_, definition)) = self
+            .subcircuits
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(subcircuit))
+        {
+            if !definition
+                .params
+                .iter()
+                .any(|(n, _)| n.eq_ignore_ascii_case(name))
+            {
+                return Err(unknown());
+            }
+        }
+        let mut params = assignments(0, strip_params(params))
+            .map_err(|_| Error::InvalidValue(instance.to_string(), model.clone()))?;
+        match params
+            .iter_mut()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        {
+            Some((_, v)) => *v = value.to_string(),
+            None => params.push((name.to_string(), value.to_string())),
+        }
+        check_params(instance, &params)?;
+        let model = format!("{} {}", subcircuit, format_params(&params));
+        if let Some(CircuitItem::X(_, _, current)) = self.items.iter_mut().find(|i| i.is(instance))
+        {
+            *current = model;
+        }
+        Ok(())
+    }
+
+    ///Find the library file of the subcircuit or model.
+    ///
+    ///Returns the file of the definition and the files it includes.
     pub fn get_includes(&self, key: String) -> Result<IndexMap<String, String>, Error> {
+        let library = Library::new(&self.pathlist)?;
+        let definition = library.resolve_in(&key, &self.sections())?;
         let mut result: IndexMap<String, String> = IndexMap::new();
-        for path in &self.pathlist {
-            let content = match fs::read_dir(path) {
-                Ok(content) => content,
-                Err(e) => return Err(Error::DirectoryError(path.to_string(), e.to_string())),
-            };
-            for entry in content {
-                let dir = entry.unwrap();
-                if dir.path().is_file() {
-                    let content = fs::read_to_string(dir.path())?;
-                    for cap in RE_SUBCKT.captures_iter(&content) {
-                        let text1 = cap.get(1).map_or("", |m| m.as_str());
-                        if text1 == key {
-                            result.insert(key, dir.path().to_str().unwrap().to_string());
-                            if let Some(caps) = RE_INCLUDE.captures(&content) {
-                                for cap in caps.iter().skip(1) {
-                                    let text1 = cap.map_or("", |m| m.as_str());
-                                    if !text1.contains('/') {
-                                        //when there is no slash i could be
-                                        //a relative path.
-                                        let mut parent = dir
-                                            .path()
-                                            .parent()
-                                            .unwrap()
-                                            .to_str()
-                                            .unwrap()
-                                            .to_string();
-                                        parent += "/";
-                                        parent += text1;
-                                        result.insert(text1.to_string(), parent.to_string());
-                                    } else {
-                                        result.insert(text1.to_string(), text1.to_string());
-                                    }
-                                }
-                            }
-                            return Ok(result);
-                        }
-                    }
-                    for cap in RE_MODEL.captures_iter(&content) {
-                        let text1 = cap.get(1).map_or("", |m| m.as_str());
-                        if text1 == key {
-                            result.insert(key, dir.path().to_str().unwrap().to_string());
-                            if let Some(caps) = RE_INCLUDE.captures(&content) {
-                                for cap in caps.iter().skip(1) {
-                                    let text1 = cap.map_or("", |m| m.as_str());
-                                    if !text1.contains('/') {
-                                        //when there is no slash i could be
-                                        //a relative path.
-                                        let mut parent = dir
-                                            .path()
-                                            .parent()
-                                            .unwrap()
-                                            .to_str()
-                                            .unwrap()
-                                            .to_string();
-                                        parent += "/";
-                                        parent += text1;
-                                        result.insert(text1.to_string(), parent.to_string());
-                                    } else {
-                                        result.insert(text1.to_string(), text1.to_string());
-                                    }
-                                }
-                            }
-                            return Ok(result);
-                        }
-                    }
-                }
-            }
+        result.insert(key, definition.path.display().to_string());
+        for include in &definition.includes {
+            let include = include.display().to_string();
+            result.insert(include.clone(), include);
         }
-        Err(Error::SpiceModelNotFound(key))
+        Ok(result)
     }
 
-    fn includes(&self) -> Result<Vec<String>, Error> {
-        let mut includes: IndexMap<String, String> = IndexMap::new();
+    ///The library includes of the models and subcircuits, the definitions are searched in
+    ///the circuit and then in the enclosing circuits, the names are not case sensitive.
+    fn includes(&self, scopes: &[&Circuit]) -> Result<Vec<String>, Error> {
+        let mut library: Option<Library> = None;
+        let sections = self.sections();
+        let mut includes: IndexMap<String, ()> = IndexMap::new();
+        for directive in &self.directives {
+            includes.insert(directive.clone(), ());
+        }
         for item in &self.items {
-            if let CircuitItem::X(_, _, value) = item {
-                if !includes.contains_key(value) && !self.subcircuits.contains_key(value) {
-                    let incs = self.get_includes(value.to_string())?;
-                    for (key, value) in incs {
-                        includes.entry(key).or_insert(value);
-                    }
-                }
-            } else if let CircuitItem::J(_, _, _, _, value) = item {
-                if !includes.contains_key(value) && !self.subcircuits.contains_key(value) {
-                    let incs = self.get_includes(value.to_string())?;
-                    for (key, value) in incs {
-                        includes.entry(key).or_insert(value);
-                    }
-                }
-            } else if let CircuitItem::Q(_, _, _, _, value) = item {
-                if !includes.contains_key(value) && !self.subcircuits.contains_key(value) {
-                    let incs = self.get_includes(value.to_string())?;
-                    for (key, value) in incs {
-                        includes.entry(key).or_insert(value);
-                    }
-                }
-            } else if let CircuitItem::D(_, _, _, value) = item {
-                if !includes.contains_key(value) && !self.subcircuits.contains_key(value) {
-                    let incs = self.get_includes(value.to_string())?;
-                    for (key, value) in incs {
-                        includes.entry(key).or_insert(value);
-                    }
-                }
+            let Some(model) = item.model() else {
+                continue;
+            };
+            if scopes.iter().any(|c| {
+                c.subcircuits
+                    .keys()
+                    .chain(c.models.keys())
+                    .any(|key| key.eq_ignore_ascii_case(model))
+            }) {
+                continue;
+            }
+            if library.is_none() {
+                library = Some(Library::new(&self.pathlist)?);
+            }
+            let definition = library.as_ref().unwrap().resolve_in(model, &sections)?;
+            includes.insert(definition.directive(), ());
+            for include in &definition.includes {
+                includes.insert(format!(".include {}", include.display()), ());
             }
         }
-        let mut result = Vec::new();
-        for (_, v) in includes {
-            result.push(format!(".include {}\n", v).to_string());
-        }
-        Ok(result)
+        Ok(includes.into_keys().map(|i| format!("{}\n", i)).collect())
     }
 
     pub fn to_str(&self, close: bool) -> Result<Vec<String>, Error> {
+        self.lines(&[], close)
+    }
+
+    ///The netlist lines, the subcircuits are written with the enclosing circuits.
+    fn lines(&self, scopes: &[&Circuit], close: bool) -> Result<Vec<String>, Error> {
+        let scopes: Vec<&Circuit> = [self].into_iter().chain(scopes.iter().copied()).collect();
         let mut res = Vec::new();
 
         res.push(String::from(".title auto generated netlist file."));
 
-        res.append(&mut self.includes()?);
-        for (key, value) in &self.subcircuits {
-            let nodes = value.0.join(" ");
-            res.push(format!(".subckt {} {}", key, nodes));
-            res.append(&mut value.1.to_str(false).unwrap());
+        res.append(&mut self.includes(&scopes)?);
+        for (key, value) in &self.params {
+            res.push(format!(".param {}={}", key, value));
+        }
+        for (key, (args, expression)) in &self.funcs {
+            res.push(format!(".func {}({}) {}", key, args.join(", "), expression));
+        }
+        for (key, subcircuit) in &self.subcircuits {
+            let nodes = subcircuit.pins.join(" ");
+            if subcircuit.params.is_empty() {
+                res.push(format!(".subckt {} {}", key, nodes));
+            } else {
+                res.push(format!(
+                    ".subckt {} {} params: {}",
+                    key,
+                    nodes,
+                    format_params(&subcircuit.params)
+                ));
+            }
+            //the title is only written for the netlist.
+            res.extend(
+                subcircuit
+                    .circuit
+                    .lines(&scopes, false)?
+                    .into_iter()
+                    .skip(1),
+            );
             res.push(".ends".to_string());
         }
 
@@ -314,55 +1234,138 @@ impl Circuit {
             res.push(format!(".{} {}", key, value));
         }
 
+        for (key, value) in &self.models {
+            res.push(format!(".model {} {}", key, value));
+        }
+
         for item in &self.items {
-            match item {
+            res.push(match item {
                 CircuitItem::R(reference, n0, n1, value) => {
-                    if reference.starts_with('R') {
-                        res.push(format!("{} {} {} {}", reference, n0, n1, value));
-                    } else {
-                        res.push(format!("R{} {} {} {}", reference, n0, n1, value));
-                    }
+                    format!("{} {} {} {}", name('R', reference), n0, n1, value)
                 }
                 CircuitItem::C(reference, n0, n1, value) => {
-                    if reference.starts_with('C') {
-                        res.push(format!("{} {} {} {}", reference, n0, n1, value));
-                    } else {
-                        res.push(format!("C{} {} {} {}", reference, n0, n1, value));
-                    }
+                    format!("{} {} {} {}", name('C', reference), n0, n1, value)
+                }
+                CircuitItem::L(reference, n0, n1, value) => {
+                    format!("{} {} {} {}", name('L', reference), n0, n1, value)
                 }
                 CircuitItem::D(reference, n0, n1, value) => {
-                    if reference.starts_with('D') {
-                        res.push(format!("{} {} {} {}", reference, n0, n1, value));
-                    } else {
-                        res.push(format!("D{} {} {} {}", reference, n0, n1, value));
-                    }
+                    format!("{} {} {} {}", name('D', reference), n0, n1, value)
                 }
                 CircuitItem::Q(reference, n0, n1, n2, value) => {
-                    if reference.starts_with('Q') {
-                        res.push(format!("{} {} {} {} {}", reference, n0, n1, n2, value));
-                    } else {
-                        res.push(format!("Q{} {} {} {} {}", reference, n0, n1, n2, value));
-                    }
+                    format!("{} {} {} {} {}", name('Q', reference), n0, n1, n2, value)
                 }
                 CircuitItem::J(reference, n0, n1, n2, value) => {
-                    if reference.starts_with('Q') {
-                        res.push(format!("{} {} {} {} {}", reference, n0, n1, n2, value));
-                    } else {
-                        res.push(format!("J{} {} {} {} {}", reference, n0, n1, n2, value));
-                    }
+                    format!("{} {} {} {} {}", name('J', reference), n0, n1, n2, value)
                 }
+                CircuitItem::M(reference, nd, ng, ns, nb, value) => format!(
+                    "{} {} {} {} {} {}",
+                    name('M', reference),
+                    nd,
+                    ng,
+                    ns,
+                    nb,
+                    value
+                ),
                 CircuitItem::X(reference, n, value) => {
                     let mut nodes: String = String::new();
                     for _n in n {
                         nodes += _n;
                         nodes += " ";
                     }
-                    res.push(format!("X{} {}{}", reference, nodes, value));
+                    format!("{} {}{}", name('X', reference), nodes, value)
                 }
-                CircuitItem::V(reference, n0, n1, value) => {
-                    res.push(format!("V{} {} {} {}", reference, n0, n1, value));
+                CircuitItem::V(reference, n0, n1, source) => {
+                    format!("{} {} {} {}", name('V', reference), n0, n1, source)
                 }
-            }
+                CircuitItem::I(reference, n0, n1, source) => {
+                    format!("{} {} {} {}", name('I', reference), n0, n1, source)
+                }
+                CircuitItem::E(reference, n0, n1, nc0, nc1, value) => format!(
+                    "{} {} {} {} {} {}",
+                    name('E', reference),
+                    n0,
+                    n1,
+                    nc0,
+                    nc1,
+                    value
+                ),
+                CircuitItem::G(reference, n0, n1, nc0, nc1, value) => format!(
+                    "{} {} {} {} {} {}",
+                    name('G', reference),
+                    n0,
+                    n1,
+                    nc0,
+                    nc1,
+                    value
+                ),
+                CircuitItem::F(reference, n0, n1, source, value) => {
+                    format!(
+                        "{} {} {} {} {}",
+                        name('F', reference),
+                        n0,
+                        n1,
+                        source,
+                        value
+                    )
+                }
+                CircuitItem::H(reference, n0, n1, source, value) => {
+                    format!(
+                        "{} {} {} {} {}",
+                        name('H', reference),
+                        n0,
+                        n1,
+                        source,
+                        value
+                    )
+                }
+                CircuitItem::K(reference, l0, l1, value) => {
+                    format!("{} {} {} {}", name('K', reference), l0, l1, value)
+                }
+                CircuitItem::S(reference, n0, n1, nc0, nc1, model) => format!(
+                    "{} {} {} {} {} {}",
+                    name('S', reference),
+                    n0,
+                    n1,
+                    nc0,
+                    nc1,
+                    model
+                ),
+                CircuitItem::W(reference, n0, n1, source, model) => {
+                    format!(
+                        "{} {} {} {} {}",
+                        name('W', reference),
+                        n0,
+                        n1,
+                        source,
+                        model
+                    )
+                }
+                CircuitItem::T(reference, n0, n1, n2, n3, value) => format!(
+                    "{} {} {} {} {} {}",
+                    name('T', reference),
+                    n0,
+                    n1,
+                    n2,
+                    n3,
+                    value
+                ),
+                CircuitItem::B(reference, n0, n1, expression) => {
+                    format!("{} {} {} {}", name('B', reference), n0, n1, expression)
+                }
+                CircuitItem::A(reference, ports, model) => {
+                    format!("{} {} {}", name('A', reference), ports.join(" "), model)
+                }
+                CircuitItem::Poly(prefix, reference, n0, n1, specification) => {
+                    format!(
+                        "{} {} {} {}",
+                        name(*prefix, reference),
+                        n0,
+                        n1,
+                        specification
+                    )
+                }
+            });
         }
 
         if close {
@@ -371,18 +1374,3 @@ impl Circuit {
         Ok(res)
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use super::RE_SUBCKT;
-
-    #[test]
-    fn test_subckt_regext() {
-        let cap = RE_SUBCKT.captures_iter(".SUBCKT CMOS4007 1 2 3 4 5 6 7 8 9 10 11 12 13 14");
-        let mut res = String::from("not found");
-        for c in cap {
-            res = c.get(1).map_or("", |m| m.as_str()).to_string();
-        }
-        assert_eq!("CMOS4007", res)
-    }
-}
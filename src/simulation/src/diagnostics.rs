@@ -0,0 +1,144 @@
+//! Parse the ngspice output into structured diagnostics.
+//!
+//! ngspice reports problems only as text on stderr. The lines are matched
+//! against the known messages to return the offending element to the caller.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref RE_UNKNOWN_SUBCKT: regex::Regex =
+        Regex::new(r"(?i)unknown subckt:\s*(\S+)\s.*?(\S+)\s*$").unwrap();
+    static ref RE_UNKNOWN_MODEL: regex::Regex =
+        Regex::new(r"(?i)unable to find definition of model\s+(\S+)").unwrap();
+    static ref RE_INVALID_MODEL: regex::Regex =
+        Regex::new(r"(?i)could not find a valid modelname").unwrap();
+    static ref RE_ERROR_LINE: regex::Regex =
+        Regex::new(r"(?i)^error on line \d+").unwrap();
+    static ref RE_SINGULAR: regex::Regex =
+        Regex::new(r"(?i)singular matrix:?\s*check node\s+(\S+)").unwrap();
+    static ref RE_TIMESTEP: regex::Regex = Regex::new(
+        r#"(?i)timestep too small;?\s*(?:time\s*=\s*([^,\s]+),?\s*timestep\s*=\s*[^:\s]+:?)?\s*(?:trouble with (?:node|instance)\s+"?([^"\s]+)"?)?"#
+    )
+    .unwrap();
+    static ref RE_UNKNOWN_NODE: regex::Regex =
+        Regex::new(r"(?i)(?:no such (?:vector|node)|(?:vector|node) (\S+) not found)\s*(\S*)")
+            .unwrap();
+}
+
+///A problem reported by ngspice.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Diagnostic {
+    ///The model or subcircuit for the element is not defined.
+    UnknownModel { element: String, model: String },
+    ///The matrix is singular, usually a floating node.
+    SingularMatrix { node: String },
+    ///The transient analysis aborted, the timestep became too small.
+    TimestepTooSmall { element: String, time: String },
+    ///A node or vector that does not exist is referenced.
+    UnknownNode { node: String },
+    ///Any other error message.
+    Error(String),
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Diagnostic::UnknownModel { element, model } => {
+                write!(f, "unknown model '{}' for element '{}'", model, element)
+            }
+            Diagnostic::SingularMatrix { node } => {
+                write!(f, "singular matrix, check node '{}'", node)
+            }
+            Diagnostic::TimestepTooSmall { element, time } => {
+                write!(
+                    f,
+                    "timestep too small at {}, trouble with '{}'",
+                    time, element
+                )
+            }
+            Diagnostic::UnknownNode { node } => write!(f, "unknown node '{}'", node),
+            Diagnostic::Error(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+///Get the model name from a netlist line, the last token which is not a parameter.
+fn model_name(line: &str) -> String {
+    line.split_whitespace()
+        .skip(1)
+        .filter(|t| !t.contains('=') && t.parse::<f64>().is_err())
+        .last()
+        .unwrap_or("")
+        .to_string()
+}
+
+///Parse the ngspice output lines into diagnostics.
+///
+///The lines can be prefixed with `stdout` or `stderr` as ngspice sends them.
+pub fn parse_diagnostics(lines: &[String]) -> Vec<Diagnostic> {
+    let mut result = Vec::new();
+    let mut context: Option<String> = None;
+    let mut lines = lines
+        .iter()
+        .flat_map(|l| l.lines())
+        .map(|l| {
+            l.strip_prefix("stderr ")
+                .or_else(|| l.strip_prefix("stdout "))
+                .unwrap_or(l)
+                .trim()
+        })
+        .filter(|l| !l.is_empty());
+    while let Some(line) = lines.next() {
+        if RE_ERROR_LINE.is_match(line) {
+            context = lines.next().map(|l| l.to_string());
+            continue;
+        }
+        let diagnostic = if let Some(cap) = RE_UNKNOWN_SUBCKT.captures(line) {
+            Diagnostic::UnknownModel {
+                element: cap[1].to_string(),
+                model: cap[2].to_string(),
+            }
+        } else if let Some(cap) = RE_UNKNOWN_MODEL.captures(line) {
+            Diagnostic::UnknownModel {
+                element: context
+                    .as_ref()
+                    .and_then(|c| c.split_whitespace().next())
+                    .unwrap_or("")
+                    .to_string(),
+                model: cap[1].to_string(),
+            }
+        } else if RE_INVALID_MODEL.is_match(line) {
+            let element = context.clone().unwrap_or_default();
+            Diagnostic::UnknownModel {
+                element: element.split_whitespace().next().unwrap_or("").to_string(),
+                model: model_name(&element),
+            }
+        } else if let Some(cap) = RE_SINGULAR.captures(line) {
+            Diagnostic::SingularMatrix {
+                node: cap[1].to_string(),
+            }
+        } else if let Some(cap) = RE_TIMESTEP.captures(line) {
+            Diagnostic::TimestepTooSmall {
+                element: cap.get(2).map_or("", |m| m.as_str()).to_string(),
+                time: cap.get(1).map_or("", |m| m.as_str()).to_string(),
+            }
+        } else if let Some(cap) = RE_UNKNOWN_NODE.captures(line) {
+            Diagnostic::UnknownNode {
+                node: cap
+                    .get(1)
+                    .or(cap.get(2))
+                    .map_or("", |m| m.as_str())
+                    .to_string(),
+            }
+        } else if line.to_lowercase().starts_with("error") {
+            Diagnostic::Error(line.to_string())
+        } else {
+            continue;
+        };
+        if !result.contains(&diagnostic) {
+            result.push(diagnostic);
+        }
+    }
+    result
+}
@@ -1,3 +1,5 @@
+use crate::diagnostics::Diagnostic;
+
 #[derive(thiserror::Error, Debug, Clone)]
 pub enum Error {
     #[error("Library not found {1} (2).")]
@@ -8,20 +10,55 @@ pub enum Error {
     PropertyNotFound(String),
     #[error("Spice model not found: {0}")]
     SpiceModelNotFound(String),
+    #[error("Spice model {0} is defined more than once: {1}")]
+    DuplicateModel(String, String),
+    #[error("Include {1} not found in {0}")]
+    IncludeNotFound(String, String),
+    #[error("Unit {1} of {0} is not placed in the schema")]
+    UnitNotFound(String, usize),
     #[error("Unknown circuit element {0}")]
     UnknownCircuitElement(String),
+    #[error("Invalid value for {0}: '{1}'")]
+    InvalidValue(String, String),
+    #[error("Invalid circuit element {0}: {1}")]
+    InvalidElement(String, String),
     #[error("File manipulatuion error {0}.")]
     IoError(String),
     #[error("NgSpice Error: \"{0}\"")]
     NgSpiceError(String),
+    #[error("NgSpice Error: {}", .0.iter().map(|d| d.to_string()).collect::<Vec<String>>().join(", "))]
+    Diagnostics(Vec<Diagnostic>),
+    #[error("Can not run ngspice: {0}")]
+    NgSpiceProcess(String),
+    #[error("Can not load schematic {0}")]
+    SexpError(String),
+    #[error("Can not parse netlist line {0}: {1}")]
+    ParseError(usize, String),
+    #[error("Can not read rawfile {0}: {1}")]
+    RawFile(String, String),
 }
 impl std::convert::From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Self {
         Error::IoError(err.to_string())
     }
 }
+impl std::convert::From<hound::Error> for Error {
+    fn from(err: hound::Error) -> Self {
+        Error::IoError(err.to_string())
+    }
+}
+impl std::convert::From<parquet::errors::ParquetError> for Error {
+    fn from(err: parquet::errors::ParquetError) -> Self {
+        Error::IoError(err.to_string())
+    }
+}
 impl std::convert::From<ngspice::NgSpiceError> for Error {
     fn from(err: ngspice::NgSpiceError) -> Self {
         Error::IoError(err.to_string())
     }
 }
+impl std::convert::From<sexp::Error> for Error {
+    fn from(err: sexp::Error) -> Self {
+        Error::SexpError(err.to_string())
+    }
+}
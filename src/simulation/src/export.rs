@@ -0,0 +1,371 @@
+//! Export the connectivity of a design.
+//!
+//! The nets of a [`Project`] can be written as KiCad netlist, compatible with
+//! the netlist from `kicad-cli sch export netlist`, as JSON graph of the nets
+//! and the connected pins and as Graphviz DOT graph for the documentation.
+//!
+//! # Examples
+//! ```
+//! use std::path::Path;
+//! use simulation::Project;
+//!
+//! let project = Project::load(Path::new("tests/hierarchy/root.kicad_sch")).unwrap();
+//! let connectivity = project.connectivity().unwrap();
+//! let gnd = connectivity.nets.iter().find(|n| n.name == "GND").unwrap();
+//! assert_eq!(3, gnd.nodes.len());
+//! assert!(connectivity.to_kicad().unwrap().starts_with("(export"));
+//! ```
+
+use indexmap::IndexMap;
+use ndarray::Array1;
+use sexp::{
+    el,
+    math::{Shape, Transform},
+    utils, Builder, SexpProperty, SexpValueQuery, SexpWriter,
+};
+
+use crate::{
+    error::Error,
+    netlist::{natural_cmp, Point},
+    project::Project,
+};
+
+///A component of the design.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub reference: String,
+    pub value: String,
+    pub footprint: String,
+    ///The library symbol, like `Device:R`.
+    pub lib_id: String,
+    ///The path of the sheet instance.
+    pub sheet: String,
+    ///The uuids of the sheet instance.
+    pub tstamps: String,
+    ///The uuid of the symbol.
+    pub uuid: String,
+}
+
+///A pin connected to a net.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetNode {
+    pub reference: String,
+    ///The pin number.
+    pub pin: String,
+    ///The pin name, if the pin is named.
+    pub function: Option<String>,
+    ///The electrical type of the pin, like `passive` or `power_in`.
+    pub pin_type: String,
+}
+
+///A net with the connected pins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Net {
+    pub code: usize,
+    pub name: String,
+    pub nodes: Vec<NetNode>,
+}
+
+///The components and the nets of a design.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connectivity {
+    ///The root schematic file.
+    pub source: String,
+    pub components: Vec<Component>,
+    pub nets: Vec<Net>,
+}
+
+///The name KiCad uses for a net with a single pin.
+fn unconnected(reference: &str, pin: &str) -> String {
+    format!("unconnected-({}-Pad{})", reference, pin)
+}
+
+impl Project {
+    ///Collect the components and the nets of the design.
+    ///
+    ///Power symbols and symbols which are not on the board are not exported,
+    ///pins without a connection get their own `unconnected-(R1-Pad1)` net. The local nets are named with the
+    ///sheet path, like `/MID` in the root sheet.
+    pub fn connectivity(&self) -> Result<Connectivity, Error> {
+        let netlists = self.netlists()?;
+        let nets = self.net_names(&netlists);
+        let mut components: IndexMap<String, Component> = IndexMap::new();
+        let mut pins: IndexMap<(String, String), (Option<String>, NetNode)> = IndexMap::new();
+        for (index, netlist) in netlists.iter().enumerate() {
+            let sheet = &self.sheets[index];
+            let root = sheet.tree.root()?;
+            for symbol in root.query(el::SYMBOL) {
+                let lib_id: String = symbol.value(el::LIB_ID).unwrap();
+                let on_board: Option<bool> = symbol.value("on_board");
+                if lib_id.starts_with("power:") || on_board == Some(false) {
+                    continue;
+                }
+                let Some(reference) = self.reference(index, symbol) else {
+                    return Err(Error::PropertyNotFound(lib_id));
+                };
+                let Some(lib) = utils::get_library(root, &lib_id) else {
+                    return Err(Error::LibraryNotFound(lib_id));
+                };
+                components
+                    .entry(reference.clone())
+                    .or_insert_with(|| Component {
+                        reference: reference.clone(),
+                        value: symbol.property(el::PROPERTY_VALUE).unwrap_or_default(),
+                        footprint: symbol.property("Footprint").unwrap_or_default(),
+                        lib_id: lib_id.clone(),
+                        sheet: sheet.path.clone(),
+                        tstamps: if sheet.uuids.is_empty() {
+                            String::from("/")
+                        } else {
+                            format!("/{}/", sheet.uuids.join("/"))
+                        },
+                        uuid: symbol
+                            .query("uuid")
+                            .next()
+                            .and_then(|u| u.get(0))
+                            .unwrap_or_default(),
+                    });
+                let unit: usize = symbol.value(el::SYMBOL_UNIT).unwrap();
+                let Ok(lib_pins) = utils::pins(lib, unit) else {
+                    continue;
+                };
+                for pin in lib_pins {
+                    let number: String = pin
+                        .query(el::PIN_NUMBER)
+                        .next()
+                        .and_then(|n| n.get(0))
+                        .unwrap_or_default();
+                    let name: Option<String> = pin.query("name").next().and_then(|n| n.get(0));
+                    let at: Array1<f64> = utils::at(pin).unwrap();
+                    let pts = Shape::transform(symbol, &at);
+                    let net = netlist
+                        .node_name(&Point::new(pts[0], pts[1]))
+                        .filter(|n| n != "NC")
+                        .map(|n| nets.get(&(index, n.clone())).cloned().unwrap_or(n));
+                    let key = (reference.clone(), number.clone());
+                    //the common pins of the units are exported once.
+                    if pins
+                        .get(&key)
+                        .is_some_and(|(n, _)| n.is_some() || net.is_none())
+                    {
+                        continue;
+                    }
+                    pins.insert(
+                        key,
+                        (
+                            net,
+                            NetNode {
+                                reference: reference.clone(),
+                                pin: number,
+                                function: name.filter(|n| n != "~" && !n.is_empty()),
+                                pin_type: pin.get(0).unwrap_or_default(),
+                            },
+                        ),
+                    );
+                }
+            }
+        }
+
+        let mut by_name: IndexMap<String, Vec<NetNode>> = IndexMap::new();
+        for (net, node) in pins.into_values() {
+            let name = net.unwrap_or_else(|| unconnected(&node.reference, &node.pin));
+            by_name.entry(name).or_default().push(node);
+        }
+        by_name.sort_by(|a, _, b, _| natural_cmp(a, b));
+        let nets = by_name
+            .into_iter()
+            .enumerate()
+            .map(|(code, (name, mut nodes))| {
+                nodes.sort_by(|a, b| {
+                    natural_cmp(&a.reference, &b.reference).then(natural_cmp(&a.pin, &b.pin))
+                });
+                Net {
+                    code: code + 1,
+                    name,
+                    nodes,
+                }
+            })
+            .collect();
+        let mut components: Vec<Component> = components.into_values().collect();
+        components.sort_by(|a, b| natural_cmp(&a.reference, &b.reference));
+        Ok(Connectivity {
+            source: self.sheets[0].file.display().to_string(),
+            components,
+            nets,
+        })
+    }
+}
+
+impl Connectivity {
+    ///Write the KiCad netlist.
+    pub fn to_kicad(&self) -> Result<String, Error> {
+        let mut builder = Builder::new();
+        builder.push("export");
+        builder.push("version");
+        builder.text("E");
+        builder.end();
+        builder.push("design");
+        builder.push("source");
+        builder.text(&self.source);
+        builder.end();
+        builder.push("tool");
+        builder.text("elektron");
+        builder.end();
+        builder.end();
+        builder.push("components");
+        for component in &self.components {
+            let (lib, part) = component
+                .lib_id
+                .split_once(':')
+                .unwrap_or(("", &component.lib_id));
+            builder.push("comp");
+            for (name, value) in [
+                ("ref", &component.reference),
+                ("value", &component.value),
+                ("footprint", &component.footprint),
+            ] {
+                builder.push(name);
+                builder.text(value);
+                builder.end();
+            }
+            builder.push("libsource");
+            builder.push("lib");
+            builder.text(lib);
+            builder.end();
+            builder.push("part");
+            builder.text(part);
+            builder.end();
+            builder.end();
+            builder.push("sheetpath");
+            builder.push("names");
+            builder.text(&component.sheet);
+            builder.end();
+            builder.push("tstamps");
+            builder.text(&component.tstamps);
+            builder.end();
+            builder.end();
+            builder.push("tstamps");
+            builder.text(&component.uuid);
+            builder.end();
+            builder.end();
+        }
+        builder.end();
+        builder.push("nets");
+        for net in &self.nets {
+            builder.push("net");
+            builder.push("code");
+            builder.text(&net.code.to_string());
+            builder.end();
+            builder.push("name");
+            builder.text(&net.name);
+            builder.end();
+            for node in &net.nodes {
+                builder.push("node");
+                builder.push("ref");
+                builder.text(&node.reference);
+                builder.end();
+                builder.push("pin");
+                builder.text(&node.pin);
+                builder.end();
+                if let Some(function) = &node.function {
+                    builder.push("pinfunction");
+                    builder.text(function);
+                    builder.end();
+                }
+                builder.push("pintype");
+                builder.text(&node.pin_type);
+                builder.end();
+                builder.end();
+            }
+            builder.end();
+        }
+        builder.end();
+        builder.end();
+
+        let mut out: Vec<u8> = Vec::new();
+        builder.sexp()?.root()?.write(&mut out, 0)?;
+        Ok(String::from_utf8_lossy(&out).to_string())
+    }
+
+    ///Write the nets and the connected pins as JSON.
+    pub fn to_json(&self) -> String {
+        let mut components = json::JsonValue::new_array();
+        for component in &self.components {
+            components
+                .push(json::object! {
+                    reference: component.reference.clone(),
+                    value: component.value.clone(),
+                    footprint: component.footprint.clone(),
+                    lib_id: component.lib_id.clone(),
+                    sheet: component.sheet.clone(),
+                })
+                .unwrap();
+        }
+        let mut nets = json::JsonValue::new_array();
+        for net in &self.nets {
+            let mut nodes = json::JsonValue::new_array();
+            for node in &net.nodes {
+                nodes
+                    .push(json::object! {
+                        reference: node.reference.clone(),
+                        pin: node.pin.clone(),
+                        function: node.function.clone(),
+                        type: node.pin_type.clone(),
+                    })
+                    .unwrap();
+            }
+            nets.push(json::object! {
+                code: net.code,
+                name: net.name.clone(),
+                nodes: nodes,
+            })
+            .unwrap();
+        }
+        json::object! {
+            source: self.source.clone(),
+            components: components,
+            nets: nets,
+        }
+        .pretty(2)
+    }
+
+    ///Write the components and the nets as Graphviz DOT graph.
+    ///
+    ///The unconnected pins are not in the graph.
+    pub fn to_dot(&self) -> String {
+        let quote = |text: &str| format!("\"{}\"", text.replace('"', "\\\""));
+        let mut dot = vec![
+            String::from("graph netlist {"),
+            String::from("  rankdir=LR;"),
+            String::from("  node [shape=box];"),
+        ];
+        for component in &self.components {
+            dot.push(format!(
+                "  {} [label={}];",
+                quote(&component.reference),
+                quote(&format!("{}\\n{}", component.reference, component.value))
+            ));
+        }
+        for net in &self.nets {
+            if net.nodes.len() == 1 && net.name.starts_with("unconnected-") {
+                continue;
+            }
+            let id = quote(&format!("net:{}", net.name));
+            dot.push(format!(
+                "  {} [label={}, shape=ellipse];",
+                id,
+                quote(&net.name)
+            ));
+            for node in &net.nodes {
+                dot.push(format!(
+                    "  {} -- {} [label={}];",
+                    quote(&node.reference),
+                    id,
+                    quote(&node.pin)
+                ));
+            }
+        }
+        dot.push(String::from("}"));
+        dot.join("\n") + "\n"
+    }
+}
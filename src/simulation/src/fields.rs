@@ -0,0 +1,529 @@
+//! Read the simulation fields of the schematic symbols.
+//!
+//! KiCad 7 and later store the simulation model in the `Sim.*` fields. The
+//! older schematics use the `Spice_*` fields, these are used when no
+//! `Sim.Device` is set.
+
+use std::path::Path;
+
+use indexmap::IndexMap;
+use sexp::{Sexp, SexpProperty};
+
+use crate::{
+    circuit::Circuit,
+    error::Error,
+    source::{Source, Waveform},
+};
+
+///Parse the `key=value` pairs of the `Sim.Params` field, the values can be quoted.
+pub fn parse_params(params: &str) -> IndexMap<String, String> {
+    let mut result = IndexMap::new();
+    //the quotes are escaped in the schematic file.
+    let params = params.replace("\\\"", "\"");
+    let mut chars = params.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let key: String = chars
+            .by_ref()
+            .take_while(|c| *c != '=' && !c.is_whitespace())
+            .collect();
+        if key.is_empty() {
+            break;
+        }
+        let value: String = if chars.peek() == Some(&'"') {
+            chars.next();
+            let mut value = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some(c) = chars.next() {
+                            value.push(c)
+                        }
+                    }
+                    '"' => break,
+                    c => value.push(c),
+                }
+            }
+            value
+        } else {
+            chars.by_ref().take_while(|c| !c.is_whitespace()).collect()
+        };
+        result.insert(key.to_lowercase(), value);
+    }
+    result
+}
+
+///The pin order of the built in devices.
+fn pin_order(device: &str) -> &'static [&'static str] {
+    match device {
+        "D" => &["A", "K"],
+        "NPN" | "PNP" => &["C", "B", "E", "S"],
+        "NJFET" | "PJFET" | "NMES" | "PMES" => &["D", "G", "S"],
+        "NMOS" | "PMOS" => &["D", "G", "S", "B"],
+        "E" | "G" => &["+", "-", "C+", "C-"],
+        "SW" => &["no+", "no-", "ctrl+", "ctrl-"],
+        "TLINE" => &["1+", "1-", "2+", "2-"],
+        _ => &["+", "-"],
+    }
+}
+
+///The simulation fields of a symbol.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimFields {
+    ///The device, `Sim.Device` or `Spice_Primitive`.
+    pub device: Option<String>,
+    ///The model type, `Sim.Type`.
+    pub kind: Option<String>,
+    ///The symbol pin and the model pin, `Sim.Pins` or `Spice_Node_Sequence`.
+    pub pins: Vec<(String, String)>,
+    ///The parameters, `Sim.Params`.
+    pub params: IndexMap<String, String>,
+    ///The library file, `Sim.Library` or `Spice_Lib_File`.
+    pub library: Option<String>,
+    ///The model name, `Sim.Name` or `Spice_Model`.
+    pub name: Option<String>,
+    ///The symbol is netlisted, `Sim.Enable` or `Spice_Netlist_Enabled`.
+    pub enabled: bool,
+    ///The value of the symbol.
+    pub value: Option<String>,
+    ///The fields are the legacy `Spice_*` fields.
+    pub legacy: bool,
+}
+
+impl SimFields {
+    ///Read the simulation fields from the symbol.
+    pub fn from(symbol: &Sexp) -> Self {
+        let value: Option<String> = symbol.property("Value");
+        let device: Option<String> = symbol.property("Sim.Device");
+        if let Some(device) = device {
+            let enable: Option<String> = symbol.property("Sim.Enable");
+            let pins: Option<String> = symbol.property("Sim.Pins");
+            let params: Option<String> = symbol.property("Sim.Params");
+            Self {
+                device: Some(device.to_uppercase()),
+                kind: symbol.property("Sim.Type"),
+                pins: pins
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .filter_map(|p| p.split_once('='))
+                    .map(|(s, m)| (s.to_string(), m.to_string()))
+                    .collect(),
+                params: parse_params(&params.unwrap_or_default()),
+                library: symbol.property("Sim.Library"),
+                name: symbol.property("Sim.Name"),
+                enabled: !matches!(enable.as_deref(), Some("0") | Some("N") | Some("n")),
+                value,
+                legacy: false,
+            }
+        } else {
+            let enable: Option<String> = symbol.property("Spice_Netlist_Enabled");
+            let sequence: Option<String> = symbol.property("Spice_Node_Sequence");
+            Self {
+                device: symbol.property("Spice_Primitive"),
+                kind: None,
+                pins: sequence
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .enumerate()
+                    .map(|(i, s)| (s.to_string(), (i + 1).to_string()))
+                    .collect(),
+                params: IndexMap::new(),
+                library: symbol.property("Spice_Lib_File"),
+                name: symbol.property("Spice_Model"),
+                enabled: !matches!(enable.as_deref(), Some("N") | Some("n")),
+                value,
+                legacy: true,
+            }
+        }
+    }
+
+    ///Use the simulation fields of the library symbol when the symbol has none.
+    ///
+    ///The value and the enable flag of the symbol are kept.
+    pub fn or_library(self, lib: &Sexp) -> Self {
+        if self.device.is_some() {
+            return self;
+        }
+        let library = Self::from(lib);
+        if library.device.is_none() {
+            return self;
+        }
+        Self {
+            value: self.value,
+            enabled: self.enabled && library.enabled,
+            ..library
+        }
+    }
+
+    ///Resolve the relative library paths against the schematic directory.
+    ///
+    ///KiCad loads the libraries relative to the schematic and not to the
+    ///working directory.
+    pub fn relative_to(mut self, directory: &Path) -> Self {
+        let resolve = |library: &mut String| {
+            if !library.is_empty() && Path::new(library.as_str()).is_relative() {
+                *library = directory.join(library.as_str()).display().to_string();
+            }
+        };
+        if let Some(library) = self.library.as_mut() {
+            resolve(library);
+        }
+        if let Some(library) = self.params.get_mut("lib") {
+            resolve(library);
+        }
+        self
+    }
+
+    ///The value from the parameters or the symbol value.
+    fn param(&self, key: &str) -> Option<String> {
+        self.params
+            .get(key)
+            .cloned()
+            .or_else(|| self.value.clone())
+            .filter(|v| !v.is_empty())
+    }
+
+    ///Order the nodes by the model pins.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The reference of the symbol.
+    /// * `nodes`     - The symbol pin numbers and the connected nodes.
+    /// * `order`     - The model pin names in the netlist order.
+    pub fn nodes(
+        &self,
+        reference: &str,
+        nodes: &IndexMap<String, String>,
+        order: &[String],
+    ) -> Result<Vec<String>, Error> {
+        if self.pins.is_empty() {
+            return Ok(nodes.values().cloned().collect());
+        }
+        let mut result: Vec<(usize, String)> = Vec::new();
+        for (symbol_pin, model_pin) in &self.pins {
+            let index = match model_pin.parse::<usize>() {
+                Ok(index) => index,
+                Err(_) => {
+                    order
+                        .iter()
+                        .position(|o| o.eq_ignore_ascii_case(model_pin))
+                        .ok_or_else(|| {
+                            Error::InvalidElement(
+                                reference.to_string(),
+                                format!("unknown model pin '{}'", model_pin),
+                            )
+                        })?
+                        + 1
+                }
+            };
+            let Some(node) = nodes.get(symbol_pin) else {
+                return Err(Error::InvalidElement(
+                    reference.to_string(),
+                    format!("symbol pin '{}' not found", symbol_pin),
+                ));
+            };
+            result.push((index, node.to_string()));
+        }
+        result.sort_by_key(|(index, _)| *index);
+        Ok(result.into_iter().map(|(_, node)| node).collect())
+    }
+
+    ///Add the element to the circuit.
+    ///
+    ///Returns false when the fields do not define a spice element.
+    ///
+    /// # Arguments
+    ///
+    /// * `circuit`   - The circuit to add the element to.
+    /// * `reference` - The reference of the symbol.
+    /// * `nodes`     - The symbol pin numbers and the connected nodes in pin order.
+    pub fn netlist(
+        &self,
+        circuit: &mut Circuit,
+        reference: &str,
+        nodes: &IndexMap<String, String>,
+    ) -> Result<bool, Error> {
+        if let Some(library) = &self.library {
+            if !library.is_empty() {
+                circuit.library(library.to_string());
+            }
+        }
+        let Some(device) = &self.device else {
+            return Ok(false);
+        };
+        if self.legacy {
+            let nodes = self.nodes(reference, nodes, &[])?;
+            let value = self.name.clone().or(self.value.clone()).unwrap_or_default();
+            circuit.element(device, reference.to_string(), nodes, value)?;
+            return Ok(true);
+        }
+        let order: Vec<String> = pin_order(device).iter().map(|p| p.to_string()).collect();
+        let reference = reference.to_string();
+        match device.as_str() {
+            "SPICE" => {
+                //raw spice element, the KiCad conversion of the legacy fields.
+                let Some(primitive) = self.params.get("type") else {
+                    return Err(Error::InvalidElement(
+                        reference,
+                        String::from("Sim.Params has no type"),
+                    ));
+                };
+                if let Some(lib) = self.params.get("lib") {
+                    if !lib.is_empty() {
+                        circuit.library(lib.to_string());
+                    }
+                }
+                let value = self
+                    .params
+                    .get("model")
+                    .cloned()
+                    .or(self.value.clone())
+                    .unwrap_or_default();
+                let nodes = self.nodes(&reference, nodes, &order)?;
+                circuit.element(primitive, reference, nodes, value)?;
+            }
+            "R" | "C" | "L" => {
+                let key = device.to_lowercase();
+                let value = self.param(&key).ok_or_else(|| {
+                    Error::InvalidElement(reference.clone(), format!("parameter {} not set", key))
+                })?;
+                let nodes = self.nodes(&reference, nodes, &order)?;
+                circuit.element(device, reference, nodes, value)?;
+            }
+            "V" | "I" => {
+                let source = self.source(&reference)?;
+                let mut nodes = self.nodes(&reference, nodes, &order)?.into_iter();
+                let (Some(n1), Some(n2)) = (nodes.next(), nodes.next()) else {
+                    return Err(Error::InvalidElement(
+                        reference,
+                        String::from("2 nodes expected"),
+                    ));
+                };
+                if device == "V" {
+                    circuit.voltage_source(reference, n1, n2, source)?;
+                } else {
+                    circuit.current_source(reference, n1, n2, source)?;
+                }
+            }
+            "E" | "G" => {
+                let value = self.params.values().next().cloned().ok_or_else(|| {
+                    Error::InvalidElement(reference.clone(), String::from("gain not set"))
+                })?;
+                let nodes = self.nodes(&reference, nodes, &order)?;
+                circuit.element(device, reference, nodes, value)?;
+            }
+            "D" | "NPN" | "PNP" | "NJFET" | "PJFET" | "NMOS" | "PMOS" => {
+                let model = self.model(circuit, &reference, device)?;
+                let mut nodes = self.nodes(&reference, nodes, &order)?;
+                let primitive = match device.as_str() {
+                    "D" => "D",
+                    "NPN" | "PNP" => "Q",
+                    "NJFET" | "PJFET" => "J",
+                    _ => {
+                        //the bulk is connected to the source for three pin symbols.
+                        if nodes.len() == 3 {
+                            nodes.push(nodes[2].clone());
+                        }
+                        "M"
+                    }
+                };
+                circuit.element(primitive, reference, nodes, model)?;
+            }
+            "SUBCKT" => {
+                let Some(name) = &self.name else {
+                    return Err(Error::InvalidElement(
+                        reference,
+                        String::from("Sim.Name is not set"),
+                    ));
+                };
+                let order = if self.pins.iter().all(|(_, m)| m.parse::<usize>().is_ok()) {
+                    Vec::new()
+                } else {
+                    circuit.definition(name)?.pins
+                };
+                let nodes = self.nodes(&reference, nodes, &order)?;
+                circuit.circuit(reference, nodes, name.to_string())?;
+            }
+            "SW" => {
+                if !self
+                    .kind
+                    .as_deref()
+                    .unwrap_or("V")
+                    .eq_ignore_ascii_case("V")
+                {
+                    return Err(Error::InvalidElement(
+                        reference,
+                        String::from("only voltage controlled switches are supported"),
+                    ));
+                }
+                let model = format!("{}_sw", reference);
+                let params: Vec<String> = self
+                    .params
+                    .iter()
+                    .map(|(k, v)| match k.as_str() {
+                        "thr" => format!("vt={}", v),
+                        "his" => format!("vh={}", v),
+                        _ => format!("{}={}", k, v),
+                    })
+                    .collect();
+                circuit.model(model.clone(), format!("SW({})", params.join(" ")));
+                let nodes = self.nodes(&reference, nodes, &order)?;
+                circuit.element("S", reference, nodes, model)?;
+            }
+            "TLINE" => {
+                let params: Vec<String> = self
+                    .params
+                    .iter()
+                    .map(|(k, v)| format!("{}={}", k.to_uppercase(), v))
+                    .collect();
+                let nodes = self.nodes(&reference, nodes, &order)?;
+                circuit.element("T", reference, nodes, params.join(" "))?;
+            }
+            _ => {
+                return Err(Error::InvalidElement(
+                    reference,
+                    format!("unsupported simulation device {}", device),
+                ))
+            }
+        }
+        Ok(true)
+    }
+
+    ///The model of a semiconductor, from the library or defined by the parameters.
+    fn model(&self, circuit: &mut Circuit, reference: &str, device: &str) -> Result<String, Error> {
+        if let Some(name) = &self.name {
+            if !name.is_empty() {
+                return Ok(name.to_string());
+            }
+        }
+        let model = format!("{}_{}", reference, device.to_lowercase());
+        let mut params: Vec<String> = self
+            .params
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect();
+        if let Some(level) = self
+            .kind
+            .as_ref()
+            .and_then(|k| k.to_uppercase().strip_prefix("MOS").map(|l| l.to_string()))
+        {
+            params.insert(0, format!("level={}", level));
+        }
+        let kind = match device {
+            "NJFET" => "NJF",
+            "PJFET" => "PJF",
+            _ => device,
+        };
+        circuit.model(model.clone(), format!("{}({})", kind, params.join(" ")));
+        Ok(model)
+    }
+
+    ///Create the source from the parameters.
+    fn source(&self, reference: &str) -> Result<Source, Error> {
+        let get = |key: &str| self.params.get(key).cloned();
+        let kind = self
+            .kind
+            .clone()
+            .unwrap_or(String::from("DC"))
+            .to_uppercase();
+        let mut source = Source {
+            dc: get("dc"),
+            ac: get("ac").map(|ac| (ac, get("ph"))),
+            waveform: None,
+        };
+        let required = |key: &str| {
+            get(key).ok_or_else(|| {
+                Error::InvalidElement(reference.to_string(), format!("parameter {} not set", key))
+            })
+        };
+        match kind.as_str() {
+            "DC" => {
+                if source.dc.is_none() {
+                    source.dc = self.value.clone().filter(|v| crate::is_value(v));
+                }
+            }
+            "SIN" => {
+                source.waveform = Some(Waveform::Sin {
+                    offset: get("dc").unwrap_or(String::from("0")),
+                    amplitude: required("ampl")?,
+                    frequency: get("f"),
+                    delay: get("td"),
+                    damping: get("theta"),
+                    phase: get("phase"),
+                });
+                source.dc = None;
+            }
+            "PULSE" => {
+                source.waveform = Some(Waveform::Pulse {
+                    initial: required("y1")?,
+                    pulsed: required("y2")?,
+                    delay: get("td"),
+                    rise: get("tr"),
+                    fall: get("tf"),
+                    width: get("tw").or(get("pw")),
+                    period: get("per"),
+                });
+            }
+            "PWL" => {
+                let values: Vec<String> = required("pwl")?
+                    .split_whitespace()
+                    .map(|v| v.to_string())
+                    .collect();
+                if values.len() % 2 == 1 {
+                    return Err(Error::InvalidValue(reference.to_string(), values.join(" ")));
+                }
+                source.waveform = Some(Waveform::Pwl(
+                    values
+                        .chunks(2)
+                        .map(|c| (c[0].clone(), c[1].clone()))
+                        .collect(),
+                ));
+            }
+            _ => {
+                return Err(Error::InvalidElement(
+                    reference.to_string(),
+                    format!("unsupported source type {}", kind),
+                ))
+            }
+        }
+        if source.dc.is_none() && source.ac.is_none() && source.waveform.is_none() {
+            return Err(Error::InvalidElement(
+                reference.to_string(),
+                String::from("source value not set"),
+            ));
+        }
+        Ok(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{parse_params, SimFields};
+
+    #[test]
+    fn test_parse_params() {
+        let params = parse_params("type=\\\"X\\\" model=\"TL072c\" lib=\"\" R=10k");
+        assert_eq!("X", params["type"]);
+        assert_eq!("TL072c", params["model"]);
+        assert_eq!("", params["lib"]);
+        assert_eq!("10k", params["r"]);
+    }
+    #[test]
+    fn test_relative_to() {
+        let fields = SimFields {
+            library: Some(String::from("models/opamp.lib")),
+            params: parse_params("lib=/usr/share/spice/diode.lib"),
+            ..Default::default()
+        }
+        .relative_to(Path::new("project"));
+        assert_eq!(
+            Some(String::from("project/models/opamp.lib")),
+            fields.library
+        );
+        assert_eq!("/usr/share/spice/diode.lib", fields.params["lib"]);
+    }
+}
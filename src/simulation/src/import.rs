@@ -0,0 +1,411 @@
+//! Import a spice netlist to a circuit.
+//!
+//! The elements, `.subckt`, `.model`, `.param`, `.func`, `.include` and
+//! `.lib` statements and the `.control` block are loaded to a [`Circuit`], the
+//! values can be changed with [`Circuit::set_value`] and the circuit can
+//! be simulated again. Other dot commands like `.tran` or `.options` are
+//! kept as they are. The `.lib section` ... `.endl` blocks of a library file
+//! are skipped, the sections are loaded with `.lib file section`.
+//!
+//! # Examples
+//! ```
+//! use simulation::Circuit;
+//!
+//! let mut circuit = Circuit::parse(
+//!     "voltage divider\nV1 in 0 DC 5\nR1 in out 10k\nR2 out 0 10k\n.end",
+//!     vec![],
+//! )
+//! .unwrap();
+//! circuit.set_value("R2", "20k").unwrap();
+//! assert_eq!("R2 out 0 20k", circuit.to_str(false).unwrap()[3]);
+//! ```
+
+use std::{fs, path::Path};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{
+    circuit::{node_count, reference, Circuit},
+    error::Error,
+    library::{logical_lines, unquote},
+    xspice::port_type,
+};
+
+lazy_static! {
+    static ref RE_ASSIGN: Regex = Regex::new(r"\s*=\s*").unwrap();
+}
+
+///Dot commands which can be repeated, the arguments are collected.
+const REPEATABLE: [&str; 7] = [
+    "options", "option", "opt", "save", "ic", "nodeset", "global",
+];
+
+///Split the text at the whitespace outside of braces and brackets.
+fn split(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in text.chars() {
+        match c {
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' => depth = usize::saturating_sub(depth, 1),
+            _ => {}
+        }
+        if c.is_whitespace() && depth == 0 {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+///Split the `name=value` pairs, the values can contain spaces in braces.
+pub(crate) fn assignments(line: usize, text: &str) -> Result<Vec<(String, String)>, Error> {
+    split(&RE_ASSIGN.replace_all(text.trim(), "="))
+        .into_iter()
+        .map(|token| match token.split_once('=') {
+            Some((name, value)) if !name.is_empty() && !value.is_empty() => {
+                Ok((name.to_string(), value.to_string()))
+            }
+            _ => Err(Error::ParseError(
+                line,
+                format!("'{}' is not a parameter assignment", token),
+            )),
+        })
+        .collect()
+}
+
+///Remove the `params:` keyword of the subcircuit parameters.
+pub(crate) fn strip_params(text: &str) -> &str {
+    let text = text.trim();
+    match text.get(..7) {
+        Some(keyword) if keyword.eq_ignore_ascii_case("params:") => &text[7..],
+        _ => text,
+    }
+}
+
+///Resolve the include file relative to the netlist file.
+fn resolve(directory: Option<&Path>, file: &str) -> String {
+    let file = unquote(file);
+    match directory {
+        Some(directory) if Path::new(file).is_relative() => {
+            directory.join(file).display().to_string()
+        }
+        _ => file.to_string(),
+    }
+}
+
+///Add the lines to the circuit, the subcircuits are parsed recursively.
+fn parse_lines(
+    circuit: &mut Circuit,
+    lines: &[(usize, String)],
+    pathlist: &[String],
+    directory: Option<&Path>,
+) -> Result<(), Error> {
+    let mut index = 0;
+    while index < lines.len() {
+        let (number, line) = &lines[index];
+        let number = *number;
+        index += 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some(first) = tokens.first() else {
+            continue;
+        };
+        let Some(command) = first.strip_prefix('.') else {
+            element(circuit, number, line)?;
+            continue;
+        };
+        let command = command.to_lowercase();
+        let rest = tokens[1..].join(" ");
+        match command.as_str() {
+            "end" => break,
+            "title" => {}
+            "subckt" => {
+                if tokens.len() < 2 {
+                    return Err(Error::ParseError(
+                        number,
+                        String::from("subcircuit name expected"),
+                    ));
+                }
+                let definition = RE_ASSIGN.replace_all(&rest, "=");
+                let definition: Vec<&str> = definition.split_whitespace().skip(1).collect();
+                let pins: Vec<String> = definition
+                    .iter()
+                    .take_while(|t| !t.contains('=') && !t.eq_ignore_ascii_case("params:"))
+                    .map(|t| t.to_string())
+                    .collect();
+                let params = definition[pins.len()..].join(" ");
+                let params = assignments(number, strip_params(&params))?;
+                let start = index;
+                let mut depth = 1;
+                while index < lines.len() {
+                    let first = lines[index].1.split_whitespace().next().unwrap_or("");
+                    if first.eq_ignore_ascii_case(".subckt") {
+                        depth += 1;
+                    } else if first.eq_ignore_ascii_case(".ends") {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    index += 1;
+                }
+                if depth != 0 {
+                    return Err(Error::ParseError(
+                        number,
+                        format!("subcircuit {} is not closed with .ends", tokens[1]),
+                    ));
+                }
+                let mut subcircuit = Circuit::new(tokens[1].to_string(), pathlist.to_vec());
+                parse_lines(&mut subcircuit, &lines[start..index], pathlist, directory)?;
+                circuit.parametric_subcircuit(tokens[1].to_string(), pins, params, subcircuit)?;
+                index += 1;
+            }
+            "ends" => {
+                return Err(Error::ParseError(
+                    number,
+                    String::from(".ends without .subckt"),
+                ))
+            }
+            "model" if tokens.len() > 2 => {
+                circuit.model(tokens[1].to_string(), tokens[2..].join(" "));
+            }
+            "param" => {
+                for (name, value) in assignments(number, &rest)? {
+                    circuit.param(name, value)?;
+                }
+            }
+            "func" => {
+                let (Some(open), Some(close)) = (rest.find('('), rest.find(')')) else {
+                    return Err(Error::ParseError(
+                        number,
+                        String::from("function arguments expected"),
+                    ));
+                };
+                let args = rest[open + 1..close]
+                    .split(',')
+                    .map(|a| a.trim().to_string())
+                    .filter(|a| !a.is_empty())
+                    .collect();
+                let expression = rest[close + 1..].trim().trim_start_matches('=').trim();
+                circuit.func(
+                    rest[..open].trim().to_string(),
+                    args,
+                    expression.to_string(),
+                )?;
+            }
+            "include" | "inc" if tokens.len() == 2 => {
+                circuit.include(resolve(directory, tokens[1]), None);
+            }
+            "lib" if tokens.len() == 3 => {
+                circuit.include(resolve(directory, tokens[1]), Some(tokens[2].to_string()));
+            }
+            //the sections of a library file are loaded with `.lib file section`.
+            "lib" if tokens.len() == 2 => {
+                while index < lines.len()
+                    && !lines[index]
+                        .1
+                        .split_whitespace()
+                        .next()
+                        .is_some_and(|t| t.eq_ignore_ascii_case(".endl"))
+                {
+                    index += 1;
+                }
+                if index == lines.len() {
+                    return Err(Error::ParseError(
+                        number,
+                        format!(".lib {} is not closed with .endl", tokens[1]),
+                    ));
+                }
+                index += 1;
+            }
+            "control" => {
+                let start = index;
+                while index < lines.len() && !lines[index].1.eq_ignore_ascii_case(".endc") {
+                    index += 1;
+                }
+                if index == lines.len() {
+                    return Err(Error::ParseError(
+                        number,
+                        String::from(".control is not closed with .endc"),
+                    ));
+                }
+                for (_, control) in &lines[start..index] {
+                    circuit.control(control.clone());
+                }
+                index += 1;
+            }
+            "model" | "include" | "inc" | "lib" | "endc" | "endl" => {
+                return Err(Error::ParseError(number, format!("invalid .{}", command)))
+            }
+            //the measurements are named.
+            "meas" | "measure" if tokens.len() > 3 => {
+                circuit.option(
+                    format!("{} {} {}", command, tokens[1], tokens[2]),
+                    tokens[3..].join(" "),
+                );
+            }
+            _ if REPEATABLE.contains(&command.as_str()) => {
+                circuit
+                    .options
+                    .entry(command)
+                    .and_modify(|value| {
+                        value.push(' ');
+                        value.push_str(&rest);
+                    })
+                    .or_insert(rest);
+            }
+            _ => {
+                if circuit.options.contains_key(&command) {
+                    return Err(Error::ParseError(
+                        number,
+                        format!(".{} is defined more than once", command),
+                    ));
+                }
+                circuit.option(command, rest);
+            }
+        }
+    }
+    Ok(())
+}
+
+///Add an element line to the circuit.
+fn element(circuit: &mut Circuit, number: usize, line: &str) -> Result<(), Error> {
+    //the parameters of the subcircuits can be written as `r = 1k`.
+    let line = if line.starts_with(['X', 'x']) {
+        RE_ASSIGN.replace_all(line, "=")
+    } else {
+        line.into()
+    };
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let element = tokens[0];
+    let primitive: String = element
+        .chars()
+        .next()
+        .map(|c| c.to_uppercase().collect())
+        .unwrap_or_default();
+    let Some(expected) = node_count(&primitive) else {
+        return Err(Error::ParseError(
+            number,
+            format!("unknown circuit element {}", element),
+        ));
+    };
+    //the element type is added again to the reference in the netlist.
+    let reference = reference(primitive.chars().next().unwrap_or_default(), element);
+    if matches!(primitive.as_str(), "E" | "G" | "F" | "H") && tokens.len() > 3 {
+        let form = tokens[3].to_uppercase();
+        if ["POLY", "VALUE", "TABLE"]
+            .iter()
+            .any(|f| form.starts_with(f))
+        {
+            return circuit.polynomial(
+                &primitive,
+                reference,
+                tokens[1].to_string(),
+                tokens[2].to_string(),
+                tokens[3..].join(" "),
+            );
+        }
+    }
+    //the ports of the code models can be vectors in brackets.
+    if primitive == "A" {
+        //the port type is joined with the port, like `%vd (a b)`.
+        let mut ports: Vec<String> = Vec::new();
+        let mut modifier: Option<String> = None;
+        for token in split(&tokens[1..].join(" ")) {
+            if port_type(&token).0.is_some() && port_type(&token).1.is_empty() {
+                modifier = Some(token);
+                continue;
+            }
+            ports.push(match modifier.take() {
+                Some(modifier) => format!("{} {}", modifier, token),
+                None => token,
+            });
+        }
+        let Some(model) = ports.pop().filter(|_| !ports.is_empty()) else {
+            return Err(Error::ParseError(
+                number,
+                format!("ports and a model expected for {}", element),
+            ));
+        };
+        return circuit.xspice(reference, ports, model);
+    }
+    let nodes = match expected {
+        Some(expected) => expected,
+        //the nodes of a subcircuit are followed by the name and the parameters.
+        None => tokens
+            .iter()
+            .position(|t| t.contains('=') || t.eq_ignore_ascii_case("params:"))
+            .unwrap_or(tokens.len())
+            .saturating_sub(2),
+    };
+    if tokens.len() < nodes + 2 {
+        return Err(Error::ParseError(
+            number,
+            format!("{} nodes and a value expected for {}", nodes, element),
+        ));
+    }
+    circuit.element(
+        &primitive,
+        reference,
+        tokens[1..=nodes].iter().map(|t| t.to_string()).collect(),
+        tokens[nodes + 1..].join(" "),
+    )
+}
+
+impl Circuit {
+    ///Parse a spice netlist.
+    ///
+    ///The first line of a netlist is the title, unless it is a dot command,
+    ///this allows to load library files.
+    ///
+    /// # Arguments
+    ///
+    /// * `content`  - The netlist.
+    /// * `pathlist` - The library paths for the models which are not included in the netlist.
+    pub fn parse(content: &str, pathlist: Vec<String>) -> Result<Circuit, Error> {
+        Self::parse_netlist(content, pathlist, None)
+    }
+
+    ///Load a spice netlist file, the includes are relative to the file.
+    pub fn load(path: &Path, pathlist: Vec<String>) -> Result<Circuit, Error> {
+        let content = fs::read(path)?;
+        Self::parse_netlist(&String::from_utf8_lossy(&content), pathlist, path.parent())
+    }
+
+    fn parse_netlist(
+        content: &str,
+        pathlist: Vec<String>,
+        directory: Option<&Path>,
+    ) -> Result<Circuit, Error> {
+        let mut lines = logical_lines(content);
+        let mut title = String::new();
+        if let Some((number, line)) = content
+            .lines()
+            .enumerate()
+            .find(|(_, l)| !l.trim().is_empty())
+        {
+            let line = line.trim();
+            if !line.starts_with('.') {
+                title = line.trim_start_matches('*').trim().to_string();
+                lines.retain(|(n, _)| *n != number + 1);
+            }
+        }
+        if let Some((_, line)) = lines
+            .iter()
+            .find(|(_, l)| l.to_lowercase().starts_with(".title"))
+        {
+            title = line[6..].trim().to_string();
+        }
+        let mut circuit = Circuit::new(title, pathlist.clone());
+        parse_lines(&mut circuit, &lines, &pathlist, directory)?;
+        Ok(circuit)
+    }
+}
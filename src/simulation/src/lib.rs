@@ -1,12 +1,36 @@
 //! package to run ngspice simulatations.
+mod background;
+mod batch;
+mod bus;
 mod circuit;
+mod diagnostics;
 mod error;
+mod export;
+mod fields;
+mod import;
+mod library;
 mod netlist;
+mod project;
+mod rawfile;
+mod results;
 mod simulation;
+mod source;
+mod xspice;
 
 pub use {
-    self::simulation::Simulation,
+    self::simulation::{run_parallel, Backend, Simulation},
+    background::Progress,
+    bus::{expand_bus, Bus},
     circuit::Circuit,
+    diagnostics::{parse_diagnostics, Diagnostic},
     error::Error,
-    netlist::{Netlist, NodePositions, Point},
+    export::{Component, Connectivity, Net, NetNode},
+    fields::{parse_params, SimFields},
+    library::{Definition, Kind, Library},
+    netlist::{natural_cmp, Netlist, NodePositions, Point},
+    project::{Project, Sheet},
+    rawfile::{read_rawfile, write_rawfile, Plot},
+    results::{resample, write_csv, write_parquet, write_wav},
+    source::{is_value, parse_value, spice_value, Source, Waveform},
+    xspice::{CodeModel, EVENTS},
 };
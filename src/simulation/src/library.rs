@@ -0,0 +1,371 @@
+//! Index the spice model libraries.
+//!
+//! The files in the library paths are parsed for `.subckt` and `.model`
+//! definitions. Files referenced with `.include` or `.lib file section` are
+//! indexed recursively, definitions within `.lib section` ... `.endl` blocks
+//! are loaded with the `.lib` directive. A name that is defined in several
+//! sections is resolved with the selected sections. The parsed files are
+//! cached until they are modified.
+//!
+//! # Examples
+//! ```
+//! use simulation::Library;
+//!
+//! let library = Library::new(&[String::from("tests/spice/")]).unwrap();
+//! let definition = library.resolve("tl072").unwrap();
+//! assert_eq!(".include tests/spice/TL072.lib", definition.directive());
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+
+use crate::error::Error;
+
+lazy_static! {
+    static ref CACHE: Mutex<HashMap<PathBuf, (Option<SystemTime>, FileIndex)>> =
+        Mutex::new(HashMap::new());
+}
+
+///The kind of the definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Subckt,
+    Model,
+}
+
+///A subcircuit or model definition in a library file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    ///The name as written in the file.
+    pub name: String,
+    pub kind: Kind,
+    ///The file containing the definition.
+    pub path: PathBuf,
+    ///The `.lib` section of the definition.
+    pub section: Option<String>,
+    ///The line number of the definition.
+    pub line: usize,
+    ///The pin names of a subcircuit.
+    pub pins: Vec<String>,
+    ///The files the defining file includes.
+    pub includes: Vec<PathBuf>,
+    ///The includes of the defining file that are not found.
+    pub missing: Vec<String>,
+}
+
+impl Definition {
+    ///The directive to load the definition in a netlist.
+    pub fn directive(&self) -> String {
+        match &self.section {
+            Some(section) => format!(".lib {} {}", self.path.display(), section),
+            None => format!(".include {}", self.path.display()),
+        }
+    }
+}
+
+///The parsed content of a single file.
+#[derive(Debug, Clone, Default)]
+struct FileIndex {
+    definitions: Vec<Definition>,
+    includes: Vec<String>,
+    libs: Vec<String>,
+}
+
+///Remove the quotes around a filename.
+pub(crate) fn unquote(name: &str) -> &str {
+    name.trim_matches(|c| c == '"' || c == '\'')
+}
+
+///Join the continuation lines and remove the comments.
+///
+///Returns the logical lines with the line number where they start.
+pub(crate) fn logical_lines(content: &str) -> Vec<(usize, String)> {
+    let mut result: Vec<(usize, String)> = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('*') || line.is_empty() {
+            continue;
+        }
+        let line = line.split(';').next().unwrap_or("");
+        let line = line.split(" $ ").next().unwrap_or("").trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(continuation) = line.strip_prefix('+') {
+            if let Some(last) = result.last_mut() {
+                last.1.push(' ');
+                last.1.push_str(continuation.trim());
+            }
+        } else {
+            result.push((number + 1, line.to_string()));
+        }
+    }
+    result
+}
+
+///Parse the definitions and references of a library file.
+fn parse(content: &str) -> FileIndex {
+    let mut index = FileIndex::default();
+    let mut section: Option<String> = None;
+    let mut depth = 0;
+    for (number, line) in logical_lines(content) {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some(first) = tokens.first() else {
+            continue;
+        };
+        match first.to_lowercase().as_str() {
+            ".subckt" if tokens.len() > 1 => {
+                if depth == 0 {
+                    let pins = tokens[2..]
+                        .iter()
+                        .take_while(|t| !t.contains('=') && !t.eq_ignore_ascii_case("params:"))
+                        .map(|t| t.to_string())
+                        .collect();
+                    index.definitions.push(Definition {
+                        name: tokens[1].to_string(),
+                        kind: Kind::Subckt,
+                        path: PathBuf::new(),
+                        section: section.clone(),
+                        line: number,
+                        pins,
+                        includes: Vec::new(),
+                        missing: Vec::new(),
+                    });
+                }
+                depth += 1;
+            }
+            ".ends" => depth = usize::saturating_sub(depth, 1),
+            ".model" if tokens.len() > 1 && depth == 0 => {
+                index.definitions.push(Definition {
+                    name: tokens[1].to_string(),
+                    kind: Kind::Model,
+                    path: PathBuf::new(),
+                    section: section.clone(),
+                    line: number,
+                    pins: Vec::new(),
+                    includes: Vec::new(),
+                    missing: Vec::new(),
+                });
+            }
+            ".lib" if tokens.len() == 2 => section = Some(tokens[1].to_string()),
+            ".lib" if tokens.len() > 2 => index.libs.push(unquote(tokens[1]).to_string()),
+            ".endl" => section = None,
+            ".include" | ".inc" if tokens.len() > 1 => {
+                index.includes.push(unquote(tokens[1]).to_string())
+            }
+            _ => {}
+        }
+    }
+    index
+}
+
+///Load the file index from the cache or parse the file.
+fn load(path: &Path) -> Result<FileIndex, Error> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut cache = CACHE.lock().unwrap();
+    if let Some((time, index)) = cache.get(path) {
+        if modified.is_some() && *time == modified {
+            return Ok(index.clone());
+        }
+    }
+    let content = fs::read(path)?;
+    let index = parse(&String::from_utf8_lossy(&content));
+    cache.insert(path.to_path_buf(), (modified, index.clone()));
+    Ok(index)
+}
+
+///The index of all definitions in the library paths.
+#[derive(Debug, Clone, Default)]
+pub struct Library {
+    definitions: IndexMap<String, Vec<Definition>>,
+}
+
+impl Library {
+    ///Index the library paths, the paths can be directories or files.
+    pub fn new(pathlist: &[String]) -> Result<Self, Error> {
+        let mut library = Library::default();
+        let mut visited = HashSet::new();
+        for path in pathlist {
+            let path = Path::new(path);
+            if path.is_file() {
+                library.index(path, &mut visited)?;
+                continue;
+            }
+            let content = match fs::read_dir(path) {
+                Ok(content) => content,
+                Err(e) => {
+                    return Err(Error::DirectoryError(
+                        path.display().to_string(),
+                        e.to_string(),
+                    ))
+                }
+            };
+            let mut files: Vec<PathBuf> = content
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.is_file()
+                        && !p
+                            .file_name()
+                            .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+                })
+                .collect();
+            files.sort();
+            for file in files {
+                library.index(&file, &mut visited)?;
+            }
+        }
+        Ok(library)
+    }
+
+    ///Index a file and the files it references.
+    ///
+    ///The includes that are not found are stored in the definitions of the
+    ///file, they are reported when the definition is resolved.
+    fn index(&mut self, path: &Path, visited: &mut HashSet<PathBuf>) -> Result<(), Error> {
+        let canonical = path.canonicalize()?;
+        if !visited.insert(canonical) {
+            return Ok(());
+        }
+        let index = load(path)?;
+        let parent = path.parent().unwrap_or(Path::new(""));
+        let mut includes: Vec<PathBuf> = Vec::new();
+        let mut libs: Vec<PathBuf> = Vec::new();
+        let mut missing: Vec<String> = Vec::new();
+        for (names, files) in [(&index.includes, &mut includes), (&index.libs, &mut libs)] {
+            for name in names {
+                let file = parent.join(name);
+                if file.is_file() {
+                    files.push(file);
+                } else {
+                    missing.push(name.to_string());
+                }
+            }
+        }
+        for definition in index.definitions {
+            self.definitions
+                .entry(definition.name.to_lowercase())
+                .or_default()
+                .push(Definition {
+                    path: path.to_path_buf(),
+                    includes: includes.clone(),
+                    missing: missing.clone(),
+                    ..definition
+                });
+        }
+        for file in includes.iter().chain(libs.iter()) {
+            self.index(file, visited)?;
+        }
+        Ok(())
+    }
+
+    ///Find the definition by name, the names are not case sensitive.
+    ///
+    ///Returns an error when the name is not found, defined more than once
+    ///or the file of the definition includes a file that is not found.
+    pub fn resolve(&self, name: &str) -> Result<&Definition, Error> {
+        self.resolve_in(name, &[])
+    }
+
+    ///Find the definition by name in the selected `.lib` sections.
+    ///
+    ///The definitions outside of a section are always found, without
+    ///selected sections all sections are searched.
+    pub fn resolve_in(&self, name: &str, sections: &[String]) -> Result<&Definition, Error> {
+        let Some(definitions) = self.definitions.get(&name.to_lowercase()) else {
+            return Err(Error::SpiceModelNotFound(name.to_string()));
+        };
+        let definitions: Vec<&Definition> = definitions
+            .iter()
+            .filter(|d| match &d.section {
+                Some(section) if !sections.is_empty() => {
+                    sections.iter().any(|s| s.eq_ignore_ascii_case(section))
+                }
+                _ => true,
+            })
+            .collect();
+        match definitions.as_slice() {
+            [] => Err(Error::SpiceModelNotFound(name.to_string())),
+            [definition] => match definition.missing.first() {
+                Some(missing) => Err(Error::IncludeNotFound(
+                    definition.path.display().to_string(),
+                    missing.to_string(),
+                )),
+                None => Ok(definition),
+            },
+            definitions => Err(Error::DuplicateModel(
+                name.to_string(),
+                definitions
+                    .iter()
+                    .map(|d| match &d.section {
+                        Some(section) => {
+                            format!("{}:{} [{}]", d.path.display(), d.line, section)
+                        }
+                        None => format!("{}:{}", d.path.display(), d.line),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            )),
+        }
+    }
+
+    ///All the definitions in the index.
+    pub fn definitions(&self) -> impl Iterator<Item = &Definition> {
+        self.definitions.values().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{logical_lines, parse, Kind};
+
+    #[test]
+    fn test_logical_lines() {
+        let lines = logical_lines(
+            "* comment\n.model D1 D(IS=1n\n+ RS=1) ; inline\n\nR1 1 2 1k\n; only comment",
+        );
+        assert_eq!(
+            vec![
+                (2, String::from(".model D1 D(IS=1n RS=1)")),
+                (5, String::from("R1 1 2 1k"))
+            ],
+            lines
+        );
+    }
+    #[test]
+    fn test_parse() {
+        let index = parse(
+            ".LIB tt\n.Model N1 NMOS\n.ENDL\n.SubCkt AMP 1 2\n.model LOCAL D\n.ends\n.inc \"other.lib\"\n.lib corners.lib ff",
+        );
+        let definitions: Vec<(&str, Kind, Option<&str>, usize, usize)> = index
+            .definitions
+            .iter()
+            .map(|d| {
+                (
+                    d.name.as_str(),
+                    d.kind,
+                    d.section.as_deref(),
+                    d.line,
+                    d.pins.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("N1", Kind::Model, Some("tt"), 2, 0),
+                ("AMP", Kind::Subckt, None, 4, 2)
+            ],
+            definitions
+        );
+        assert_eq!(vec![String::from("other.lib")], index.includes);
+        assert_eq!(vec![String::from("corners.lib")], index.libs);
+    }
+}
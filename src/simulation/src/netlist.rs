@@ -1,14 +1,22 @@
 use std::{
     cell::RefCell,
+    cmp::Ordering,
     collections::HashMap,
     hash::{Hash, Hasher},
+    path::Path,
     rc::Rc,
 };
 
 use indexmap::IndexMap;
+use log::warn;
 use ndarray::{arr1, s, Array1};
 
-use crate::{circuit::Circuit, error::Error};
+use crate::{
+    bus::{expand_bus, Bus},
+    circuit::Circuit,
+    error::Error,
+    fields::SimFields,
+};
 use sexp::{
     el,
     math::{Shape, Transform},
@@ -29,20 +37,91 @@ fn pin_names(symbol: &Sexp) -> Result<HashMap<String, (Sexp, usize)>, Error> {
     Ok(pins)
 }
 
+///Test if the symbol is the unit.
+fn units_eq(symbol: &Sexp, unit: usize) -> bool {
+    let symbol_unit: usize = symbol.value(el::SYMBOL_UNIT).unwrap();
+    symbol_unit == unit
+}
+
+///Split the pin number to the numeric and the text parts.
+fn chunks(number: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut digits = None;
+    for (index, c) in number.char_indices() {
+        let digit = c.is_ascii_digit();
+        if digits.is_some_and(|d| d != digit) {
+            chunks.push(&number[start..index]);
+            start = index;
+        }
+        digits = Some(digit);
+    }
+    if start < number.len() {
+        chunks.push(&number[start..]);
+    }
+    chunks
+}
+
+///Compare the pin numbers in natural order.
+///
+///The numeric parts are compared by value, `A2` is sorted before `A10`
+///and `2` before `10`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    for (a, b) in chunks(a).iter().zip(chunks(b).iter()) {
+        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y).then_with(|| a.len().cmp(&b.len())),
+            _ => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    chunks(a).len().cmp(&chunks(b).len()).then_with(|| a.cmp(b))
+}
+
+///Infer the spice primitive of a symbol without simulation fields.
+///
+///The symbol and the library symbol have no `Sim.Device` or
+///`Spice_Primitive`. Like KiCad, the passive devices and the sources are
+///inferred from the reference prefix of the library symbol.
+fn infer_primitive(lib: &Sexp) -> Option<&'static str> {
+    let prefix: String = lib.property(el::PROPERTY_REFERENCE)?;
+    match prefix.trim_end_matches('?') {
+        "R" => Some("R"),
+        "C" => Some("C"),
+        "L" => Some("L"),
+        "V" => Some("V"),
+        "I" => Some("I"),
+        _ => None,
+    }
+}
+
 ///A point in the schema.
-#[derive(Clone, Copy, Debug)]
+///
+///The coordinates are rounded to 0.1µm when the point is created with
+///[`Point::new`], the points are compared exactly.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Point {
     pub x: f64,
     pub y: f64,
 }
+///The resolution of the point coordinates in steps per mm.
+///
+///The transformed pin positions have rounding errors, like
+///`78.74000000000001` for a junction at `78.74`.
+const RESOLUTION: f64 = 1e4;
+
+///Round the coordinate to the resolution, `-0.0` becomes `0.0`.
+fn snap(value: f64) -> f64 {
+    (value * RESOLUTION).round() / RESOLUTION + 0.0
+}
+
 impl Point {
     pub fn new(x: f64, y: f64) -> Point {
-        Point { x, y }
-    }
-}
-impl PartialEq for Point {
-    fn eq(&self, other: &Self) -> bool {
-        (self.x == other.x) && (self.y == other.y)
+        Point {
+            x: snap(x),
+            y: snap(y),
+        }
     }
 }
 impl Eq for Point {}
@@ -70,21 +149,66 @@ pub enum NodePositions<'a> {
     Wire(Point, Point),
     Label(Point, &'a Sexp),
     GlobalLabel(Point, &'a Sexp),
+    HierarchicalLabel(Point, &'a Sexp),
+    SheetPin(Point, &'a Sexp, &'a Sexp),
     NoConnect(Point),
     Junction(Point),
+    Bus(Point, Point),
+    BusEntry(Point, Point),
+}
+
+///The kind of the node name, sorted by the priority.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Driver {
+    Power,
+    GlobalLabel,
+    Label,
+    HierarchicalLabel,
+    NoConnect,
+}
+
+///The KiCad name of a node without label, the first pin names the node.
+pub(crate) fn auto_name<'s>(
+    pins: impl Iterator<Item = (&'s String, &'s String)>,
+) -> Option<String> {
+    pins.min_by(|a, b| natural_cmp(a.0, b.0).then(natural_cmp(a.1, b.1)))
+        .map(|(reference, pin)| format!("Net-({}-Pad{})", reference, pin))
+}
+
+///Replace the characters in the node name that ngspice can not parse.
+pub(crate) fn spice_node(name: &str) -> String {
+    name.replace(['(', ')', ' '], "_")
 }
 
 #[derive(Clone, Debug)]
 pub struct Node {
-    identifier: Option<String>,
-    points: Vec<Point>,
+    pub(crate) identifier: Option<String>,
+    pub(crate) points: Vec<Point>,
     // pins: Vec<Pin>,
+    ///The names of the global labels and power symbols on the node.
+    pub(crate) global: Vec<String>,
+    ///The hierarchical labels on the node.
+    pub(crate) hierarchical: Vec<String>,
+    ///The node has no label and the name is generated.
+    pub(crate) numbered: bool,
+    ///The labels and power symbols on the node.
+    pub(crate) labels: Vec<(Driver, String)>,
+    ///The symbol pins of the node, the symbol uuid, the reference and the pin number.
+    pub(crate) pins: Vec<(String, String, String)>,
 }
 
 /// create a new node with values.
 impl Node {
     pub fn from(identifier: Option<String>, points: Vec<Point>) -> Self {
-        Self { identifier, points }
+        Self {
+            identifier,
+            points,
+            global: Vec::new(),
+            hierarchical: Vec::new(),
+            numbered: false,
+            labels: Vec::new(),
+            pins: Vec::new(),
+        }
     }
 }
 
@@ -100,6 +224,8 @@ pub struct Netlist<'a> {
     symbols: IndexMap<String, Vec<&'a Sexp>>,
     pub nodes: Vec<Node>, //TODO only public for tests
     node_positions: Vec<(Point, NodePositions<'a>)>,
+    aliases: IndexMap<String, Vec<String>>,
+    buses: Vec<Bus>,
 }
 
 impl<'a> Netlist<'a> {
@@ -111,77 +237,215 @@ impl<'a> Netlist<'a> {
             symbols,
             nodes: Vec::new(),
             node_positions,
+            aliases: Self::aliases(schema.root().unwrap()),
+            buses: Vec::new(),
         };
 
         let used_vec = &mut Vec::new();
         let used = &Rc::new(RefCell::new(used_vec));
         let mut used_pins: Vec<&NodePositions> = Vec::new();
         for pos in &netlist.node_positions {
-            if let NodePositions::Pin(point, p, s) = &pos.1 {
-                if !used_pins.contains(&&pos.1) {
-                    used_pins.push(&pos.1);
-                    used.borrow_mut().clear();
-                    used.borrow_mut().push(&pos.1);
-
-                    if let Some(nodes) = Netlist::next_node(&pos.0, &netlist.node_positions, used) {
-                        let mut identifier: Option<String> = None;
-                        let mut points: Vec<Point> = vec![point.to_owned()];
-                        let mut pins: Vec<Sexp> = vec![p.to_owned().clone()];
-                        let lib_id: String = s.value(el::LIB_ID).unwrap();
-                        if lib_id.starts_with("power:") {
-                            identifier = s.property(el::PROPERTY_VALUE);
-                        }
-                        for node in &nodes {
-                            match node {
-                                NodePositions::Pin(point, p, s) => {
-                                    let lib_id: String = s.value(el::LIB_ID).unwrap();
-                                    if lib_id.starts_with("power:") {
-                                        identifier = s.property(el::PROPERTY_VALUE);
-                                    }
-                                    pins.push(p.to_owned().clone());
-                                    points.push(*point);
-                                    used_pins.push(node);
-                                }
-                                NodePositions::Junction(point) => {
-                                    points.push(*point);
-                                    used_pins.push(&pos.1);
-                                }
-                                NodePositions::Wire(_, p2) => {
-                                    points.push(*point);
-                                    points.push(*p2);
-                                    used_pins.push(node);
-                                }
-                                NodePositions::NoConnect(point) => {
-                                    points.push(*point);
-                                    used_pins.push(node);
-                                    identifier = Some(String::from("NC"));
-                                }
-                                NodePositions::Label(point, l) => {
-                                    identifier = Some(l.get(0).unwrap());
-                                    points.push(*point);
-                                    used_pins.push(node);
-                                }
-                                NodePositions::GlobalLabel(point, l) => {
-                                    identifier = Some(l.get(0).unwrap());
-                                    points.push(*point);
-                                    used_pins.push(node);
-                                }
+            let (point, p, s) = match &pos.1 {
+                NodePositions::Pin(point, p, s) => (point, p, Some(s)),
+                NodePositions::SheetPin(point, _, p) => {
+                    //bus pins are connected with the buses
+                    let name: String = p.get(0).unwrap();
+                    if netlist.bus_members(&name).is_some() {
+                        continue;
+                    }
+                    (point, p, None)
+                }
+                _ => continue,
+            };
+            if !used_pins.contains(&&pos.1) {
+                used_pins.push(&pos.1);
+                used.borrow_mut().clear();
+                used.borrow_mut().push(&pos.1);
+
+                if let Some(nodes) = Netlist::next_node(&pos.0, &netlist.node_positions, used) {
+                    let mut labels: Vec<(Driver, String)> = Vec::new();
+                    let mut hierarchical: Vec<String> = Vec::new();
+                    let mut points: Vec<Point> = vec![point.to_owned()];
+                    let mut pins: Vec<(String, String, String)> = Vec::new();
+                    if let Some(s) = s {
+                        Self::symbol_pin(p, s, &mut labels, &mut pins);
+                    }
+                    for node in &nodes {
+                        match node {
+                            NodePositions::Pin(point, p, s) => {
+                                Self::symbol_pin(p, s, &mut labels, &mut pins);
+                                points.push(*point);
+                                used_pins.push(node);
+                            }
+                            NodePositions::SheetPin(point, _, _) => {
+                                points.push(*point);
+                                used_pins.push(node);
+                            }
+                            NodePositions::Junction(point) => {
+                                points.push(*point);
+                                used_pins.push(&pos.1);
+                            }
+                            NodePositions::Wire(_, p2) => {
+                                points.push(*point);
+                                points.push(*p2);
+                                used_pins.push(node);
+                            }
+                            NodePositions::NoConnect(point) => {
+                                points.push(*point);
+                                used_pins.push(node);
+                                labels.push((Driver::NoConnect, String::from("NC")));
                             }
+                            NodePositions::Label(point, l) => {
+                                labels.push((Driver::Label, l.get(0).unwrap()));
+                                points.push(*point);
+                                used_pins.push(node);
+                            }
+                            NodePositions::GlobalLabel(point, l) => {
+                                labels.push((Driver::GlobalLabel, l.get(0).unwrap()));
+                                points.push(*point);
+                                used_pins.push(node);
+                            }
+                            NodePositions::HierarchicalLabel(point, l) => {
+                                labels.push((Driver::HierarchicalLabel, l.get(0).unwrap()));
+                                hierarchical.push(l.get(0).unwrap());
+                                points.push(*point);
+                                used_pins.push(node);
+                            }
+                            NodePositions::Bus(..) | NodePositions::BusEntry(..) => {}
                         }
-                        netlist.nodes.push(Node::from(identifier, points));
                     }
+                    labels.sort();
+                    labels.dedup();
+                    let mut node = Node::from(None, points);
+                    node.global = labels
+                        .iter()
+                        .filter(|(d, _)| matches!(d, Driver::Power | Driver::GlobalLabel))
+                        .map(|(_, name)| name.to_string())
+                        .collect();
+                    node.hierarchical = hierarchical;
+                    node.labels = labels;
+                    node.pins = pins;
+                    netlist.nodes.push(node);
                 }
             }
         }
 
+        netlist.name_nodes();
+        netlist.buses = netlist.connect_buses();
+        Ok(netlist)
+    }
+
+    ///Collect the pin of the symbol, the power symbols name the node.
+    fn symbol_pin(
+        pin: &Sexp,
+        symbol: &Sexp,
+        labels: &mut Vec<(Driver, String)>,
+        pins: &mut Vec<(String, String, String)>,
+    ) {
+        let lib_id: String = symbol.value(el::LIB_ID).unwrap();
+        if lib_id.starts_with("power:") {
+            //the power flags have an output pin and do not name the node.
+            let pin_type: Option<String> = pin.get(0);
+            if pin_type.as_deref() == Some("power_in") {
+                if let Some(value) = symbol.property(el::PROPERTY_VALUE) {
+                    labels.push((Driver::Power, value));
+                }
+            }
+        } else if let Some(reference) = symbol.property(el::PROPERTY_REFERENCE) {
+            let uuid: String = symbol
+                .query("uuid")
+                .next()
+                .and_then(|u| u.get(0))
+                .unwrap_or_default();
+            let number: String = pin
+                .query(el::PIN_NUMBER)
+                .next()
+                .and_then(|n| n.get(0))
+                .unwrap_or_default();
+            pins.push((uuid, reference, number));
+        }
+    }
+
+    ///Name the nodes.
+    ///
+    ///Nodes that share a label are named by the label with the highest
+    ///priority, the power symbols first, then the global, local and
+    ///hierarchical labels. Labels of the same kind are sorted by name.
+    ///Nodes without a label are named by the first pin like `Net-(R1-Pad2)`.
+    fn name_nodes(&mut self) {
+        let mut parents: Vec<usize> = (0..self.nodes.len()).collect();
+        fn find(parents: &mut [usize], index: usize) -> usize {
+            let mut root = index;
+            while parents[root] != root {
+                root = parents[root];
+            }
+            parents[index] = root;
+            root
+        }
+        let mut names: HashMap<String, usize> = HashMap::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            for (driver, name) in &node.labels {
+                if *driver == Driver::NoConnect {
+                    continue;
+                }
+                if let Some(other) = names.get(name) {
+                    let (a, b) = (find(&mut parents, index), find(&mut parents, *other));
+                    parents[a] = b;
+                } else {
+                    names.insert(name.to_string(), index);
+                }
+            }
+        }
+        let mut best: HashMap<usize, (Driver, String)> = HashMap::new();
+        for index in 0..self.nodes.len() {
+            let root = find(&mut parents, index);
+            if let Some(label) = self.nodes[index].labels.first() {
+                let entry = best.entry(root).or_insert(label.clone());
+                if *label < *entry {
+                    *entry = label.clone();
+                }
+            }
+        }
         let mut name = 1;
-        for n in &mut netlist.nodes {
-            if n.identifier.is_none() {
-                n.identifier = Some(name.to_string());
+        for index in 0..self.nodes.len() {
+            let root = find(&mut parents, index);
+            let node = &mut self.nodes[index];
+            if let Some((_, label)) = best.get(&root) {
+                node.identifier = Some(label.to_string());
+            } else if let Some(auto) = auto_name(node.pins.iter().map(|(_, r, n)| (r, n))) {
+                node.identifier = Some(auto);
+                node.numbered = true;
+            } else {
+                node.identifier = Some(name.to_string());
+                node.numbered = true;
                 name += 1;
             }
         }
-        Ok(netlist)
+    }
+
+    ///The nodes with more than one label.
+    ///
+    ///Returns the used name, all the names and the position of the node.
+    pub fn label_conflicts(&self) -> Vec<(String, Vec<String>, Point)> {
+        let mut conflicts = Vec::new();
+        for node in &self.nodes {
+            let mut names: Vec<String> = node
+                .labels
+                .iter()
+                .filter(|(d, _)| *d != Driver::NoConnect)
+                .map(|(_, name)| name.to_string())
+                .collect();
+            names.sort();
+            names.dedup();
+            if names.len() > 1 {
+                conflicts.push((
+                    node.identifier.clone().unwrap_or_default(),
+                    names,
+                    node.points[0],
+                ));
+            }
+        }
+        conflicts
     }
 
     //Get the symbols, with units collected.
@@ -254,11 +518,126 @@ impl<'a> Netlist<'a> {
                     at.clone().into(),
                     NodePositions::GlobalLabel(Point::new(at[0], at[1]), symbol),
                 ));
+            } else if symbol.name == el::SHEET_PIN {
+                let at: Array1<f64> = symbol.value(el::AT).unwrap();
+                positions.push((
+                    at.clone().into(),
+                    NodePositions::HierarchicalLabel(Point::new(at[0], at[1]), symbol),
+                ));
+            } else if symbol.name == el::BUS {
+                let pts = symbol.query(el::PTS).next().unwrap();
+                let xy = pts.query(el::XY).collect::<Vec<&Sexp>>();
+                let xy1: Array1<f64> = xy.first().unwrap().values();
+                let xy2: Array1<f64> = xy.get(1).unwrap().values();
+                positions.push((
+                    Point::new(xy1[0], xy1[1]),
+                    NodePositions::Bus(Point::new(xy1[0], xy1[1]), Point::new(xy2[0], xy2[1])),
+                ));
+            } else if symbol.name == el::BUS_ENTRY {
+                let at: Array1<f64> = symbol.value(el::AT).unwrap();
+                let size: Array1<f64> = symbol.value("size").unwrap();
+                positions.push((
+                    Point::new(at[0], at[1]),
+                    NodePositions::BusEntry(
+                        Point::new(at[0], at[1]),
+                        Point::new(
+                            ((at[0] + size[0]) * 100.0).round() / 100.0,
+                            ((at[1] + size[1]) * 100.0).round() / 100.0,
+                        ),
+                    ),
+                ));
+            } else if symbol.name == el::SHEET {
+                for pin in symbol.query(el::PIN) {
+                    let at: Array1<f64> = pin.value(el::AT).unwrap();
+                    positions.push((
+                        at.clone().into(),
+                        NodePositions::SheetPin(Point::new(at[0], at[1]), symbol, pin),
+                    ));
+                }
             }
         }
         Ok(positions)
     }
 
+    ///Get the bus aliases of the schema.
+    fn aliases(schema: &Sexp) -> IndexMap<String, Vec<String>> {
+        let mut aliases = IndexMap::new();
+        for alias in schema.query("bus_alias") {
+            let name: String = alias.get(0).unwrap();
+            let members: Vec<String> = alias
+                .query("members")
+                .next()
+                .map(|m| m.values())
+                .unwrap_or_default();
+            aliases.insert(name, members);
+        }
+        aliases
+    }
+
+    ///Collect the buses with the labels and sheet pins.
+    fn connect_buses(&self) -> Vec<Bus> {
+        let segments = self
+            .node_positions
+            .iter()
+            .filter_map(|(_, p)| match p {
+                NodePositions::Bus(a, b) => Some((*a, *b)),
+                _ => None,
+            })
+            .collect();
+        let mut buses = Bus::connect(segments);
+        for (point, position) in &self.node_positions {
+            let Some(bus) = buses.iter_mut().find(|b| b.contains(point)) else {
+                continue;
+            };
+            match position {
+                NodePositions::Label(_, l)
+                | NodePositions::GlobalLabel(_, l)
+                | NodePositions::HierarchicalLabel(_, l) => {
+                    let name: String = l.get(0).unwrap();
+                    if self.bus_members(&name).is_none() {
+                        continue;
+                    }
+                    match position {
+                        NodePositions::Label(..) => bus.labels.push(name),
+                        NodePositions::GlobalLabel(..) => bus.global.push(name),
+                        _ => bus.hierarchical.push(name),
+                    }
+                }
+                NodePositions::SheetPin(_, sheet, pin) => {
+                    let name: String = pin.get(0).unwrap();
+                    if self.bus_members(&name).is_none() {
+                        continue;
+                    }
+                    let uuid: String = sheet
+                        .query("uuid")
+                        .next()
+                        .and_then(|u| u.get(0))
+                        .unwrap_or_default();
+                    bus.sheet_pins.push((uuid, name));
+                }
+                _ => {}
+            }
+        }
+        buses
+    }
+
+    ///The buses of the schema.
+    pub fn buses(&self) -> &Vec<Bus> {
+        &self.buses
+    }
+
+    ///Expand the bus name with the aliases of the schema.
+    ///
+    ///Returns `None` when the name is not a bus.
+    pub fn bus_members(&self, name: &str) -> Option<Vec<String>> {
+        expand_bus(name, &self.aliases)
+    }
+
+    ///Get the bus at the point.
+    pub fn bus(&self, point: &Point) -> Option<&Bus> {
+        self.buses.iter().find(|b| b.contains(point))
+    }
+
     ///Get the node name for the Point.
     pub fn node_name(&self, point: &Point) -> Option<String> {
         for n in &self.nodes {
@@ -269,6 +648,19 @@ impl<'a> Netlist<'a> {
         None
     }
 
+    ///The node is connected to another sheet with a hierarchical label or a sheet pin.
+    pub fn is_external(&self, name: &str) -> bool {
+        self.nodes
+            .iter()
+            .filter(|n| n.identifier.as_deref() == Some(name))
+            .any(|n| {
+                !n.hierarchical.is_empty()
+                    || self.node_positions.iter().any(|(p, pos)| {
+                        matches!(pos, NodePositions::SheetPin(..)) && n.points.contains(p)
+                    })
+            })
+    }
+
     ///Get the connected endpoints to this elements.
     pub fn next_node(
         pos: &'a Point,
@@ -278,7 +670,7 @@ impl<'a> Netlist<'a> {
         for (p, e) in elements {
             if !used.borrow().contains(&e) {
                 match e {
-                    NodePositions::Label(_, _) => {
+                    NodePositions::Label(_, _) | NodePositions::HierarchicalLabel(_, _) => {
                         if p == pos {
                             used.borrow_mut().push(e);
                             let mut found_nodes: Vec<&'a NodePositions> = vec![e];
@@ -318,7 +710,7 @@ impl<'a> Netlist<'a> {
         for (p, e) in elements {
             if !used.borrow().contains(&e) {
                 match e {
-                    NodePositions::Pin(_point, _pin, _symbol) => {
+                    NodePositions::Pin(..) | NodePositions::SheetPin(..) => {
                         if p == pos {
                             return Some(vec![e]);
                         }
@@ -350,8 +742,28 @@ impl<'a> Netlist<'a> {
     }
 
     pub fn circuit(&self, circuit: &mut Circuit) -> Result<(), Error> {
+        self.netlist_symbols(circuit, &self.symbols, Path::new(""), &|node| {
+            node.to_string()
+        })
+    }
+
+    ///The nodes of the sheet.
+    pub(crate) fn nodes(&self) -> &Vec<Node> {
+        &self.nodes
+    }
+
+    ///Add the symbols to the circuit, the node names are mapped with the function.
+    ///
+    ///The relative library paths are resolved against the directory.
+    pub(crate) fn netlist_symbols(
+        &self,
+        circuit: &mut Circuit,
+        symbols: &IndexMap<String, Vec<&Sexp>>,
+        directory: &Path,
+        node_name: &dyn Fn(&str) -> String,
+    ) -> Result<(), Error> {
         //Create a spice entry for each referenca
-        for (reference, symbols) in &self.symbols {
+        for (reference, symbols) in symbols {
             let lib_id: String = symbols.first().unwrap().value(el::LIB_ID).unwrap();
             //but not for the power symbols
             if lib_id.starts_with("power:") {
@@ -359,18 +771,6 @@ impl<'a> Netlist<'a> {
             }
 
             let first_symbol = &symbols.first().unwrap();
-
-            //skip symbol when Netlist_Enabled is 'N'
-            let netlist_enabled: Option<String> = first_symbol.property("Spice_Netlist_Enabled"); //TODO differenet
-                                                                                                  //name in new
-                                                                                                  //KiCAD verison
-            if let Some(enabled) = netlist_enabled {
-                if enabled == "N" {
-                    continue;
-                }
-            }
-
-            //create the pin order
             let lib_symbols = self
                 .schema
                 .root()
@@ -385,99 +785,87 @@ impl<'a> Netlist<'a> {
                     name == lib_id
                 })
                 .unwrap();
+
+            //skip symbol when the simulation is disabled
+            let fields = SimFields::from(first_symbol)
+                .or_library(lib)
+                .relative_to(directory);
+            if !fields.enabled {
+                continue;
+            }
+
+            //create the pin order
             let my_pins = pin_names(lib).unwrap();
             let mut pin_sequence: Vec<String> = my_pins.keys().map(|s| s.to_string()).collect();
-            pin_sequence.sort_by_key(|x| x.parse::<i32>().unwrap()); //TODO could be string
-
-            //when Node_Sequence is defined, use it
-            let netlist_sequence: Option<String> = first_symbol.property("Spice_Node_Sequence"); //TODO
-            if let Some(sequence) = netlist_sequence {
-                pin_sequence.clear();
-                let splits: Vec<&str> = sequence.split(' ').collect();
-                for s in splits {
-                    pin_sequence.push(s.to_string());
+            pin_sequence.sort_by(|a, b| natural_cmp(a, b));
+
+            //the pins of units that are not placed are skipped and reported,
+            //the units with pins used in the pin mapping must be placed.
+            let units: Vec<usize> = symbols
+                .iter()
+                .map(|s| s.value(el::SYMBOL_UNIT).unwrap())
+                .collect();
+            let mut missing: Vec<(usize, bool)> = Vec::new();
+            for (number, (_, unit)) in &my_pins {
+                if *unit == 0 || units.contains(unit) {
+                    continue;
+                }
+                let mapped = fields.pins.iter().any(|(pin, _)| pin == number);
+                match missing.iter_mut().find(|(u, _)| u == unit) {
+                    Some(entry) => entry.1 |= mapped,
+                    None => missing.push((*unit, mapped)),
                 }
             }
+            missing.sort();
+            if let Some((unit, _)) = missing.iter().find(|(_, mapped)| *mapped) {
+                return Err(Error::UnitNotFound(reference.to_string(), *unit));
+            }
+            for (unit, _) in &missing {
+                warn!("{}", Error::UnitNotFound(reference.to_string(), *unit));
+            }
 
-            let mut nodes = Vec::new();
+            let mut nodes = IndexMap::new();
             for n in pin_sequence {
                 let pin = my_pins.get(&n).unwrap();
-                for symbol in symbols {
-                    let unit: usize = symbol.value(el::SYMBOL_UNIT).unwrap();
-                    if unit == pin.1 {
-                        let at = pin.0.query(el::AT).next().unwrap();
-                        let x: f64 = at.get(0).unwrap();
-                        let y: f64 = at.get(1).unwrap();
+                if pin.1 > 0 && !units.contains(&pin.1) {
+                    continue;
+                }
+                let at = pin.0.query(el::AT).next().unwrap();
+                let x: f64 = at.get(0).unwrap();
+                let y: f64 = at.get(1).unwrap();
+                //the pins of unit 0 are common to all units, take the first connected.
+                let node = symbols
+                    .iter()
+                    .filter(|symbol| pin.1 == 0 || units_eq(symbol, pin.1))
+                    .find_map(|symbol| {
                         let pts = Shape::transform(*symbol, &arr1(&[x, y]));
-                        let p0 = Point::new(pts[0], pts[1]);
-                        if let Some(nn) = self.node_name(&p0) {
-                            nodes.push(nn);
-                        } else {
-                            nodes.push(String::from("NF"));
-                        }
-                    }
+                        self.node_name(&Point::new(pts[0], pts[1]))
+                    });
+                if let Some(nn) = node {
+                    nodes.insert(n.clone(), spice_node(&node_name(&nn)));
+                } else {
+                    nodes.insert(n.clone(), String::from("NF"));
                 }
             }
 
             //write the spice netlist item
-            let spice_primitive: Option<String> = first_symbol.property("Spice_Primitive"); //TODO
-            let spice_model = first_symbol.property("Spice_Model");
-            let spice_value = first_symbol.property("Value");
-            if let Some(primitive) = spice_primitive {
-                if primitive == "X" {
-                    circuit.circuit(reference.to_string(), nodes, spice_model.unwrap())?;
-                } else if primitive == "Q" {
-                    circuit.bjt(
-                        reference.to_string(),
-                        nodes[0].clone(),
-                        nodes[1].clone(),
-                        nodes[2].clone(),
-                        spice_model.unwrap(),
-                    );
-                } else if primitive == "J" {
-                    circuit.jfet(
-                        reference.to_string(),
-                        nodes[0].clone(),
-                        nodes[1].clone(),
-                        nodes[2].clone(),
-                        spice_model.unwrap(),
-                    );
-                } else if primitive == "D" {
-                    circuit.diode(
-                        reference.to_string(),
-                        nodes[0].clone(),
-                        nodes[1].clone(),
-                        spice_model.unwrap(),
-                    );
-                } else {
-                    println!(
-                        "Other node with 'X' -> {}{} - - {}",
-                        primitive,
-                        reference,
-                        spice_value.unwrap()
-                    );
-                }
-            } else if reference.starts_with('R') {
-                circuit.resistor(
-                    reference.clone(),
-                    nodes[0].clone(),
-                    nodes[1].clone(),
-                    spice_value.unwrap(),
-                );
-            } else if reference.starts_with('C') {
-                circuit.capacitor(
-                    reference.clone(),
-                    nodes[0].clone(),
-                    nodes[1].clone(),
-                    spice_value.unwrap(),
-                );
+            if fields.netlist(circuit, reference, &nodes)? {
+                continue;
+            }
+            if let Some(primitive) = infer_primitive(lib) {
+                circuit.element(
+                    primitive,
+                    reference.to_string(),
+                    nodes.into_values().collect(),
+                    fields.value.unwrap_or_default(),
+                )?;
             // } else if std::env::var("ELEKTRON_DEBUG").is_ok() {
             } else {
                 println!(
                     "Unkknwon Reference: {} ({:?}) {}",
                     reference,
                     nodes,
-                    spice_value.unwrap()
+                    fields.value.unwrap_or_default()
                 );
             }
         }
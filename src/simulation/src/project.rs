@@ -0,0 +1,434 @@
+//! Netlist a hierarchical design.
+//!
+//! The root schematic and all the sheets are loaded recursively. A sheet that
+//! is used more than once gets an instance for every use. The nets of the
+//! sheets are connected by the hierarchical labels, the global labels and the
+//! power symbols. The members of the buses are connected by the bus labels
+//! and the bus sheet pins.
+//!
+//! The nets of the root sheet keep their names, local nets in the sheets are
+//! prefixed with the sheet path like `/filter1/OUT`. Nets without a label are
+//! named with the reference of the sheet instance like `Net-(R2-Pad1)`.
+//!
+//! # Examples
+//! ```
+//! use std::path::Path;
+//! use simulation::{Circuit, Project};
+//!
+//! let project = Project::load(Path::new("tests/hierarchy/root.kicad_sch")).unwrap();
+//! assert_eq!(3, project.sheets.len());
+//! assert_eq!("/filter2/", project.sheets[2].path);
+//!
+//! let mut circuit = Circuit::new(String::from("hierarchy"), vec![]);
+//! project.circuit(&mut circuit).unwrap();
+//! ```
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use indexmap::IndexMap;
+use ndarray::Array1;
+use sexp::{el, Sexp, SexpParser, SexpProperty, SexpTree, SexpValueQuery};
+
+use crate::{
+    circuit::Circuit,
+    error::Error,
+    netlist::{auto_name, Netlist, Point},
+};
+
+///A sheet instance of the design.
+#[derive(Debug, Clone)]
+pub struct Sheet {
+    ///The instance path, `/` for the root sheet.
+    pub path: String,
+    ///The schematic file.
+    pub file: PathBuf,
+    ///The content of the schematic file.
+    pub tree: SexpTree,
+    ///The index of the parent sheet.
+    pub parent: Option<usize>,
+    ///The uuids of the sheet symbols from the root to this sheet.
+    pub uuids: Vec<String>,
+    ///The sheet pins in the parent sheet, the name and the position.
+    pub pins: Vec<(String, Point)>,
+}
+
+///A hierarchical design.
+#[derive(Debug, Clone)]
+pub struct Project {
+    ///The sheet instances, the root sheet is the first.
+    pub sheets: Vec<Sheet>,
+}
+
+///Load a schematic file.
+fn load_tree(file: &Path) -> Result<SexpTree, Error> {
+    let doc = SexpParser::load(file.to_str().unwrap())
+        .map_err(|e| Error::SexpError(format!("{}: {}", file.display(), e)))?;
+    SexpTree::from(doc.iter()).map_err(|e| Error::SexpError(format!("{}: {}", file.display(), e)))
+}
+
+///Get a property of the sheet, the names changed with KiCad 7.
+fn sheet_property(sheet: &Sexp, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| sheet.property(name))
+}
+
+///The uuid of a node.
+fn uuid(node: &Sexp) -> Option<String> {
+    node.query("uuid").next().and_then(|u| u.get(0))
+}
+
+impl Project {
+    ///Load the root schematic and all the sheets.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut project = Project { sheets: Vec::new() };
+        let tree = load_tree(path)?;
+        project.sheets.push(Sheet {
+            path: String::from("/"),
+            file: path.to_path_buf(),
+            tree,
+            parent: None,
+            uuids: Vec::new(),
+            pins: Vec::new(),
+        });
+        project.load_sheets(0, &mut vec![path.canonicalize()?])?;
+        Ok(project)
+    }
+
+    ///Create a project from a single schematic, sheets are not loaded.
+    pub fn from(tree: SexpTree) -> Self {
+        Project {
+            sheets: vec![Sheet {
+                path: String::from("/"),
+                file: PathBuf::new(),
+                tree,
+                parent: None,
+                uuids: Vec::new(),
+                pins: Vec::new(),
+            }],
+        }
+    }
+
+    ///Load the sheets of the sheet recursively.
+    fn load_sheets(&mut self, index: usize, stack: &mut Vec<PathBuf>) -> Result<(), Error> {
+        let parent = &self.sheets[index];
+        let dir = parent.file.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut children = Vec::new();
+        for sheet in parent.tree.root()?.query(el::SHEET) {
+            let name = sheet_property(sheet, &["Sheetname", "Sheet name"]).ok_or_else(|| {
+                Error::PropertyNotFound(format!("Sheetname in {}", parent.file.display()))
+            })?;
+            let file = sheet_property(sheet, &["Sheetfile", "Sheet file"])
+                .ok_or_else(|| Error::PropertyNotFound(format!("Sheetfile of {}", name)))?;
+            let mut uuids = parent.uuids.clone();
+            uuids.push(uuid(sheet).unwrap_or_else(|| name.clone()));
+            let pins = sheet
+                .query(el::PIN)
+                .map(|pin| {
+                    let at: Array1<f64> = pin.value(el::AT).unwrap();
+                    (pin.get(0).unwrap(), Point::new(at[0], at[1]))
+                })
+                .collect();
+            children.push((
+                format!("{}{}/", parent.path, name),
+                dir.join(file),
+                uuids,
+                pins,
+            ));
+        }
+        for (path, file, uuids, pins) in children {
+            let canonical = file.canonicalize().map_err(|e| {
+                Error::IoError(format!("sheet {} ({}): {}", path, file.display(), e))
+            })?;
+            if stack.contains(&canonical) {
+                return Err(Error::IoError(format!(
+                    "recursive sheet {} ({})",
+                    path,
+                    file.display()
+                )));
+            }
+            let tree = load_tree(&file)?;
+            self.sheets.push(Sheet {
+                path,
+                file,
+                tree,
+                parent: Some(index),
+                uuids,
+                pins,
+            });
+            stack.push(canonical);
+            self.load_sheets(self.sheets.len() - 1, stack)?;
+            stack.pop();
+        }
+        Ok(())
+    }
+
+    ///The reference of the symbol in the sheet instance.
+    ///
+    ///The reference is taken from the symbol instances, the reference property
+    ///is used when the instance is not found.
+    pub fn reference(&self, sheet: usize, symbol: &Sexp) -> Option<String> {
+        let root = self.sheets[0].tree.root().ok()?;
+        let uuids = &self.sheets[sheet].uuids;
+        let find = |instances: &Sexp, path: &str| -> Option<String> {
+            instances
+                .query("path")
+                .find(|p| SexpValueQuery::<String>::get(*p, 0).as_deref() == Some(path))
+                .and_then(|p| p.query("reference").next())
+                .and_then(|r| r.get(0))
+        };
+        //KiCad 7 stores the instances in the symbol
+        if let Some(root_uuid) = uuid(root) {
+            let path = std::iter::once(root_uuid)
+                .chain(uuids.iter().cloned())
+                .collect::<Vec<String>>()
+                .join("/");
+            for instances in symbol.query("instances") {
+                for project in instances.query("project") {
+                    if let Some(reference) = find(project, &format!("/{}", path)) {
+                        return Some(reference);
+                    }
+                }
+            }
+        }
+        //KiCad 6 stores the instances in the root schematic
+        if let (Some(instances), Some(symbol_uuid)) =
+            (root.query("symbol_instances").next(), uuid(symbol))
+        {
+            let path = uuids
+                .iter()
+                .cloned()
+                .chain(std::iter::once(symbol_uuid))
+                .collect::<Vec<String>>()
+                .join("/");
+            if let Some(reference) = find(instances, &format!("/{}", path)) {
+                return Some(reference);
+            }
+        }
+        symbol.property(el::PROPERTY_REFERENCE)
+    }
+
+    ///The references of the symbols in the sheet instance by the symbol uuid.
+    fn references(&self, index: usize) -> HashMap<String, String> {
+        let mut references = HashMap::new();
+        if let Ok(root) = self.sheets[index].tree.root() {
+            for symbol in root.query(el::SYMBOL) {
+                if let (Some(uuid), Some(reference)) = (uuid(symbol), self.reference(index, symbol))
+                {
+                    references.insert(uuid, reference);
+                }
+            }
+        }
+        references
+    }
+
+    ///Create the netlists of all sheets.
+    pub fn netlists(&self) -> Result<Vec<Netlist<'_>>, Error> {
+        self.sheets.iter().map(|s| Netlist::from(&s.tree)).collect()
+    }
+
+    ///The KiCad net names of the nodes in the netlists of the sheets.
+    ///
+    ///Returns the project-wide net names by sheet index and node identifier.
+    pub fn net_names(&self, netlists: &[Netlist]) -> HashMap<(usize, String), String> {
+        self.connect(netlists, "/")
+    }
+
+    ///Add the net of the sheet to the union find.
+    fn net(&self, nets: &mut Nets, index: usize, identifier: &str, numbered: bool) -> usize {
+        let sheet = &self.sheets[index];
+        let depth = sheet.path.matches('/').count();
+        let name = if sheet.parent.is_none() {
+            format!("{}{}", nets.prefix, identifier)
+        } else {
+            format!("{}{}", sheet.path, identifier)
+        };
+        let rank = if numbered { 2 } else { 1 };
+        nets.add((index, identifier.to_string()), (rank, depth, name))
+    }
+
+    ///Add the global net to the union find.
+    fn global(nets: &mut Nets, name: &str) -> usize {
+        nets.add((usize::MAX, name.to_string()), (0, 0, name.to_string()))
+    }
+
+    ///The global net names for the nodes of the sheets.
+    ///
+    ///The local nets of the root sheet are prefixed with `prefix`, KiCad
+    ///names them like `/MID`. Returns the net names by sheet index and node
+    ///identifier.
+    pub(crate) fn connect(
+        &self,
+        netlists: &[Netlist],
+        prefix: &str,
+    ) -> HashMap<(usize, String), String> {
+        let mut nets = Nets {
+            prefix: prefix.to_string(),
+            ..Default::default()
+        };
+        for (index, netlist) in netlists.iter().enumerate() {
+            let sheet = &self.sheets[index];
+            let references = self.references(index);
+            for node in netlist.nodes() {
+                let Some(identifier) = &node.identifier else {
+                    continue;
+                };
+                //the generated names use the references of the sheet instance.
+                let key = match auto_name(
+                    node.pins
+                        .iter()
+                        .filter_map(|(uuid, _, pin)| references.get(uuid).map(|r| (r, pin))),
+                ) {
+                    Some(name) if node.numbered => nets.add(
+                        (index, identifier.to_string()),
+                        (2, sheet.path.matches('/').count(), name),
+                    ),
+                    _ => self.net(&mut nets, index, identifier, node.numbered),
+                };
+                for global in &node.global {
+                    let global = Self::global(&mut nets, global);
+                    nets.union(key, global);
+                }
+                //connect the hierarchical labels with the sheet pins in the parent.
+                let Some(parent) = sheet.parent else {
+                    continue;
+                };
+                for label in &node.hierarchical {
+                    let Some((_, point)) = sheet.pins.iter().find(|(name, _)| name == label) else {
+                        continue;
+                    };
+                    let Some(parent_node) = netlists[parent].node_name(point) else {
+                        continue;
+                    };
+                    if let Some(parent_key) = nets.keys.get(&(parent, parent_node)) {
+                        nets.union(key, *parent_key);
+                    }
+                }
+            }
+        }
+        //connect the bus members
+        for (index, netlist) in netlists.iter().enumerate() {
+            let sheet = &self.sheets[index];
+            for bus in netlist.buses() {
+                //all the labels on the bus name the same members.
+                let Some(members) = bus.names().next().and_then(|n| netlist.bus_members(n)) else {
+                    continue;
+                };
+                for name in bus.names() {
+                    let Some(other) = netlist.bus_members(name) else {
+                        continue;
+                    };
+                    for (member, other) in members.iter().zip(other.iter()) {
+                        let a = self.net(&mut nets, index, member, false);
+                        let b = self.net(&mut nets, index, other, false);
+                        nets.union(a, b);
+                        if bus.global.contains(name) {
+                            let global = Self::global(&mut nets, other);
+                            nets.union(b, global);
+                        }
+                    }
+                }
+                //the hierarchical labels connect to the bus at the sheet pin.
+                let Some(parent) = sheet.parent else {
+                    continue;
+                };
+                for name in &bus.hierarchical {
+                    let Some((_, point)) = sheet.pins.iter().find(|(pin, _)| pin == name) else {
+                        continue;
+                    };
+                    let Some(members) = netlist.bus_members(name) else {
+                        continue;
+                    };
+                    let parent_members = netlists[parent]
+                        .bus(point)
+                        .and_then(|b| b.names().next())
+                        .and_then(|n| netlists[parent].bus_members(n))
+                        .or_else(|| netlists[parent].bus_members(name))
+                        .unwrap_or_default();
+                    for (member, parent_member) in members.iter().zip(parent_members.iter()) {
+                        let a = self.net(&mut nets, index, member, false);
+                        let b = self.net(&mut nets, parent, parent_member, false);
+                        nets.union(a, b);
+                    }
+                }
+            }
+        }
+        nets.resolve()
+    }
+
+    ///Add all the symbols of the design to the circuit.
+    pub fn circuit(&self, circuit: &mut Circuit) -> Result<(), Error> {
+        let netlists = self.netlists()?;
+        let nets = self.connect(&netlists, "");
+        for (index, netlist) in netlists.iter().enumerate() {
+            let mut symbols: IndexMap<String, Vec<&Sexp>> = IndexMap::new();
+            for symbol in self.sheets[index].tree.root()?.query(el::SYMBOL) {
+                let Some(reference) = self.reference(index, symbol) else {
+                    let lib_id: String = symbol.value(el::LIB_ID).unwrap();
+                    return Err(Error::PropertyNotFound(lib_id));
+                };
+                symbols.entry(reference).or_default().push(symbol);
+            }
+            let directory = self.sheets[index].file.parent().unwrap_or(Path::new(""));
+            netlist.netlist_symbols(circuit, &symbols, directory, &|node| {
+                nets.get(&(index, node.to_string()))
+                    .cloned()
+                    .unwrap_or(node.to_string())
+            })?;
+        }
+        Ok(())
+    }
+}
+
+///Union find of the net names.
+#[derive(Default)]
+struct Nets {
+    keys: HashMap<(usize, String), usize>,
+    parents: Vec<usize>,
+    ///The rank, the sheet depth and the name, the lowest is the net name.
+    names: Vec<(usize, usize, String)>,
+    ///The prefix of the local nets in the root sheet.
+    prefix: String,
+}
+
+impl Nets {
+    fn add(&mut self, key: (usize, String), name: (usize, usize, String)) -> usize {
+        if let Some(index) = self.keys.get(&key) {
+            return *index;
+        }
+        let index = self.parents.len();
+        self.parents.push(index);
+        self.names.push(name);
+        self.keys.insert(key, index);
+        index
+    }
+    fn find(&mut self, index: usize) -> usize {
+        let mut root = index;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        self.parents[index] = root;
+        root
+    }
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.names[a] <= self.names[b] {
+            self.parents[b] = a;
+        } else {
+            self.parents[a] = b;
+        }
+    }
+    fn resolve(mut self) -> HashMap<(usize, String), String> {
+        let keys: Vec<((usize, String), usize)> = self.keys.clone().into_iter().collect();
+        keys.into_iter()
+            .map(|(key, index)| {
+                let root = self.find(index);
+                (key, self.names[root].2.clone())
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,588 @@
+//! Read and write the ngspice rawfile format.
+//!
+//! A rawfile can contain multiple plots, each plot starts with a header
+//! followed by the values of all the vectors. The values are either written
+//! as text or as binary doubles.
+//!
+//! The simulation results can be cached in a rawfile and loaded later, the
+//! plots get the same names as the results of the simulation, like `tran1`.
+//! The name is stored in the `Option: plot = tran1` line of the header, the
+//! plots without the option are numbered like ngspice.
+//!
+//! # Examples
+//! ```
+//! use std::collections::HashMap;
+//! use simulation::{read_rawfile, write_rawfile};
+//!
+//! let mut vectors = HashMap::new();
+//! vectors.insert(String::from("time"), vec![0.0, 1e-3]);
+//! vectors.insert(String::from("out"), vec![0.0, 1.0]);
+//! let mut plots = HashMap::new();
+//! plots.insert(String::from("tran1"), vectors);
+//!
+//! let mut content: Vec<u8> = Vec::new();
+//! write_rawfile(&mut content, &plots, true).unwrap();
+//! assert_eq!(plots, read_rawfile(&content).unwrap());
+//! ```
+
+use std::{collections::HashMap, io::Write};
+
+use indexmap::IndexMap;
+
+use crate::{error::Error, netlist::natural_cmp};
+
+///The plot names and the short names used in the simulation results.
+const PLOT_NAMES: [(&str, &str); 10] = [
+    ("op", "Operating Point"),
+    ("tran", "Transient Analysis"),
+    ("ac", "AC Analysis"),
+    ("dc", "DC transfer characteristic"),
+    ("noise", "Noise Spectral Density Curves"),
+    ("pz", "Pole-Zero Analysis"),
+    ("tf", "Transfer Function"),
+    ("sens", "Sensitivity Analysis"),
+    ("disto", "Distortion Analysis"),
+    ("const", "Constants"),
+];
+
+///The vectors which are the scale of the plot.
+const SCALES: [&str; 6] = [
+    "time",
+    "frequency",
+    "v-sweep",
+    "i-sweep",
+    "temp-sweep",
+    "res-sweep",
+];
+
+///The prefix of the plots with the values of a digital node.
+const DIGITAL: &str = "Digital ";
+
+///The header option with the name of the plot in the results.
+const PLOT_OPTION: &str = "plot";
+
+///Get the plot name from the value of an `Option:` line.
+fn plot_option(value: &str) -> Option<String> {
+    let (name, value) = value.split_once('=')?;
+    (name.trim() == PLOT_OPTION).then(|| value.trim().to_string())
+}
+
+///Short plot name like ngspice names the plots internally.
+pub(crate) fn plot_prefix(plotname: &str) -> &'static str {
+    let plotname = plotname.to_lowercase();
+    if plotname.starts_with("operating point") {
+        "op"
+    } else if plotname.starts_with("transient") {
+        "tran"
+    } else if plotname.starts_with("ac ") {
+        "ac"
+    } else if plotname.starts_with("dc ") {
+        "dc"
+    } else if plotname.contains("noise") {
+        "noise"
+    } else if plotname.starts_with("pole-zero") {
+        "pz"
+    } else if plotname.starts_with("transfer function") {
+        "tf"
+    } else if plotname.starts_with("sensitivity") {
+        "sens"
+    } else if plotname.starts_with("distortion") {
+        "disto"
+    } else if plotname.starts_with("constant") {
+        "const"
+    } else {
+        "plot"
+    }
+}
+
+///Numbers the plots like ngspice, `tran1`, `tran2`.
+#[derive(Debug, Default)]
+pub(crate) struct PlotKeys {
+    counter: HashMap<&'static str, usize>,
+}
+
+impl PlotKeys {
+    ///The name of the next plot.
+    pub(crate) fn key(&mut self, plotname: &str) -> String {
+        let prefix = plot_prefix(plotname);
+        let index = self.counter.entry(prefix).or_insert(0);
+        *index += 1;
+        if prefix == "const" {
+            prefix.to_string()
+        } else {
+            format!("{}{}", prefix, index)
+        }
+    }
+}
+
+///A plot loaded from a rawfile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plot {
+    pub title: String,
+    pub date: String,
+    pub name: String,
+    pub flags: String,
+    ///The name of the plot in the results, like `tran1`.
+    pub key: Option<String>,
+    pub vectors: IndexMap<String, Vec<f64>>,
+}
+
+impl Plot {
+    ///Create the plot from the simulation results.
+    ///
+    ///The scale vector, like `time`, is the first vector.
+    pub fn new(key: &str, vectors: &HashMap<String, Vec<f64>>) -> Self {
+        let prefix = key.trim_end_matches(|c: char| c.is_ascii_digit());
+        let name = PLOT_NAMES
+            .iter()
+            .find(|(p, _)| *p == prefix)
+            .map(|(_, n)| n.to_string())
+            .unwrap_or(key.to_string());
+        let mut names: Vec<&String> = vectors.keys().collect();
+        names.sort_by(|a, b| {
+            let scale = |n: &str| !SCALES.contains(&n.to_lowercase().as_str());
+            scale(a).cmp(&scale(b)).then(natural_cmp(a, b))
+        });
+        Plot {
+            title: String::from("elektron"),
+            date: String::new(),
+            name,
+            flags: String::from("real"),
+            key: Some(key.to_string()),
+            vectors: names
+                .into_iter()
+                .map(|n| (n.clone(), vectors[n].clone()))
+                .collect(),
+        }
+    }
+
+    ///The number of points, all vectors have the same length.
+    fn points(&self) -> usize {
+        self.vectors.values().map(|v| v.len()).max().unwrap_or(0)
+    }
+
+    ///Write the header of the plot.
+    fn header(&self, out: &mut dyn Write) -> Result<(), Error> {
+        writeln!(out, "Title: {}", self.title)?;
+        writeln!(out, "Date: {}", self.date)?;
+        writeln!(out, "Plotname: {}", self.name)?;
+        writeln!(out, "Flags: {}", self.flags)?;
+        writeln!(out, "No. Variables: {}", self.vectors.len())?;
+        writeln!(out, "No. Points: {}", self.points())?;
+        if let Some(key) = &self.key {
+            writeln!(out, "Option: {} = {}", PLOT_OPTION, key)?;
+        }
+        writeln!(out, "Variables:")?;
+        for (i, name) in self.vectors.keys().enumerate() {
+            writeln!(out, "\t{}\t{}\t{}", i, name, vector_type(name))?;
+        }
+        Ok(())
+    }
+}
+
+///The type of the vector by the name.
+fn vector_type(name: &str) -> &'static str {
+    let name = name.to_lowercase();
+    if name == "time" {
+        "time"
+    } else if name == "frequency" {
+        "frequency"
+    } else if name.ends_with("#branch") || name.starts_with("i(") {
+        "current"
+    } else {
+        "voltage"
+    }
+}
+
+///Format the value like ngspice, `1.000000000000000e-03`.
+fn format_value(value: f64) -> String {
+    let formatted = format!("{:.15e}", value);
+    match formatted.split_once('e') {
+        Some((mantissa, exponent)) => {
+            let exponent: i32 = exponent.parse().unwrap_or(0);
+            format!(
+                "{}e{}{:02}",
+                mantissa,
+                if exponent < 0 { '-' } else { '+' },
+                exponent.abs()
+            )
+        }
+        None => formatted,
+    }
+}
+
+///Split the results to plots, the digital nodes are written to one plot per node.
+fn to_plots(plots: &HashMap<String, HashMap<String, Vec<f64>>>) -> Vec<Plot> {
+    let mut keys: Vec<&String> = plots.keys().collect();
+    keys.sort_by(|a, b| natural_cmp(a, b));
+    let mut result = Vec::new();
+    for key in keys {
+        let vectors = &plots[key];
+        if key != crate::xspice::EVENTS {
+            result.push(Plot::new(key, vectors));
+            continue;
+        }
+        let mut nodes: Vec<&String> = vectors.keys().filter(|n| !n.ends_with("#time")).collect();
+        nodes.sort_by(|a, b| natural_cmp(a, b));
+        for node in nodes {
+            let mut vectors = IndexMap::new();
+            vectors.insert(
+                String::from("time"),
+                plots[key]
+                    .get(&format!("{}#time", node))
+                    .cloned()
+                    .unwrap_or_default(),
+            );
+            vectors.insert(node.clone(), plots[key][node].clone());
+            result.push(Plot {
+                title: String::from("elektron"),
+                date: String::new(),
+                name: format!("{}{}", DIGITAL, node),
+                flags: String::from("real"),
+                key: None,
+                vectors,
+            });
+        }
+    }
+    result
+}
+
+///Write the simulation results as rawfile.
+///
+/// # Arguments
+///
+/// * `out`    - The output.
+/// * `plots`  - The simulation results by plot name.
+/// * `binary` - Write the values as binary doubles.
+pub fn write_rawfile(
+    out: &mut dyn Write,
+    plots: &HashMap<String, HashMap<String, Vec<f64>>>,
+    binary: bool,
+) -> Result<(), Error> {
+    for plot in to_plots(plots) {
+        plot.header(out)?;
+        let points = plot.points();
+        if binary {
+            writeln!(out, "Binary:")?;
+            for point in 0..points {
+                for values in plot.vectors.values() {
+                    let value = values.get(point).copied().unwrap_or(f64::NAN);
+                    out.write_all(&value.to_le_bytes())?;
+                }
+            }
+        } else {
+            writeln!(out, "Values:")?;
+            for point in 0..points {
+                for (i, values) in plot.vectors.values().enumerate() {
+                    let value = format_value(values.get(point).copied().unwrap_or(f64::NAN));
+                    if i == 0 {
+                        writeln!(out, " {}\t{}", point, value)?;
+                    } else {
+                        writeln!(out, "\t{}", value)?;
+                    }
+                }
+                writeln!(out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+///Read the plots from a rawfile, the values can be ASCII or binary.
+///
+///Returns the plots by name like the simulation results, the digital nodes
+///are collected to the `events` plot.
+pub fn read_rawfile(content: &[u8]) -> Result<HashMap<String, HashMap<String, Vec<f64>>>, Error> {
+    let plots = if content.windows(8).any(|w| w == b"Binary:\n") {
+        read_binary(content)?
+    } else {
+        read_ascii(&String::from_utf8_lossy(content))?
+    };
+    let mut keys = PlotKeys::default();
+    let mut result: HashMap<String, HashMap<String, Vec<f64>>> = HashMap::new();
+    for plot in plots {
+        if let Some(node) = plot.name.strip_prefix(DIGITAL) {
+            let events = result.entry(crate::xspice::EVENTS.to_string()).or_default();
+            let mut vectors = plot.vectors.into_iter();
+            if let (Some((_, time)), Some((_, levels))) = (vectors.next(), vectors.next()) {
+                events.insert(format!("{}#time", node), time);
+                events.insert(node.to_string(), levels);
+            }
+            continue;
+        }
+        let key = plot.key.unwrap_or_else(|| keys.key(&plot.name));
+        result.insert(key, plot.vectors.into_iter().collect());
+    }
+    Ok(result)
+}
+
+///Read the plots from a binary rawfile.
+pub fn read_binary(content: &[u8]) -> Result<Vec<Plot>, Error> {
+    let mut plots = Vec::new();
+    let mut position = 0;
+    while position < content.len() {
+        let mut plot = Plot {
+            title: String::new(),
+            date: String::new(),
+            name: String::new(),
+            flags: String::new(),
+            key: None,
+            vectors: IndexMap::new(),
+        };
+        let mut variables = 0;
+        let mut points = 0;
+        let mut names: Vec<String> = Vec::new();
+        let mut found = false;
+        let mut in_variables = false;
+        while position < content.len() {
+            let end = content[position..]
+                .iter()
+                .position(|c| *c == b'\n')
+                .map(|p| position + p)
+                .unwrap_or(content.len());
+            let line = String::from_utf8_lossy(&content[position..end]).to_string();
+            position = end + 1;
+            if in_variables && names.len() < variables {
+                let items: Vec<&str> = line.split_whitespace().collect();
+                if items.len() < 2 {
+                    return Err(Error::RawFile(
+                        line.to_string(),
+                        String::from("variable definition expected"),
+                    ));
+                }
+                names.push(vector_name(items[1]));
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let number = |value: &str| {
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|e| Error::RawFile(line.to_string(), e.to_string()))
+            };
+            match key.trim() {
+                "Title" => plot.title = value.trim().to_string(),
+                "Date" => plot.date = value.trim().to_string(),
+                "Plotname" => plot.name = value.trim().to_string(),
+                "Flags" => plot.flags = value.trim().to_string(),
+                "Option" => plot.key = plot_option(value).or(plot.key),
+                "No. Variables" => variables = number(value)?,
+                "No. Points" => points = number(value)?,
+                "Variables" => in_variables = true,
+                "Binary" => {
+                    found = true;
+                    break;
+                }
+                "Values" => {
+                    return Err(Error::RawFile(
+                        plot.name,
+                        String::from("ASCII values in a binary rawfile"),
+                    ));
+                }
+                _ => {}
+            }
+        }
+        if !found {
+            break;
+        }
+        let width = if plot.flags.contains("complex") {
+            16
+        } else {
+            8
+        };
+        let size = points * names.len() * width;
+        if position + size > content.len() {
+            return Err(Error::RawFile(
+                plot.name,
+                String::from("unexpected end of values"),
+            ));
+        }
+        let mut values: Vec<Vec<f64>> = vec![Vec::with_capacity(points); names.len()];
+        for (i, chunk) in content[position..position + size].chunks(width).enumerate() {
+            //complex values return the real part.
+            let value = f64::from_le_bytes(chunk[..8].try_into().unwrap());
+            values[i % names.len()].push(value);
+        }
+        position += size;
+        for (name, values) in names.into_iter().zip(values) {
+            plot.vectors.insert(name, values);
+        }
+        plots.push(plot);
+    }
+    Ok(plots)
+}
+
+///Strip the `V(...)` wrapper ngspice adds to numeric node names.
+fn vector_name(name: &str) -> String {
+    if let Some(inner) = name
+        .strip_prefix("V(")
+        .or_else(|| name.strip_prefix("v("))
+        .and_then(|n| n.strip_suffix(')'))
+    {
+        if inner.starts_with(|c: char| c.is_ascii_digit()) {
+            return inner.to_string();
+        }
+    }
+    name.to_string()
+}
+
+///Parse a value from the rawfile, complex values return the real part.
+fn parse_value(value: &str) -> Result<f64, Error> {
+    let real = value.split(',').next().unwrap_or(value);
+    real.trim()
+        .parse::<f64>()
+        .map_err(|e| Error::RawFile(value.to_string(), e.to_string()))
+}
+
+///Read the plots from an ASCII rawfile.
+pub fn read_ascii(content: &str) -> Result<Vec<Plot>, Error> {
+    let mut plots = Vec::new();
+    let mut lines = content.lines().peekable();
+    while lines.peek().is_some() {
+        let mut plot = Plot {
+            title: String::new(),
+            date: String::new(),
+            name: String::new(),
+            flags: String::new(),
+            key: None,
+            vectors: IndexMap::new(),
+        };
+        let mut variables = 0;
+        let mut points = 0;
+        let mut names: Vec<String> = Vec::new();
+        let mut found = false;
+        while let Some(line) = lines.next() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            match key.trim() {
+                "Title" => plot.title = value.trim().to_string(),
+                "Date" => plot.date = value.trim().to_string(),
+                "Plotname" => plot.name = value.trim().to_string(),
+                "Flags" => plot.flags = value.trim().to_string(),
+                "Option" => plot.key = plot_option(value).or(plot.key),
+                "No. Variables" => {
+                    variables = value
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|e| Error::RawFile(line.to_string(), e.to_string()))?
+                }
+                "No. Points" => {
+                    points = value
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|e| Error::RawFile(line.to_string(), e.to_string()))?
+                }
+                "Variables" => {
+                    while names.len() < variables {
+                        let Some(line) = lines.next() else {
+                            return Err(Error::RawFile(
+                                plot.name,
+                                String::from("unexpected end of variables"),
+                            ));
+                        };
+                        let items: Vec<&str> = line.split_whitespace().collect();
+                        if items.len() < 2 {
+                            return Err(Error::RawFile(
+                                line.to_string(),
+                                String::from("variable definition expected"),
+                            ));
+                        }
+                        names.push(vector_name(items[1]));
+                    }
+                }
+                "Values" => {
+                    found = true;
+                    break;
+                }
+                "Binary" => {
+                    return Err(Error::RawFile(
+                        plot.name,
+                        String::from("binary rawfiles are not supported"),
+                    ));
+                }
+                _ => {}
+            }
+        }
+        if !found {
+            break;
+        }
+        let mut values: Vec<Vec<f64>> = vec![Vec::with_capacity(points); names.len()];
+        let mut tokens: Vec<&str> = Vec::new();
+        while tokens.len() < points * (names.len() + 1) {
+            let Some(line) = lines.next() else {
+                return Err(Error::RawFile(
+                    plot.name,
+                    String::from("unexpected end of values"),
+                ));
+            };
+            tokens.extend(line.split_whitespace());
+        }
+        for point in tokens.chunks(names.len() + 1) {
+            for (i, value) in point.iter().skip(1).enumerate() {
+                values[i].push(parse_value(value)?);
+            }
+        }
+        for (name, values) in names.into_iter().zip(values) {
+            plot.vectors.insert(name, values);
+        }
+        plots.push(plot);
+    }
+    Ok(plots)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{format_value, plot_prefix, read_ascii, read_rawfile, write_rawfile};
+
+    #[test]
+    fn test_read_ascii() {
+        let content = "Title: test\nDate: Thu Jan  1 00:00:00  2024\nPlotname: Transient Analysis\nFlags: real\nNo. Variables: 3\nNo. Points: 2\nVariables:\n\t0\ttime\ttime\n\t1\tout\tvoltage\n\t2\tV(1)\tvoltage\nValues:\n 0\t0.000000e+00\n\t1.000000e+00\n\t2.000000e+00\n\n 1\t1.000000e-03\n\t3.000000e+00\n\t4.000000e+00\n\n";
+        let plots = read_ascii(content).unwrap();
+        assert_eq!(1, plots.len());
+        assert_eq!("Transient Analysis", plots[0].name);
+        assert_eq!(vec![0.0, 1e-3], plots[0].vectors["time"]);
+        assert_eq!(vec![1.0, 3.0], plots[0].vectors["out"]);
+        assert_eq!(vec![2.0, 4.0], plots[0].vectors["1"]);
+    }
+    #[test]
+    fn test_plot_prefix() {
+        assert_eq!("tran", plot_prefix("Transient Analysis"));
+        assert_eq!("ac", plot_prefix("AC Analysis"));
+        assert_eq!("op", plot_prefix("Operating Point"));
+    }
+    #[test]
+    fn test_format_value() {
+        assert_eq!("1.000000000000000e-03", format_value(1e-3));
+        assert_eq!("-2.500000000000000e+02", format_value(-250.0));
+    }
+    #[test]
+    fn test_write_ascii() {
+        let mut vectors = HashMap::new();
+        vectors.insert(String::from("out"), vec![1.0, 3.0]);
+        vectors.insert(String::from("time"), vec![0.0, 1e-3]);
+        vectors.insert(String::from("v1#branch"), vec![2.0, 4.0]);
+        let mut plots = HashMap::new();
+        plots.insert(String::from("tran1"), vectors.clone());
+        plots.insert(String::from("tran3"), vectors);
+        let mut events = HashMap::new();
+        events.insert(String::from("clk"), vec![0.0, 1.0, 0.0]);
+        events.insert(String::from("clk#time"), vec![0.0, 1e-3, 2e-3]);
+        plots.insert(String::from("events"), events);
+
+        let mut content: Vec<u8> = Vec::new();
+        write_rawfile(&mut content, &plots, false).unwrap();
+        let text = String::from_utf8(content.clone()).unwrap();
+        assert!(text.starts_with("Title: elektron\nDate: \nPlotname: Digital clk\n"));
+        assert!(text.contains(concat!(
+            "Plotname: Transient Analysis\nFlags: real\nNo. Variables: 3\nNo. Points: 2\n",
+            "Option: plot = tran1\nVariables:\n\t0\ttime\ttime\n\t1\tout\tvoltage\n\t2\tv1#branch\tcurrent\n",
+            "Values:\n 0\t0.000000000000000e+00\n\t1.000000000000000e+00\n"
+        )));
+        assert_eq!(plots, read_rawfile(&content).unwrap());
+    }
+}
@@ -0,0 +1,256 @@
+//! Export the simulation results to CSV, Parquet and WAV files.
+//!
+//! The CSV and the Parquet file have one column per vector, the scale
+//! vector like `time` is the first column. Transient results can be written
+//! as WAV file, the
+//! simulation uses a variable time step, the values are resampled to the
+//! sample rate of the audio file.
+//!
+//! # Examples
+//! ```
+//! use std::collections::HashMap;
+//! use simulation::{resample, write_csv};
+//!
+//! let mut vectors = HashMap::new();
+//! vectors.insert(String::from("time"), vec![0.0, 1.0]);
+//! vectors.insert(String::from("out"), vec![0.0, 2.0]);
+//!
+//! let mut content: Vec<u8> = Vec::new();
+//! write_csv(&mut content, &vectors).unwrap();
+//! assert_eq!("time,out\n0,0\n1,2\n", String::from_utf8(content).unwrap());
+//! assert_eq!(vec![0.0, 1.0, 2.0], resample(&[0.0, 1.0], &[0.0, 2.0], 2));
+//! ```
+
+use std::{collections::HashMap, io::Write, path::Path, sync::Arc};
+
+use parquet::{
+    basic::{Repetition, Type as PhysicalType},
+    data_type::DoubleType,
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::types::Type,
+};
+
+use crate::{error::Error, natural_cmp};
+
+///The vectors which are written to the first column.
+const SCALES: [&str; 3] = ["time", "frequency", "v-sweep"];
+
+///Quote the CSV field when it contains a separator or a quote.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+///The vector names sorted by name, the scale vector is the first.
+fn columns(vectors: &HashMap<String, Vec<f64>>) -> Vec<&String> {
+    let mut names: Vec<&String> = vectors.keys().collect();
+    names.sort_by(|a, b| {
+        let scale = |n: &str| !SCALES.contains(&n.to_lowercase().as_str());
+        scale(a).cmp(&scale(b)).then(natural_cmp(a, b))
+    });
+    names
+}
+
+///Write the vectors of a plot as CSV.
+///
+///The vectors are sorted by name, the scale vector is the first column.
+///When the vectors have different lengths the missing values are empty.
+pub fn write_csv(out: &mut dyn Write, vectors: &HashMap<String, Vec<f64>>) -> Result<(), Error> {
+    let names = columns(vectors);
+    writeln!(
+        out,
+        "{}",
+        names
+            .iter()
+            .map(|n| quote(n))
+            .collect::<Vec<String>>()
+            .join(",")
+    )?;
+    let rows = vectors.values().map(|v| v.len()).max().unwrap_or(0);
+    for row in 0..rows {
+        writeln!(
+            out,
+            "{}",
+            names
+                .iter()
+                .map(|n| {
+                    vectors[*n]
+                        .get(row)
+                        .map(|v| v.to_string())
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<String>>()
+                .join(",")
+        )?;
+    }
+    Ok(())
+}
+
+///Write the vectors of a plot as Parquet file.
+///
+///The columns are ordered like the CSV file and contain doubles, when the
+///vectors have different lengths the missing values are null.
+pub fn write_parquet<W: Write + Send>(
+    out: W,
+    vectors: &HashMap<String, Vec<f64>>,
+) -> Result<(), Error> {
+    let names = columns(vectors);
+    let mut fields = Vec::new();
+    for name in &names {
+        fields.push(Arc::new(
+            Type::primitive_type_builder(name, PhysicalType::DOUBLE)
+                .with_repetition(Repetition::OPTIONAL)
+                .build()?,
+        ));
+    }
+    let schema = Type::group_type_builder("results")
+        .with_fields(fields)
+        .build()?;
+    let properties = WriterProperties::builder().build();
+    let mut writer = SerializedFileWriter::new(out, Arc::new(schema), Arc::new(properties))?;
+    let rows = vectors.values().map(|v| v.len()).max().unwrap_or(0);
+    let mut row_group = writer.next_row_group()?;
+    for name in names {
+        let values = &vectors[name];
+        let levels: Vec<i16> = (0..rows).map(|i| (i < values.len()) as i16).collect();
+        if let Some(mut column) = row_group.next_column()? {
+            column
+                .typed::<DoubleType>()
+                .write_batch(values, Some(&levels), None)?;
+            column.close()?;
+        }
+    }
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+///Resample the values to a fixed sample rate with linear interpolation.
+///
+/// # Arguments
+///
+/// * `time`        - The time vector of the transient analysis.
+/// * `values`      - The values at the time steps.
+/// * `sample_rate` - The samples per second.
+pub fn resample(time: &[f64], values: &[f64], sample_rate: u32) -> Vec<f64> {
+    let len = time.len().min(values.len());
+    if len == 0 {
+        return Vec::new();
+    }
+    let start = time[0];
+    let samples = ((time[len - 1] - start) * sample_rate as f64).floor() as usize + 1;
+    let mut result = Vec::with_capacity(samples);
+    let mut index = 0;
+    for sample in 0..samples {
+        let t = start + sample as f64 / sample_rate as f64;
+        while index + 2 < len && time[index + 1] <= t {
+            index += 1;
+        }
+        if index + 1 >= len || time[index + 1] == time[index] {
+            result.push(values[index]);
+        } else {
+            let fraction = ((t - time[index]) / (time[index + 1] - time[index])).clamp(0.0, 1.0);
+            result.push(values[index] + (values[index + 1] - values[index]) * fraction);
+        }
+    }
+    result
+}
+
+///Write a transient result as mono WAV file with 32 bit float samples.
+///
+///The values are not scaled, the audio samples should be within -1 and 1.
+pub fn write_wav(path: &Path, time: &[f64], values: &[f64], sample_rate: u32) -> Result<(), Error> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for sample in resample(time, values, sample_rate) {
+        writer.write_sample(sample as f32)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use std::fs::File;
+
+    use parquet::{
+        file::reader::{FileReader, SerializedFileReader},
+        record::Field,
+    };
+
+    use super::{resample, write_csv, write_parquet};
+
+    #[test]
+    fn test_write_csv() {
+        let mut vectors = HashMap::new();
+        vectors.insert(String::from("v(a,b)"), vec![1.5]);
+        vectors.insert(String::from("out"), vec![1.0, 2.0]);
+        vectors.insert(String::from("time"), vec![0.0, 1e-3]);
+        let mut content: Vec<u8> = Vec::new();
+        write_csv(&mut content, &vectors).unwrap();
+        assert_eq!(
+            "time,out,\"v(a,b)\"\n0,1,1.5\n0.001,2,\n",
+            String::from_utf8(content).unwrap()
+        );
+    }
+    #[test]
+    fn test_write_parquet() {
+        let mut vectors = HashMap::new();
+        vectors.insert(String::from("out"), vec![1.0, 2.0]);
+        vectors.insert(String::from("time"), vec![0.0, 1e-3]);
+        vectors.insert(String::from("v(a)"), vec![1.5]);
+        let path = std::env::temp_dir().join("simulation_test_write_parquet.parquet");
+        write_parquet(File::create(&path).unwrap(), &vectors).unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        let names: Vec<String> = reader
+            .metadata()
+            .file_metadata()
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect();
+        assert_eq!(vec!["time", "out", "v(a)"], names);
+        let rows: Vec<Vec<Field>> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                row.unwrap()
+                    .get_column_iter()
+                    .map(|(_, f)| f.clone())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                vec![Field::Double(0.0), Field::Double(1.0), Field::Double(1.5)],
+                vec![Field::Double(1e-3), Field::Double(2.0), Field::Null],
+            ],
+            rows
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+    #[test]
+    fn test_resample() {
+        let time = vec![0.0, 0.1, 0.4, 1.0];
+        let values = vec![0.0, 1.0, 1.0, -1.0];
+        assert_eq!(
+            vec![0.0, 0.5, 1.0, 1.0, 1.0],
+            resample(&time, &values, 20)[..5].to_vec()
+        );
+        assert_eq!(21, resample(&time, &values, 20).len());
+        assert_eq!(Some(&-1.0), resample(&time, &values, 20).last());
+        assert!(resample(&[], &[], 20).is_empty());
+    }
+}
@@ -7,13 +7,23 @@ use std::collections::HashMap;
 
 use ngspice::{Callbacks, ComplexSlice, NgSpice, NgSpiceError};
 
-use crate::{circuit::Circuit, error::Error};
+use crate::{
+    background::{Background, Progress, Session},
+    batch,
+    circuit::Circuit,
+    diagnostics::parse_diagnostics,
+    error::Error,
+    xspice::{logic_level, EVENTS},
+};
 
 macro_rules! handle_error {
     ($cmd:expr, $cb:expr) => {
         match $cmd {
             Ok(_) => {}
             Err(error) => match error {
+                _ if !parse_diagnostics(&$cb.strs).is_empty() => {
+                    return Err(Error::Diagnostics(parse_diagnostics(&$cb.strs)));
+                }
                 ngspice::NgSpiceError::Unknown(code) => {
                     return Err(NgSpiceError::Spice(code, $cb.strs.join("\n")).into());
                 }
@@ -71,6 +81,80 @@ impl Callbacks for Cb {
     }
 }
 
+///The simulation results by plot and vector name.
+pub(crate) type Plots = HashMap<String, HashMap<String, Vec<f64>>>;
+
+///The backend to run the simulations.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Backend {
+    ///Load the ngspice shared library in-process.
+    #[default]
+    Shared,
+    ///Run the ngspice executable in batch mode.
+    Batch(String),
+}
+
+impl Backend {
+    ///Batch backend with the executable from `ELEKTRON_NGSPICE` or `ngspice`.
+    pub fn batch() -> Self {
+        Backend::Batch(std::env::var("ELEKTRON_NGSPICE").unwrap_or(String::from("ngspice")))
+    }
+}
+
+///Collect the data of a vector, complex values return the real part.
+fn vector_data(data: ComplexSlice) -> Vec<f64> {
+    match data {
+        ComplexSlice::Real(list) => list.to_vec(),
+        ComplexSlice::Complex(list) => list
+            .iter()
+            .map(|f| {
+                if !f.cx_real.is_nan() {
+                    f.cx_real
+                } else if !f.cx_imag.is_nan() {
+                    f.cx_imag
+                } else {
+                    todo!("can not get value from complex: {:?}", f);
+                }
+            })
+            .collect(),
+    }
+}
+
+///Collect the values of the digital nodes, the levels and the `#time` vectors.
+fn collect_events<C: Callbacks>(ng: &NgSpice<C>) -> Result<HashMap<String, Vec<f64>>, Error> {
+    let mut events: HashMap<String, Vec<f64>> = HashMap::new();
+    for node in ng.all_evt_nodes()? {
+        let values = ng.evt_node_info(&node)?;
+        events.insert(
+            format!("{}#time", node),
+            values.iter().map(|v| v.step).collect(),
+        );
+        events.insert(node, values.iter().map(|v| logic_level(&v.value)).collect());
+    }
+    Ok(events)
+}
+
+///Collect the vectors of all plots.
+///
+///The digital nodes are collected in the plot `events`.
+pub(crate) fn collect_plots<C: Callbacks>(ng: &NgSpice<C>) -> Result<Plots, Error> {
+    let mut plot_result: Plots = HashMap::new();
+    let events = collect_events(ng)?;
+    if !events.is_empty() {
+        plot_result.insert(EVENTS.to_string(), events);
+    }
+    for plot in ng.all_plots()? {
+        let vecs = ng.all_vecs(&plot)?;
+        let mut vec_values: HashMap<String, Vec<f64>> = HashMap::new();
+        for v in vecs {
+            let vals = ng.vector_info(format!("{}.{}", plot, &v).as_str())?;
+            vec_values.insert(v, vector_data(vals.data));
+        }
+        plot_result.insert(plot, vec_values);
+    }
+    Ok(plot_result)
+}
+
 ///# The Simulation struct
 ///
 /// ## Examples
@@ -86,9 +170,21 @@ impl Callbacks for Cb {
 ///
 /// assert_eq!("kicad_sch", root.name);
 /// ```
+///
+/// Select the batch backend to run ngspice in a separate process:
+///
+/// ```
+/// use simulation::{Backend, Circuit, Simulation};
+///
+/// let circuit = Circuit::new(String::from("test"), Vec::new());
+/// let simulation = Simulation::new(circuit).backend(Backend::batch());
+/// assert_ne!(Backend::Shared, simulation.backend);
+/// ```
 pub struct Simulation {
     pub circuit: Circuit,
     pub buffer: Option<Vec<String>>,
+    pub backend: Backend,
+    background: Option<Background>,
 }
 
 impl Simulation {
@@ -97,16 +193,36 @@ impl Simulation {
         Self {
             circuit,
             buffer: None,
+            backend: Backend::Shared,
+            background: None,
         }
     }
 
+    ///Set the simulation backend.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
     ///Run the stored commands.
     ///
     ///the commands can be added with xxx.
+    ///
+    ///Fails with the shared backend while a background simulation is active.
     pub fn run(&self) -> Result<HashMap<String, HashMap<String, Vec<f64>>>, Error> {
         if log_enabled!(Level::Debug) {
             debug!("run commands:\n{}", self.circuit.controls.join("\n"));
         }
+        if let Backend::Batch(executable) = &self.backend {
+            let result = batch::run(
+                executable,
+                self.circuit.to_str(false)?,
+                &self.circuit.controls,
+                &self.circuit.digital_nodes(),
+            )?;
+            return Ok(result.plots.into_iter().collect());
+        }
+        let _session = Session::acquire()?;
         let mut cb = Cb::new();
         let ng = NgSpice::new(&mut cb)?;
 
@@ -114,74 +230,125 @@ impl Simulation {
         for c in &self.circuit.controls {
             handle_error!(ng.command(c), cb);
         }
-        let mut plot_result: HashMap<String, HashMap<String, Vec<f64>>> = HashMap::new();
-        for plot in ng.all_plots()? {
-            let vecs = ng.all_vecs(&plot)?;
-            let mut vec_values: HashMap<String, Vec<f64>> = HashMap::new();
-            for v in vecs {
-                let vals = ng.vector_info(format!("{}.{}", plot, &v).as_str())?;
-                let data1 = match vals.data {
-                    ComplexSlice::Real(list) => list.to_vec(),
-                    ComplexSlice::Complex(list) => list
-                        .iter()
-                        .map(|f| {
-                            if !f.cx_real.is_nan() {
-                                f.cx_real
-                            } else if !f.cx_imag.is_nan() {
-                                f.cx_imag
-                            } else {
-                                todo!("can not get value from complex: {:?}", f);
-                            }
-                        })
-                        .collect(),
-                };
-                vec_values.insert(v, data1);
-            }
-            plot_result.insert(plot, vec_values);
-        }
-        Ok(plot_result)
+        collect_plots(&ng)
     }
 
-    ///Operating Point Analysis
+    ///Run the stored commands in the ngspice background thread.
     ///
-    /// Compute the DC operating point of the circuit with inductors
-    /// shorted and capacitorsopened.
-    pub fn op(&mut self) -> Result<HashMap<String, Vec<f64>>, Error> {
+    ///The progress and the vectors received so far can be queried with
+    ///[`Simulation::progress`], the results are returned from [`Simulation::bg_wait`].
+    pub fn bg_run(&mut self) -> Result<(), Error> {
+        if let Backend::Batch(_) = self.backend {
+            return Err(Error::NgSpiceError(String::from(
+                "background simulations need the shared backend",
+            )));
+        }
+        if self.is_running() {
+            return Err(Error::NgSpiceError(String::from(
+                "background simulation is already running",
+            )));
+        }
         if log_enabled!(Level::Debug) {
-            debug!("run operating point:\n{}", self.circuit.controls.join("\n"));
+            debug!(
+                "run background commands:\n{}",
+                self.circuit.controls.join("\n")
+            );
+        }
+        let circuit = self.circuit.to_str(true)?;
+        //release the session of a finished background simulation
+        self.background = None;
+        self.background = Some(Background::start(
+            Session::acquire()?,
+            circuit,
+            self.circuit.controls.clone(),
+        ));
+        Ok(())
+    }
+
+    ///Stop the background simulation.
+    pub fn bg_halt(&self) {
+        if let Some(background) = &self.background {
+            background.halt();
         }
+    }
+
+    ///True while the background simulation is running.
+    pub fn is_running(&self) -> bool {
+        self.background.as_ref().is_some_and(|b| b.is_running())
+    }
+
+    ///The progress of the background simulation.
+    pub fn progress(&self) -> Option<Progress> {
+        self.background.as_ref().map(|b| b.progress())
+    }
+
+    ///Wait for the background simulation and return the results.
+    ///
+    ///The shared library is released for the other simulations.
+    pub fn bg_wait(&mut self) -> Result<Plots, Error> {
+        let Some(mut background) = self.background.take() else {
+            return Err(NgSpiceError::NoResults.into());
+        };
+        let (plots, buffer) = background.wait()?;
+        self.buffer = Some(buffer);
+        Ok(plots)
+    }
+
+    ///Run a single analysis command and return the vectors of the resulting plot.
+    fn analysis(&mut self, command: &str) -> Result<HashMap<String, Vec<f64>>, Error> {
+        if let Backend::Batch(executable) = &self.backend {
+            let mut result = batch::run(
+                executable,
+                self.circuit.to_str(false)?,
+                &[command.to_string()],
+                &self.circuit.digital_nodes(),
+            )?;
+            self.buffer = Some(result.buffer);
+            let events = result.plots.shift_remove(EVENTS);
+            return match result.plots.into_iter().last() {
+                Some((_, mut vectors)) => {
+                    vectors.extend(events.unwrap_or_default());
+                    Ok(vectors)
+                }
+                None => Err(NgSpiceError::NoResults.into()),
+            };
+        }
+        let _session = Session::acquire()?;
         let mut cb = Cb::new();
         let ngspice = NgSpice::new(&mut cb)?;
         let circ = self.circuit.to_str(true)?;
         handle_error!(ngspice.circuit(circ), cb);
-        handle_error!(ngspice.command("op"), cb);
+        handle_error!(ngspice.command(command), cb);
         let plot = ngspice.current_plot()?;
+        //the analysis failed when no plot was created.
+        if plot == "const" {
+            let diagnostics = parse_diagnostics(&ngspice.callbacks.strs);
+            if !diagnostics.is_empty() {
+                return Err(Error::Diagnostics(diagnostics));
+            }
+        }
         let res = ngspice.all_vecs(plot.as_str())?;
         let mut map: HashMap<String, Vec<f64>> = HashMap::new();
         for name in res {
             let re = ngspice.vector_info(name.as_str())?;
-            let name = re.name;
-            let data1 = match re.data {
-                ComplexSlice::Real(list) => list.to_vec(),
-                ComplexSlice::Complex(list) => list
-                    .iter()
-                    .map(|f| {
-                        if !f.cx_real.is_nan() {
-                            f.cx_real
-                        } else if !f.cx_imag.is_nan() {
-                            f.cx_imag
-                        } else {
-                            todo!("can not get value from complex: {:?}", f);
-                        }
-                    })
-                    .collect(),
-            };
-            map.insert(name, data1);
+            map.insert(re.name, vector_data(re.data));
         }
+        map.extend(collect_events(&ngspice)?);
         self.buffer = Some(cb.strs.clone());
         Ok(map)
     }
 
+    ///Operating Point Analysis
+    ///
+    /// Compute the DC operating point of the circuit with inductors
+    /// shorted and capacitorsopened.
+    pub fn op(&mut self) -> Result<HashMap<String, Vec<f64>>, Error> {
+        if log_enabled!(Level::Debug) {
+            debug!("run operating point:\n{}", self.circuit.controls.join("\n"));
+        }
+        self.analysis("op")
+    }
+
     ///Transient analysis.
     ///
     /// ## Arguments
@@ -202,39 +369,7 @@ impl Simulation {
                 step, stop, start
             );
         }
-        let mut cb = Cb::new();
-        let ngspice = NgSpice::new(&mut cb)?;
-        let circ = self.circuit.to_str(true)?;
-        handle_error!(ngspice.circuit(circ), cb);
-        handle_error!(
-            ngspice.command(format!("tran {} {} {}", step, stop, start).as_str()),
-            cb
-        );
-        let plot = ngspice.current_plot()?;
-        let res = ngspice.all_vecs(plot.as_str())?;
-        let mut map: HashMap<String, Vec<f64>> = HashMap::new();
-        for name in res {
-            let re = ngspice.vector_info(name.as_str())?;
-            let name = re.name;
-            let data1 = match re.data {
-                ComplexSlice::Real(list) => list.to_vec(),
-                ComplexSlice::Complex(list) => list
-                    .iter()
-                    .map(|f| {
-                        if !f.cx_real.is_nan() {
-                            f.cx_real
-                        } else if !f.cx_imag.is_nan() {
-                            f.cx_imag
-                        } else {
-                            todo!("can not get value from complex: {:?}", f);
-                        }
-                    })
-                    .collect(),
-            };
-            map.insert(name, data1);
-        }
-        self.buffer = Some(cb.strs.clone());
-        Ok(map)
+        self.analysis(format!("tran {} {} {}", step, stop, start).as_str())
     }
 
     ///Small-Signal AC Analysis
@@ -259,50 +394,44 @@ impl Simulation {
                 start_frequency, stop_frequency, number_of_points, variation
             );
         }
-        let mut cb = Cb::new();
-        let ngspice = NgSpice::new(&mut cb)?;
-        let circ = self.circuit.to_str(true)?;
-        handle_error!(ngspice.circuit(circ), cb);
-        handle_error!(
-            ngspice
-                //DEC ND FSTART FSTOP
-                .command(
-                    format!(
-                        "ac {} {} {} {}",
-                        variation, number_of_points, start_frequency, stop_frequency
-                    )
-                    .as_str(),
-                ),
-            cb
-        );
-        let plot = ngspice.current_plot()?;
-        let res = ngspice.all_vecs(plot.as_str())?;
-        let mut map: HashMap<String, Vec<f64>> = HashMap::new();
-        for name in res {
-            let re = ngspice.vector_info(name.as_str());
-            if let Ok(r) = re {
-                let name = r.name;
-                let data1 = match r.data {
-                    ComplexSlice::Real(list) => list.to_vec(),
-                    ComplexSlice::Complex(list) => list
-                        .iter()
-                        .map(|f| {
-                            if !f.cx_real.is_nan() {
-                                f.cx_real
-                            } else if !f.cx_imag.is_nan() {
-                                f.cx_imag
-                            } else {
-                                todo!("can not get value from complex: {:?}", f);
-                            }
-                        })
-                        .collect(),
-                };
-                map.insert(name, data1);
-            } else {
-                panic!("Can not run ac with schema.");
-            }
-        }
-        self.buffer = Some(cb.strs.clone());
-        Ok(map)
+        //DEC ND FSTART FSTOP
+        self.analysis(
+            format!(
+                "ac {} {} {} {}",
+                variation, number_of_points, start_frequency, stop_frequency
+            )
+            .as_str(),
+        )
     }
 }
+
+///Run the simulations in parallel.
+///
+///Only simulations with the batch backend run concurrently, the shared
+///library has a global state and the other simulations run one after the other.
+pub fn run_parallel(simulations: &[Simulation]) -> Vec<Result<Plots, Error>> {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = simulations
+            .iter()
+            .map(|simulation| {
+                if let Backend::Batch(_) = simulation.backend {
+                    Some(scope.spawn(|| simulation.run()))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        handles
+            .into_iter()
+            .zip(simulations)
+            .map(|(handle, simulation)| match handle {
+                Some(handle) => handle
+                    .join()
+                    .unwrap_or(Err(Error::NgSpiceProcess(String::from(
+                        "simulation thread panicked",
+                    )))),
+                None => simulation.run(),
+            })
+            .collect()
+    })
+}
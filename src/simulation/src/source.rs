@@ -0,0 +1,518 @@
+//! Independent sources and the validation of the element values.
+//!
+//! The source specification of a voltage or current source is parsed into
+//! the DC and AC values and the transient waveform. The waveforms are written
+//! in the ngspice syntax.
+//!
+//! # Examples
+//! ```
+//! use simulation::{Source, Waveform};
+//!
+//! let source = Source::parse("DC 0 AC 1 SIN(0 1 1k)").unwrap();
+//! assert_eq!(Some(String::from("0")), source.dc);
+//! assert!(matches!(source.waveform, Some(Waveform::Sin { .. })));
+//! assert_eq!("DC 0 AC 1 SIN(0 1 1k)", source.to_string());
+//! ```
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::error::Error;
+
+lazy_static! {
+    static ref RE_VALUE: regex::Regex =
+        Regex::new(r"^[+-]?(\d+\.?\d*|\.\d+)([eE][+-]?\d+)?[a-zA-Zµ]*$").unwrap();
+    static ref RE_BOM_VALUE: regex::Regex =
+        Regex::new(r"^([+-]?\d+)([pnuµmkKMGTRr])(\d+)([a-zA-Zµ]*)$").unwrap();
+}
+
+///Check if the value is a spice number or an expression.
+///
+///Numbers can have a scale factor and a unit like `4.7k` or `10uF`, the
+///scale factor can also replace the decimal point like `4k7` in the
+///schematic. Expressions are written in curly braces or single quotes.
+pub fn is_value(value: &str) -> bool {
+    RE_VALUE.is_match(value)
+        || RE_BOM_VALUE.is_match(value)
+        || (value.len() > 2 && value.starts_with('{') && value.ends_with('}'))
+        || (value.len() > 2 && value.starts_with('\'') && value.ends_with('\''))
+}
+
+///Convert a value with the scale factor as decimal point to the spice
+///notation, like `4k7` to `4.7k`, `2R2` to `2.2` or `1M5` to `1.5Meg`.
+///
+///Other values are returned unchanged.
+pub fn spice_value(value: &str) -> String {
+    let Some(cap) = RE_BOM_VALUE.captures(value) else {
+        return value.to_string();
+    };
+    let scale = match &cap[2] {
+        "R" | "r" => "",
+        "M" => "Meg",
+        "K" => "k",
+        "µ" => "u",
+        scale => scale,
+    };
+    format!("{}.{}{}{}", &cap[1], &cap[3], scale, &cap[4])
+}
+
+///Convert a spice number with scale factor to a float, like `4.7k` or `10uF`.
+pub fn parse_value(value: &str) -> Option<f64> {
+    let value = spice_value(value);
+    let value = value.as_str();
+    let cap = RE_VALUE.captures(value)?;
+    let number = &value[..cap.get(2).or(cap.get(1))?.end()];
+    let suffix = value[number.len()..].to_lowercase();
+    let scale = if suffix.starts_with("meg") {
+        1e6
+    } else if suffix.starts_with("mil") {
+        25.4e-6
+    } else {
+        match suffix.chars().next() {
+            Some('t') => 1e12,
+            Some('g') => 1e9,
+            Some('k') => 1e3,
+            Some('m') => 1e-3,
+            Some('u') | Some('µ') => 1e-6,
+            Some('n') => 1e-9,
+            Some('p') => 1e-12,
+            Some('f') => 1e-15,
+            _ => 1.0,
+        }
+    };
+    number.parse::<f64>().ok().map(|n| n * scale)
+}
+
+///Return an error when the value is not valid.
+pub(crate) fn check_value(reference: &str, value: &str) -> Result<(), Error> {
+    if is_value(value) {
+        Ok(())
+    } else {
+        Err(Error::InvalidValue(
+            reference.to_string(),
+            value.to_string(),
+        ))
+    }
+}
+
+///Check a value followed by optional `key=value` parameters, like `1k tc1=0.001`.
+pub(crate) fn check_parameters(reference: &str, value: &str) -> Result<(), Error> {
+    let mut tokens = value.split_whitespace();
+    match tokens.next() {
+        Some(first) => check_value(reference, first)?,
+        None => {
+            return Err(Error::InvalidValue(
+                reference.to_string(),
+                value.to_string(),
+            ))
+        }
+    }
+    for token in tokens {
+        match token.split_once('=') {
+            Some((key, value)) if !key.is_empty() => check_value(reference, value)?,
+            _ => {
+                return Err(Error::InvalidValue(
+                    reference.to_string(),
+                    token.to_string(),
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+///Convert the value of `value [key=value ...]` to the spice notation, see [`spice_value`].
+pub(crate) fn spice_parameters(value: &str) -> String {
+    let value = value.trim();
+    match value.split_once(char::is_whitespace) {
+        Some((first, params)) => format!("{} {}", spice_value(first), params.trim()),
+        None => spice_value(value),
+    }
+}
+
+///The transient waveform of a source.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Waveform {
+    ///Sinusoidal: `SIN(VO VA FREQ TD THETA PHASE)`.
+    Sin {
+        offset: String,
+        amplitude: String,
+        frequency: Option<String>,
+        delay: Option<String>,
+        damping: Option<String>,
+        phase: Option<String>,
+    },
+    ///Pulse: `PULSE(V1 V2 TD TR TF PW PER)`.
+    Pulse {
+        initial: String,
+        pulsed: String,
+        delay: Option<String>,
+        rise: Option<String>,
+        fall: Option<String>,
+        width: Option<String>,
+        period: Option<String>,
+    },
+    ///Piece wise linear: `PWL(T1 V1 T2 V2 ...)`.
+    Pwl(Vec<(String, String)>),
+    ///The other ngspice waveforms by name, like `EXP`, `SFFM`, `AM` or `TRNOISE`.
+    Other(String, Vec<String>),
+}
+
+///The names of the waveforms that are passed to ngspice unchanged.
+const OTHER_WAVEFORMS: [&str; 5] = ["EXP", "SFFM", "AM", "TRNOISE", "TRRANDOM"];
+
+impl Waveform {
+    ///Create a sinusoidal waveform.
+    pub fn sin(offset: &str, amplitude: &str, frequency: &str) -> Self {
+        Waveform::Sin {
+            offset: offset.to_string(),
+            amplitude: amplitude.to_string(),
+            frequency: Some(frequency.to_string()),
+            delay: None,
+            damping: None,
+            phase: None,
+        }
+    }
+
+    ///Create a pulse waveform.
+    pub fn pulse(
+        initial: &str,
+        pulsed: &str,
+        delay: &str,
+        rise: &str,
+        fall: &str,
+        width: &str,
+        period: &str,
+    ) -> Self {
+        Waveform::Pulse {
+            initial: initial.to_string(),
+            pulsed: pulsed.to_string(),
+            delay: Some(delay.to_string()),
+            rise: Some(rise.to_string()),
+            fall: Some(fall.to_string()),
+            width: Some(width.to_string()),
+            period: Some(period.to_string()),
+        }
+    }
+
+    ///Create a waveform from the function name and the arguments.
+    fn from_args(reference: &str, name: &str, args: Vec<String>) -> Result<Self, Error> {
+        let error = || {
+            Error::InvalidValue(
+                reference.to_string(),
+                format!("{}({})", name, args.join(" ")),
+            )
+        };
+        let mut iter = args.clone().into_iter();
+        match name {
+            "SIN" => {
+                if args.len() < 2 || args.len() > 6 {
+                    return Err(error());
+                }
+                Ok(Waveform::Sin {
+                    offset: iter.next().unwrap(),
+                    amplitude: iter.next().unwrap(),
+                    frequency: iter.next(),
+                    delay: iter.next(),
+                    damping: iter.next(),
+                    phase: iter.next(),
+                })
+            }
+            "PULSE" => {
+                if args.len() < 2 || args.len() > 7 {
+                    return Err(error());
+                }
+                Ok(Waveform::Pulse {
+                    initial: iter.next().unwrap(),
+                    pulsed: iter.next().unwrap(),
+                    delay: iter.next(),
+                    rise: iter.next(),
+                    fall: iter.next(),
+                    width: iter.next(),
+                    period: iter.next(),
+                })
+            }
+            "PWL" => {
+                if args.is_empty() || args.len() % 2 == 1 {
+                    return Err(error());
+                }
+                Ok(Waveform::Pwl(
+                    args.chunks(2)
+                        .map(|c| (c[0].to_string(), c[1].to_string()))
+                        .collect(),
+                ))
+            }
+            _ if OTHER_WAVEFORMS.contains(&name) && !args.is_empty() => {
+                Ok(Waveform::Other(name.to_string(), args))
+            }
+            _ => Err(error()),
+        }
+    }
+
+    ///The arguments of the waveform in order.
+    fn args(&self) -> Vec<&String> {
+        match self {
+            Waveform::Sin {
+                offset,
+                amplitude,
+                frequency,
+                delay,
+                damping,
+                phase,
+            } => {
+                let mut args = vec![offset, amplitude];
+                args.extend(
+                    [frequency, delay, damping, phase]
+                        .into_iter()
+                        .map_while(|a| a.as_ref()),
+                );
+                args
+            }
+            Waveform::Pulse {
+                initial,
+                pulsed,
+                delay,
+                rise,
+                fall,
+                width,
+                period,
+            } => {
+                let mut args = vec![initial, pulsed];
+                args.extend(
+                    [delay, rise, fall, width, period]
+                        .into_iter()
+                        .map_while(|a| a.as_ref()),
+                );
+                args
+            }
+            Waveform::Pwl(points) => points.iter().flat_map(|(t, v)| [t, v]).collect(),
+            Waveform::Other(_, args) => args.iter().collect(),
+        }
+    }
+
+    ///Check the values of the waveform.
+    pub fn validate(&self, reference: &str) -> Result<(), Error> {
+        for arg in self.args() {
+            check_value(reference, arg)?;
+        }
+        if let Waveform::Pwl(points) = self {
+            let times: Vec<f64> = points.iter().filter_map(|(t, _)| parse_value(t)).collect();
+            if times.windows(2).any(|w| w[1] < w[0]) {
+                return Err(Error::InvalidValue(
+                    reference.to_string(),
+                    String::from("PWL time values must be increasing"),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Waveform {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Waveform::Sin { .. } => "SIN",
+            Waveform::Pulse { .. } => "PULSE",
+            Waveform::Pwl(_) => "PWL",
+            Waveform::Other(name, _) => name.as_str(),
+        };
+        let args: Vec<&str> = self.args().iter().map(|a| a.as_str()).collect();
+        write!(f, "{}({})", name, args.join(" "))
+    }
+}
+
+///An independent voltage or current source.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Source {
+    ///The DC value.
+    pub dc: Option<String>,
+    ///The AC magnitude and the optional phase.
+    pub ac: Option<(String, Option<String>)>,
+    ///The transient waveform.
+    pub waveform: Option<Waveform>,
+}
+
+impl Source {
+    ///Create a DC source.
+    pub fn dc(value: &str) -> Self {
+        Self {
+            dc: Some(value.to_string()),
+            ..Default::default()
+        }
+    }
+
+    ///Set the AC magnitude of the source.
+    pub fn ac(mut self, magnitude: &str) -> Self {
+        self.ac = Some((magnitude.to_string(), None));
+        self
+    }
+
+    ///Set the transient waveform of the source.
+    pub fn waveform(mut self, waveform: Waveform) -> Self {
+        self.waveform = Some(waveform);
+        self
+    }
+
+    ///Parse the source specification like `DC 5 AC 1 SIN(0 1 1k)`.
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        Self::parse_for("source", value)
+    }
+
+    ///Parse the source specification of the element, the errors name the reference.
+    pub(crate) fn parse_for(reference: &str, value: &str) -> Result<Self, Error> {
+        let error = || Error::InvalidValue(reference.to_string(), value.to_string());
+        let normalized = value
+            .replace('(', " ( ")
+            .replace(')', " ) ")
+            .replace(',', " ");
+        let tokens: Vec<&str> = normalized.split_whitespace().collect();
+        let mut source = Source::default();
+        let mut index = 0;
+        while index < tokens.len() {
+            let token = tokens[index].to_uppercase();
+            index += 1;
+            match token.as_str() {
+                "DC" => {
+                    let value = tokens.get(index).ok_or_else(error)?;
+                    source.dc = Some(value.to_string());
+                    index += 1;
+                }
+                "AC" => {
+                    let magnitude = tokens.get(index).ok_or_else(error)?;
+                    index += 1;
+                    let phase = match tokens.get(index) {
+                        Some(phase) if is_value(phase) => {
+                            index += 1;
+                            Some(phase.to_string())
+                        }
+                        _ => None,
+                    };
+                    source.ac = Some((magnitude.to_string(), phase));
+                }
+                "SIN" | "PULSE" | "PWL" | "EXP" | "SFFM" | "AM" | "TRNOISE" | "TRRANDOM" => {
+                    let mut args = Vec::new();
+                    if tokens.get(index) == Some(&"(") {
+                        index += 1;
+                        while tokens.get(index).ok_or_else(error)? != &")" {
+                            args.push(tokens[index].to_string());
+                            index += 1;
+                        }
+                        index += 1;
+                    } else {
+                        while index < tokens.len() && is_value(tokens[index]) {
+                            args.push(tokens[index].to_string());
+                            index += 1;
+                        }
+                    }
+                    source.waveform = Some(Waveform::from_args(reference, &token, args)?);
+                }
+                _ if index == 1 && is_value(tokens[0]) => {
+                    source.dc = Some(tokens[0].to_string());
+                }
+                _ => return Err(error()),
+            }
+        }
+        if source.dc.is_none() && source.ac.is_none() && source.waveform.is_none() {
+            return Err(error());
+        }
+        Ok(source)
+    }
+
+    ///Check the values of the source.
+    pub fn validate(&self, reference: &str) -> Result<(), Error> {
+        if let Some(dc) = &self.dc {
+            check_value(reference, dc)?;
+        }
+        if let Some((magnitude, phase)) = &self.ac {
+            check_value(reference, magnitude)?;
+            if let Some(phase) = phase {
+                check_value(reference, phase)?;
+            }
+        }
+        if let Some(waveform) = &self.waveform {
+            waveform.validate(reference)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(dc) = &self.dc {
+            parts.push(format!("DC {}", dc));
+        }
+        if let Some((magnitude, phase)) = &self.ac {
+            match phase {
+                Some(phase) => parts.push(format!("AC {} {}", magnitude, phase)),
+                None => parts.push(format!("AC {}", magnitude)),
+            }
+        }
+        if let Some(waveform) = &self.waveform {
+            parts.push(waveform.to_string());
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_value, parse_value, spice_value, Source, Waveform};
+
+    #[test]
+    fn test_is_value() {
+        assert!(is_value("4.7k"));
+        assert!(is_value("10uF"));
+        assert!(is_value("-1e-3"));
+        assert!(is_value("{gain*2}"));
+        assert!(!is_value("4k7x 1"));
+        assert!(!is_value("R"));
+        assert!(is_value("4k7"));
+        assert!(is_value("2R2"));
+    }
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(Some(4700.0), parse_value("4.7k"));
+        assert_eq!(Some(2.2e6), parse_value("2.2Meg"));
+        assert_eq!(Some(1e-3), parse_value("1mA"));
+        assert_eq!(None, parse_value("{x}"));
+        assert_eq!(Some(4700.0), parse_value("4k7"));
+        assert_eq!("4.7k", spice_value("4k7"));
+        assert_eq!("1.5Meg", spice_value("1M5"));
+        assert_eq!("2.2", spice_value("2R2"));
+        assert_eq!("10k", spice_value("10k"));
+    }
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(Source::dc("5"), Source::parse("5").unwrap());
+        assert_eq!(Source::dc("15V"), Source::parse("dc 15V").unwrap());
+        let source = Source::parse("AC 2V SIN(0 2V 1k)").unwrap();
+        assert_eq!(Some((String::from("2V"), None)), source.ac);
+        assert_eq!(Some(Waveform::sin("0", "2V", "1k")), source.waveform);
+        let source = Source::parse("PWL(0 0 1m 5)").unwrap();
+        assert_eq!(
+            Some(Waveform::Pwl(vec![
+                (String::from("0"), String::from("0")),
+                (String::from("1m"), String::from("5"))
+            ])),
+            source.waveform
+        );
+        assert!(Source::parse("PWL(0 0 1m)").is_err());
+        assert!(Source::parse("SIN(0)").is_err());
+        assert!(Source::parse("foo").is_err());
+        let source = Source::parse("EXP(0 1 2n 30n 60n 40n)").unwrap();
+        assert_eq!(
+            Some(Waveform::Other(
+                String::from("EXP"),
+                vec!["0", "1", "2n", "30n", "60n", "40n"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect()
+            )),
+            source.waveform
+        );
+        assert_eq!("EXP(0 1 2n 30n 60n 40n)", source.to_string());
+        assert!(Source::parse("DC 0 SFFM(0 1 10k 5 1k)").is_ok());
+        assert!(Source::parse("TRNOISE(20n 0.5n 0 0)").is_ok());
+    }
+}
@@ -0,0 +1,276 @@
+//! XSPICE code models and the digital results.
+//!
+//! The digital nodes of the XSPICE code models are event driven, ngspice
+//! stores them separate from the analog vectors. The values are returned in
+//! the `events` plot, the vector `node` contains the logic levels and
+//! `node#time` the simulation time of the events.
+//!
+//! # Examples
+//! ```
+//! use simulation::{Circuit, CodeModel};
+//!
+//! let mut circuit = Circuit::new(String::from("nand"), vec![]);
+//! circuit
+//!     .code_model(
+//!         String::from("nand1"),
+//!         CodeModel::Nand,
+//!         vec![(String::from("rise_delay"), String::from("1n"))],
+//!     )
+//!     .unwrap();
+//! circuit
+//!     .gate(
+//!         String::from("A1"),
+//!         vec![String::from("a"), String::from("b")],
+//!         String::from("y"),
+//!         String::from("nand1"),
+//!     )
+//!     .unwrap();
+//! assert_eq!(vec!["a", "b", "y"], circuit.digital_nodes());
+//! ```
+
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+
+use crate::error::Error;
+
+///The name of the plot with the digital results.
+pub const EVENTS: &str = "events";
+
+///The XSPICE code models for digital and mixed signal circuits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeModel {
+    And,
+    Nand,
+    Or,
+    Nor,
+    Xor,
+    Xnor,
+    Inverter,
+    Buffer,
+    Tristate,
+    Pullup,
+    Pulldown,
+    Dff,
+    Jkff,
+    Tff,
+    Srff,
+    Dlatch,
+    Srlatch,
+    Osc,
+    Source,
+    AdcBridge,
+    DacBridge,
+}
+
+const CODE_MODELS: [(CodeModel, &str); 21] = [
+    (CodeModel::And, "d_and"),
+    (CodeModel::Nand, "d_nand"),
+    (CodeModel::Or, "d_or"),
+    (CodeModel::Nor, "d_nor"),
+    (CodeModel::Xor, "d_xor"),
+    (CodeModel::Xnor, "d_xnor"),
+    (CodeModel::Inverter, "d_inverter"),
+    (CodeModel::Buffer, "d_buffer"),
+    (CodeModel::Tristate, "d_tristate"),
+    (CodeModel::Pullup, "d_pullup"),
+    (CodeModel::Pulldown, "d_pulldown"),
+    (CodeModel::Dff, "d_dff"),
+    (CodeModel::Jkff, "d_jkff"),
+    (CodeModel::Tff, "d_tff"),
+    (CodeModel::Srff, "d_srff"),
+    (CodeModel::Dlatch, "d_dlatch"),
+    (CodeModel::Srlatch, "d_srlatch"),
+    (CodeModel::Osc, "d_osc"),
+    (CodeModel::Source, "d_source"),
+    (CodeModel::AdcBridge, "adc_bridge"),
+    (CodeModel::DacBridge, "dac_bridge"),
+];
+
+impl CodeModel {
+    ///The name of the code model in the `.model` statement.
+    pub fn name(&self) -> &'static str {
+        CODE_MODELS.iter().find(|(m, _)| m == self).unwrap().1
+    }
+
+    ///Find the code model by name, like `d_and`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        CODE_MODELS
+            .iter()
+            .find(|(_, n)| n.eq_ignore_ascii_case(name))
+            .map(|(m, _)| *m)
+    }
+
+    ///Test if the port of the element is digital.
+    pub(crate) fn is_digital(&self, port: usize) -> bool {
+        match self {
+            CodeModel::AdcBridge | CodeModel::Osc => port == 1,
+            CodeModel::DacBridge => port == 0,
+            _ => true,
+        }
+    }
+}
+
+///Split the port type, like `%vd`, from the port.
+pub(crate) fn port_type(port: &str) -> (Option<&str>, &str) {
+    let port = port.trim();
+    if !port.starts_with('%') {
+        return (None, port);
+    }
+    let end = port
+        .find(|c: char| c.is_whitespace() || c == '(' || c == '[')
+        .unwrap_or(port.len());
+    (Some(&port[..end]), port[end..].trim())
+}
+
+///Test if the port has an analog port type, like `%v` or `%vd (a b)`.
+pub(crate) fn is_analog(port: &str) -> bool {
+    port.split(|c: char| c.is_whitespace() || "[]()".contains(c))
+        .any(|t| t.starts_with('%') && !t.eq_ignore_ascii_case("%d"))
+}
+
+///The nodes of a port, like `a`, `[a ~b]` or `%vd (a b)`.
+pub(crate) fn port_nodes(port: &str) -> Vec<String> {
+    port.split(|c: char| c.is_whitespace() || "[]()".contains(c))
+        .filter(|t| !t.is_empty() && !t.starts_with('%') && !t.eq_ignore_ascii_case("null"))
+        .map(|t| t.trim_start_matches('~').to_string())
+        .collect()
+}
+
+///Convert a digital value to the logic level.
+///
+///The values from ngspice are the state and the strength, like `1s`, the
+///values in VCD files the state only. Unknown and high impedance states
+///are 0.5, analog event nodes return the value.
+pub(crate) fn logic_level(value: &str) -> f64 {
+    match value.chars().next() {
+        Some('0') if value.len() <= 2 && !value.contains('.') => 0.0,
+        Some('1') if value.len() <= 2 && !value.contains('.') => 1.0,
+        Some('U' | 'u' | 'X' | 'x' | 'Z' | 'z') => 0.5,
+        _ => value.parse::<f64>().unwrap_or(f64::NAN),
+    }
+}
+
+///Parse the VCD timescale, like `1 ps`.
+fn timescale(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let number: f64 = text[..split].trim().parse().ok()?;
+    let unit = match text[split..].trim() {
+        "s" => 1.0,
+        "ms" => 1e-3,
+        "us" => 1e-6,
+        "ns" => 1e-9,
+        "ps" => 1e-12,
+        "fs" => 1e-15,
+        _ => return None,
+    };
+    Some(number * unit)
+}
+
+///The times and the logic levels by node name.
+pub(crate) type Events = IndexMap<String, (Vec<f64>, Vec<f64>)>;
+
+///Read the values of a value change dump.
+pub(crate) fn read_vcd(content: &str) -> Result<Events, Error> {
+    let error = |msg: &str| Error::RawFile(String::from("vcd"), msg.to_string());
+    let mut ids: HashMap<String, String> = HashMap::new();
+    let mut result: Events = IndexMap::new();
+    let mut scale = 1.0;
+    let mut time = 0.0;
+    let mut tokens = content.split_whitespace();
+    while let Some(token) = tokens.next() {
+        let mut body: Vec<&str> = Vec::new();
+        for token in tokens.by_ref() {
+            if token == "$end" {
+                break;
+            }
+            body.push(token);
+        }
+        match token {
+            "$timescale" => {
+                scale = timescale(&body.join("")).ok_or_else(|| error("invalid timescale"))?;
+            }
+            "$var" if body.len() >= 4 => {
+                ids.insert(body[2].to_string(), body[3].to_string());
+            }
+            "$enddefinitions" => break,
+            _ if token.starts_with('$') => {}
+            _ => return Err(error(&format!("unexpected '{}' in the header", token))),
+        }
+    }
+    let mut value = |id: &str, level: f64, time: f64| {
+        if let Some(name) = ids.get(id) {
+            let entry = result.entry(name.clone()).or_default();
+            entry.0.push(time);
+            entry.1.push(level);
+        }
+    };
+    while let Some(token) = tokens.next() {
+        if let Some(t) = token.strip_prefix('#') {
+            time = t.parse::<f64>().map_err(|_| error("invalid time"))? * scale;
+        } else if token.starts_with('$') {
+            //$dumpvars and the other sections contain the values.
+        } else if let Some(bits) = token.strip_prefix(['b', 'B']) {
+            let id = tokens.next().ok_or_else(|| error("vector without id"))?;
+            let level = i64::from_str_radix(bits, 2)
+                .map(|v| v as f64)
+                .unwrap_or(0.5);
+            value(id, level, time);
+        } else if let Some(real) = token.strip_prefix(['r', 'R']) {
+            let id = tokens.next().ok_or_else(|| error("real without id"))?;
+            value(id, real.parse().unwrap_or(f64::NAN), time);
+        } else if token.len() > 1 {
+            value(&token[1..], logic_level(&token[..1]), time);
+        } else {
+            return Err(error(&format!("invalid value '{}'", token)));
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_analog, logic_level, port_nodes, port_type, read_vcd, CodeModel};
+
+    #[test]
+    fn test_code_model() {
+        assert_eq!("d_nand", CodeModel::Nand.name());
+        assert_eq!(
+            Some(CodeModel::AdcBridge),
+            CodeModel::from_name("ADC_BRIDGE")
+        );
+        assert_eq!(None, CodeModel::from_name("d_unknown"));
+    }
+    #[test]
+    fn test_ports() {
+        assert_eq!((Some("%vd"), "(a b)"), port_type("%vd (a b)"));
+        assert_eq!((Some("%d"), "[a b]"), port_type("%d[a b]"));
+        assert_eq!((None, "[a ~b]"), port_type("[a ~b]"));
+        assert_eq!(vec!["a", "b"], port_nodes("%vd(a b)"));
+        assert_eq!(vec!["a", "b"], port_nodes("[a ~b NULL]"));
+        assert!(is_analog("[%vd (a b) %v c]"));
+        assert!(!is_analog("%d [a b]"));
+    }
+    #[test]
+    fn test_logic_level() {
+        assert_eq!(0.0, logic_level("0s"));
+        assert_eq!(1.0, logic_level("1r"));
+        assert_eq!(0.5, logic_level("Uz"));
+        assert_eq!(1.5, logic_level("1.5"));
+    }
+    #[test]
+    fn test_read_vcd() {
+        let vcd = read_vcd(concat!(
+            "$date today $end\n$version ngspice $end\n$timescale 1 ns $end\n",
+            "$scope module top $end\n$var wire 1 ! clk $end\n$var wire 1 \" out $end\n",
+            "$upscope $end\n$enddefinitions $end\n#0\n$dumpvars\n0!\nx\"\n$end\n",
+            "#10\n1!\n1\"\n#20\n0!\n"
+        ))
+        .unwrap();
+        assert_eq!((vec![0.0, 10e-9, 20e-9], vec![0.0, 1.0, 0.0]), vcd["clk"]);
+        assert_eq!((vec![0.0, 10e-9], vec![0.5, 1.0]), vcd["out"]);
+    }
+}
@@ -0,0 +1 @@
+.model DUP D(IS=1n)
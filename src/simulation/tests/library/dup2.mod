@@ -0,0 +1 @@
+.MODEL dup D(IS=2n)
@@ -26,6 +26,22 @@ mod tests {
             assert!(iter.next().is_none());
         }
         #[test]
+        fn test_point() {
+            use std::collections::HashSet;
+            assert_eq!(Point::new(78.74, 1.27), Point::new(78.74000000000001, 1.27));
+            assert_eq!(
+                Point { x: 78.74, y: 0.0 },
+                Point::new(78.74000000000001, -0.0)
+            );
+            //points on both sides of the rounding boundary are not equal.
+            let a = Point::new(1.000049, 0.0);
+            let b = Point::new(1.000051, 0.0);
+            assert_ne!(a, b);
+            let points: HashSet<Point> = [a, b, Point::new(1.00001, 0.0)].into_iter().collect();
+            assert_eq!(2, points.len());
+            assert!(points.contains(&Point::new(0.99999, 0.0)));
+        }
+        #[test]
         fn test_positions_summe() {
             let doc = SexpParser::load("tests/summe.kicad_sch").unwrap();
             let tree = SexpTree::from(doc.iter()).unwrap();
@@ -299,7 +315,7 @@ mod tests {
         extern crate sexp;
         extern crate simulation;
         use self::sexp::{SexpParser, SexpTree};
-        use self::simulation::{Circuit, Netlist};
+        use self::simulation::{natural_cmp, Circuit, Error, Netlist};
         #[test]
         fn test_circuit() {
             let doc = SexpParser::load("tests/low_pass_filter.kicad_sch").unwrap();
@@ -318,6 +334,94 @@ mod tests {
                 circuit.to_str(false).unwrap()
             );
         }
+        #[test]
+        fn test_circuit_sim_fields() {
+            let doc = SexpParser::load("tests/low_pass_filter_sim.kicad_sch").unwrap();
+            let tree = SexpTree::from(doc.iter()).unwrap();
+            let netlist = Netlist::from(&tree).unwrap();
+            let mut circuit = Circuit::new(String::from("sim"), vec![]);
+            netlist.circuit(&mut circuit).unwrap();
+
+            assert_eq!(
+                vec![
+                    String::from(".title auto generated netlist file."),
+                    String::from("R1 OUT IN 10k"),
+                    String::from("C1 OUT GND 100n")
+                ],
+                circuit.to_str(false).unwrap()
+            );
+        }
+        #[test]
+        fn test_circuit_labels() {
+            let doc = SexpParser::load("tests/labels.kicad_sch").unwrap();
+            let tree = SexpTree::from(doc.iter()).unwrap();
+            let netlist = Netlist::from(&tree).unwrap();
+            let mut circuit = Circuit::new(String::from("labels"), vec![]);
+            netlist.circuit(&mut circuit).unwrap();
+            assert_eq!(
+                vec![
+                    String::from(".title auto generated netlist file."),
+                    String::from("R1 A Net-_R1-Pad2_ 1k"),
+                    String::from("R2 A Net-_R1-Pad2_ 1k"),
+                ],
+                circuit.to_str(false).unwrap()
+            );
+            let conflicts = netlist.label_conflicts();
+            assert_eq!(1, conflicts.len());
+            assert_eq!("A", conflicts[0].0);
+            assert_eq!(vec![String::from("A"), String::from("B")], conflicts[0].1);
+        }
+        #[test]
+        fn test_natural_order() {
+            let mut pins = vec!["B1", "A10", "10", "A2", "2", "A1", "1"];
+            pins.sort_by(|a, b| natural_cmp(a, b));
+            assert_eq!(vec!["1", "2", "10", "A1", "A2", "A10", "B1"], pins);
+        }
+        #[test]
+        fn test_circuit_pins() {
+            let doc = SexpParser::load("tests/pins.kicad_sch").unwrap();
+            let tree = SexpTree::from(doc.iter()).unwrap();
+            let netlist = Netlist::from(&tree).unwrap();
+            let mut circuit =
+                Circuit::new(String::from("pins"), vec![String::from("tests/library/")]);
+            netlist.circuit(&mut circuit).unwrap();
+            assert_eq!(
+                vec![
+                    String::from(".title auto generated netlist file."),
+                    String::from(".include tests/library/dual.lib\n"),
+                    String::from("XU1 IN1 IN2 OUT1 VCC DUAL"),
+                ],
+                circuit.to_str(false).unwrap()
+            );
+        }
+        #[test]
+        fn test_circuit_library_primitive() {
+            let doc = SexpParser::load("tests/primitive.kicad_sch").unwrap();
+            let tree = SexpTree::from(doc.iter()).unwrap();
+            let netlist = Netlist::from(&tree).unwrap();
+            let mut circuit = Circuit::new(String::from("primitive"), vec![]);
+            netlist.circuit(&mut circuit).unwrap();
+            assert_eq!(
+                vec![
+                    String::from(".title auto generated netlist file."),
+                    String::from("RFB1 IN OUT 100"),
+                    String::from("CX1 OUT GND 10n"),
+                    String::from("R1 IN GND 1k"),
+                ],
+                circuit.to_str(false).unwrap()
+            );
+        }
+        #[test]
+        fn test_circuit_missing_unit() {
+            let doc = SexpParser::load("tests/pins_missing.kicad_sch").unwrap();
+            let tree = SexpTree::from(doc.iter()).unwrap();
+            let netlist = Netlist::from(&tree).unwrap();
+            let mut circuit = Circuit::new(String::from("pins"), vec![]);
+            assert!(matches!(
+                netlist.circuit(&mut circuit),
+                Err(Error::UnitNotFound(reference, 2)) if reference == "U1"
+            ));
+        }
         /* #[test]
         fn test_4007_vca() {
             let doc = SexpParser::load("files/4007_vca.kicad_sch").unwrap();
@@ -348,9 +452,9 @@ mod tests {
                 String::from(".include tests/spice/TL072-dual.lib\n"),
                 String::from(".include tests/spice/TL072.lib\n"),
                 String::from("R5 IN_1 OUTPUT 1k"),
-                String::from("R3 1 INPUT 100k"),
-                String::from("R4 IN_1 1 100k"),
-                String::from("XU1 IN_1 1 GND -15V NC NC NC +15V TL072c"),
+                String::from("R3 Net-_R3-Pad1_ INPUT 100k"),
+                String::from("R4 IN_1 Net-_R3-Pad1_ 100k"),
+                String::from("XU1 IN_1 Net-_R3-Pad1_ GND -15V NC NC NC +15V TL072c"),
                 String::from(".end"),
             ];
             assert_eq!(res, circuit.to_str(true).unwrap());
@@ -366,12 +470,12 @@ mod tests {
             let res = vec![
                 String::from(".title auto generated netlist file."),
                 String::from(".include tests/spice/4069ub.lib\n"),
-                String::from("R1 INPUT 1 100k"),
-                String::from("C1 1 2 47n"),
-                String::from("XU1 2 3 +5V GND 4069UB"),
-                String::from("C2 3 OUTPUT 10u"),
-                String::from("R2 3 2 100k"),
-                String::from("R3 4 GND 100k"),
+                String::from("R1 INPUT Net-_C1-Pad1_ 100k"),
+                String::from("C1 Net-_C1-Pad1_ Net-_C1-Pad2_ 47n"),
+                String::from("XU1 Net-_C1-Pad2_ Net-_C2-Pad1_ +5V GND 4069UB"),
+                String::from("C2 Net-_C2-Pad1_ OUTPUT 10u"),
+                String::from("R2 Net-_C2-Pad1_ Net-_C1-Pad2_ 100k"),
+                String::from("R3 OUTPUT GND 100k"),
                 String::from(".end"),
             ];
             assert_eq!(res, circuit.to_str(true).unwrap());
@@ -387,22 +491,22 @@ mod tests {
             let res = vec![
                 String::from(".title auto generated netlist file."),
                 String::from(".include tests/spice/4069ub.lib\n"),
-                String::from("R1 INPUT 1 100k"),
-                String::from("C1 1 2 47n"),
-                String::from("XU1 2 3 GND +5V 4069UB"),
-                String::from("C2 3 4 47n"),
-                String::from("R3 4 5 100k"),
-                String::from("XU2 5 HP GND +5V 4069UB"),
-                String::from("R5 HP 6 10k"),
-                String::from("XU3 6 BP GND +5V 4069UB"),
-                String::from("R6 BP 7 10k"),
-                String::from("XU4 7 LP GND +5V 4069UB"),
-                String::from("R2 3 2 100k"),
-                String::from("R4 HP 5 100k"),
-                String::from("C3 BP 6 10n"),
-                String::from("C4 LP 7 10n"),
-                String::from("R7 LP 5 100k"),
-                String::from("R8 BP 2 100k"),
+                String::from("R1 INPUT Net-_C1-Pad1_ 100k"),
+                String::from("C1 Net-_C1-Pad1_ Net-_C1-Pad2_ 47n"),
+                String::from("XU1 Net-_C1-Pad2_ Net-_C2-Pad1_ GND +5V 4069UB"),
+                String::from("C2 Net-_C2-Pad1_ Net-_C2-Pad2_ 47n"),
+                String::from("R3 Net-_C2-Pad2_ Net-_R3-Pad2_ 100k"),
+                String::from("XU2 Net-_R3-Pad2_ HP GND +5V 4069UB"),
+                String::from("R5 HP Net-_C3-Pad2_ 10k"),
+                String::from("XU3 Net-_C3-Pad2_ BP GND +5V 4069UB"),
+                String::from("R6 BP Net-_C4-Pad2_ 10k"),
+                String::from("XU4 Net-_C4-Pad2_ LP GND +5V 4069UB"),
+                String::from("R2 Net-_C2-Pad1_ Net-_C1-Pad2_ 100k"),
+                String::from("R4 HP Net-_R3-Pad2_ 100k"),
+                String::from("C3 BP Net-_C3-Pad2_ 10n"),
+                String::from("C4 LP Net-_C4-Pad2_ 10n"),
+                String::from("R7 LP Net-_R3-Pad2_ 100k"),
+                String::from("R8 BP Net-_C1-Pad2_ 100k"),
                 String::from(".end"),
             ];
             assert_eq!(res, circuit.to_str(true).unwrap());
@@ -426,4 +530,889 @@ mod tests {
             assert_eq!("tests/spice/CD4007.lib", include.get("CMOS4007").unwrap());
         }
     }
+    mod library {
+        extern crate simulation;
+        use self::simulation::{Circuit, Error, Kind, Library};
+        #[test]
+        fn test_resolve() {
+            let library = Library::new(&[String::from("tests/library/")]).unwrap();
+            let definition = library.resolve("opamp").unwrap();
+            assert_eq!("OPAMP", definition.name);
+            assert_eq!(Kind::Subckt, definition.kind);
+            assert_eq!(".include tests/library/opamp.lib", definition.directive());
+        }
+        #[test]
+        fn test_sections() {
+            let library = Library::new(&[String::from("tests/library/")]).unwrap();
+            let definition = library.resolve("NCH_FF").unwrap();
+            assert_eq!(Some(String::from("ff")), definition.section);
+            assert_eq!(8, definition.line);
+            assert_eq!(".lib tests/library/corners.lib ff", definition.directive());
+        }
+        #[test]
+        fn test_duplicates() {
+            let library = Library::new(&[String::from("tests/library/")]).unwrap();
+            assert!(matches!(
+                library.resolve("dup"),
+                Err(Error::DuplicateModel(_, _))
+            ));
+            assert!(matches!(
+                library.resolve("unknown"),
+                Err(Error::SpiceModelNotFound(_))
+            ));
+        }
+        #[test]
+        fn test_select_section() {
+            let library = Library::new(&[String::from("tests/library/")]).unwrap();
+            assert!(matches!(
+                library.resolve("pch"),
+                Err(Error::DuplicateModel(_, _))
+            ));
+            let definition = library.resolve_in("pch", &[String::from("ff")]).unwrap();
+            assert_eq!(Some(String::from("ff")), definition.section);
+            assert_eq!(11, definition.line);
+
+            let mut circuit = Circuit::new(String::from("corners"), vec![]);
+            circuit.include(
+                String::from("tests/library/corners.lib"),
+                Some(String::from("tt")),
+            );
+            assert_eq!(
+                Some(String::from("tt")),
+                circuit.definition("PCH").unwrap().section
+            );
+        }
+        #[test]
+        fn test_missing_include() {
+            let library = Library::new(&[String::from("tests/library/")]).unwrap();
+            assert!(matches!(
+                library.resolve("broken"),
+                Err(Error::IncludeNotFound(_, name)) if name == "missing.lib"
+            ));
+            assert_eq!("OPAMP", library.resolve("opamp").unwrap().name);
+        }
+    }
+    mod elements {
+        extern crate simulation;
+        use self::simulation::{Circuit, Error, Source, Waveform};
+        #[test]
+        fn test_elements() {
+            let mut circuit = Circuit::new(String::from("elements"), vec![]);
+            circuit
+                .inductor(
+                    String::from("L1"),
+                    String::from("1"),
+                    String::from("2"),
+                    String::from("10u"),
+                )
+                .unwrap();
+            circuit
+                .inductor(
+                    String::from("L2"),
+                    String::from("3"),
+                    String::from("0"),
+                    String::from("10u"),
+                )
+                .unwrap();
+            circuit
+                .coupling(
+                    String::from("K1"),
+                    String::from("L1"),
+                    String::from("L2"),
+                    String::from("0.99"),
+                )
+                .unwrap();
+            circuit
+                .current(
+                    String::from("I1"),
+                    String::from("0"),
+                    String::from("1"),
+                    String::from("DC 1m"),
+                )
+                .unwrap();
+            circuit
+                .vcvs(
+                    String::from("E1"),
+                    String::from("4"),
+                    String::from("0"),
+                    String::from("1"),
+                    String::from("0"),
+                    String::from("10"),
+                )
+                .unwrap();
+            circuit
+                .element(
+                    "H",
+                    String::from("H1"),
+                    vec![String::from("5"), String::from("0")],
+                    String::from("Vsense 1k"),
+                )
+                .unwrap();
+            circuit
+                .behavioral(
+                    String::from("B1"),
+                    String::from("6"),
+                    String::from("0"),
+                    String::from("V=v(4)*2"),
+                )
+                .unwrap();
+            circuit
+                .transmission_line(
+                    String::from("T1"),
+                    String::from("1"),
+                    String::from("0"),
+                    String::from("7"),
+                    String::from("0"),
+                    String::from("Z0=50 TD=10n"),
+                )
+                .unwrap();
+            circuit.model(String::from("SW1"), String::from("SW(VT=1 VH=0.1)"));
+            circuit
+                .switch(
+                    String::from("S1"),
+                    String::from("7"),
+                    String::from("0"),
+                    String::from("4"),
+                    String::from("0"),
+                    String::from("SW1"),
+                )
+                .unwrap();
+            circuit
+                .voltage_source(
+                    String::from("V1"),
+                    String::from("8"),
+                    String::from("0"),
+                    Source::dc("0")
+                        .waveform(Waveform::pulse("0", "5", "0", "1n", "1n", "1u", "2u")),
+                )
+                .unwrap();
+            assert_eq!(
+                vec![
+                    String::from(".title auto generated netlist file."),
+                    String::from(".model SW1 SW(VT=1 VH=0.1)"),
+                    String::from("L1 1 2 10u"),
+                    String::from("L2 3 0 10u"),
+                    String::from("K1 L1 L2 0.99"),
+                    String::from("I1 0 1 DC 1m"),
+                    String::from("E1 4 0 1 0 10"),
+                    String::from("H1 5 0 Vsense 1k"),
+                    String::from("B1 6 0 V=v(4)*2"),
+                    String::from("T1 1 0 7 0 Z0=50 TD=10n"),
+                    String::from("S1 7 0 4 0 SW1"),
+                    String::from("VV1 8 0 DC 0 PULSE(0 5 0 1n 1n 1u 2u)"),
+                ],
+                circuit.to_str(false).unwrap()
+            );
+        }
+        #[test]
+        fn test_names() {
+            let mut circuit = Circuit::parse(
+                "names\n.subckt amp in out\nR1 in out 1k\n.ends\nV3 c 0 DC 1\nX2 c 0 amp\n",
+                vec![],
+            )
+            .unwrap();
+            for (reference, n0) in [("R1", "a"), ("2", "b")] {
+                circuit
+                    .resistor(
+                        reference.to_string(),
+                        n0.to_string(),
+                        String::from("0"),
+                        String::from("1k"),
+                    )
+                    .unwrap();
+            }
+            for (reference, n0) in [("V1", "a"), ("2", "b")] {
+                circuit
+                    .voltage(
+                        reference.to_string(),
+                        n0.to_string(),
+                        String::from("0"),
+                        String::from("DC 1"),
+                    )
+                    .unwrap();
+            }
+            circuit.set_value("V3", "DC 2").unwrap();
+            circuit.set_value("V1", "DC 3").unwrap();
+            assert_eq!(
+                vec![
+                    ".title auto generated netlist file.",
+                    ".subckt amp in out",
+                    "R1 in out 1k",
+                    ".ends",
+                    "V3 c 0 DC 2",
+                    "X2 c 0 amp",
+                    "R1 a 0 1k",
+                    "R2 b 0 1k",
+                    "VV1 a 0 DC 3",
+                    "V2 b 0 DC 1",
+                ],
+                circuit.to_str(false).unwrap()
+            );
+        }
+        #[test]
+        fn test_invalid_values() {
+            let mut circuit = Circuit::new(String::from("elements"), vec![]);
+            assert!(matches!(
+                circuit.resistor(
+                    String::from("R1"),
+                    String::from("1"),
+                    String::from("0"),
+                    String::from("R")
+                ),
+                Err(Error::InvalidValue(_, _))
+            ));
+            assert!(matches!(
+                circuit.coupling(
+                    String::from("K1"),
+                    String::from("L1"),
+                    String::from("L2"),
+                    String::from("1.5")
+                ),
+                Err(Error::InvalidValue(_, _))
+            ));
+            assert!(matches!(
+                circuit.element(
+                    "M",
+                    String::from("M1"),
+                    vec![String::from("1")],
+                    String::from("NMOS")
+                ),
+                Err(Error::InvalidElement(_, _))
+            ));
+            assert!(circuit
+                .voltage(
+                    String::from("V1"),
+                    String::from("1"),
+                    String::from("0"),
+                    String::from("PWL(1m 0 0 5)")
+                )
+                .is_err());
+            circuit
+                .resistor(
+                    String::from("R1"),
+                    String::from("1"),
+                    String::from("0"),
+                    String::from("1k"),
+                )
+                .unwrap();
+            assert!(circuit.set_value("R1", "x").is_err());
+            circuit.set_value("R1", "2k").unwrap();
+            assert_eq!(String::from("R1 1 0 2k"), circuit.to_str(false).unwrap()[1]);
+            circuit.set_value("R1", "4k7").unwrap();
+            assert_eq!(
+                String::from("R1 1 0 4.7k"),
+                circuit.to_str(false).unwrap()[1]
+            );
+            circuit
+                .behavioral(
+                    String::from("B1"),
+                    String::from("1"),
+                    String::from("0"),
+                    String::from("V=v(2)*2"),
+                )
+                .unwrap();
+            assert!(circuit.set_value("B1", "v(2)*2").is_err());
+            assert!(matches!(
+                circuit.voltage(
+                    String::from("V2"),
+                    String::from("2"),
+                    String::from("0"),
+                    String::from("SIN(0)")
+                ),
+                Err(Error::InvalidValue(reference, _)) if reference == "V2"
+            ));
+            circuit
+                .voltage(
+                    String::from("V3"),
+                    String::from("3"),
+                    String::from("0"),
+                    String::from("EXP(0 1 2n 30n 60n 40n)"),
+                )
+                .unwrap();
+        }
+        #[test]
+        fn test_params() {
+            let mut filter = Circuit::new(String::from("filter"), vec![]);
+            filter
+                .resistor(
+                    String::from("R1"),
+                    String::from("in"),
+                    String::from("out"),
+                    String::from("{rval}"),
+                )
+                .unwrap();
+            filter
+                .capacitor(
+                    String::from("C1"),
+                    String::from("out"),
+                    String::from("0"),
+                    String::from("{1/(2*pi*fc*rval)}"),
+                )
+                .unwrap();
+            let mut circuit = Circuit::new(String::from("params"), vec![]);
+            circuit
+                .parametric_subcircuit(
+                    String::from("filter"),
+                    vec![String::from("in"), String::from("out")],
+                    vec![(String::from("rval"), String::from("1k"))],
+                    filter,
+                )
+                .unwrap();
+            circuit
+                .param(String::from("fc"), String::from("1k"))
+                .unwrap();
+            assert!(circuit
+                .param(String::from("1fc"), String::from("1k"))
+                .is_err());
+            circuit
+                .func(
+                    String::from("db"),
+                    vec![String::from("x")],
+                    String::from("{20*log10(x)}"),
+                )
+                .unwrap();
+            circuit
+                .circuit(
+                    String::from("X1"),
+                    vec![String::from("in"), String::from("out")],
+                    String::from("filter rval=2k"),
+                )
+                .unwrap();
+            circuit.set_value("fc", "10k").unwrap();
+            circuit.set_value("X1.rval", "4.7k").unwrap();
+            circuit.set_value("X1.RVAL", "4.7k").unwrap();
+            assert!(matches!(
+                circuit.set_value("X1.gain", "2"),
+                Err(Error::UnknownCircuitElement(name)) if name == "X1.gain"
+            ));
+            assert!(circuit.set_value("X2.rval", "1k").is_err());
+            assert_eq!(
+                vec![
+                    ".title auto generated netlist file.",
+                    ".param fc=10k",
+                    ".func db(x) {20*log10(x)}",
+                    ".subckt filter in out params: rval=1k",
+                    "R1 in out {rval}",
+                    "C1 out 0 {1/(2*pi*fc*rval)}",
+                    ".ends",
+                    "XX1 in out filter rval=4.7k",
+                ],
+                circuit.to_str(false).unwrap()
+            );
+        }
+    }
+    mod diagnostics {
+        extern crate simulation;
+        use self::simulation::{parse_diagnostics, Diagnostic};
+
+        #[test]
+        fn unknown_subckt() {
+            let lines = vec![String::from(
+                "stderr Error: unknown subckt: xu1 in out 0 tl072",
+            )];
+            assert_eq!(
+                vec![Diagnostic::UnknownModel {
+                    element: String::from("xu1"),
+                    model: String::from("tl072")
+                }],
+                parse_diagnostics(&lines)
+            );
+        }
+        #[test]
+        fn invalid_model() {
+            let lines = vec![
+                String::from("stderr Error on line 3 or its substitute:"),
+                String::from("stderr   q1 c b 0 bc547 area=2"),
+                String::from("stderr could not find a valid modelname"),
+            ];
+            assert_eq!(
+                vec![Diagnostic::UnknownModel {
+                    element: String::from("q1"),
+                    model: String::from("bc547")
+                }],
+                parse_diagnostics(&lines)
+            );
+        }
+        #[test]
+        fn singular_matrix() {
+            let lines = vec![
+                String::from("stdout Circuit: auto generated netlist file."),
+                String::from("stderr Warning: singular matrix:  check node out"),
+            ];
+            assert_eq!(
+                vec![Diagnostic::SingularMatrix {
+                    node: String::from("out")
+                }],
+                parse_diagnostics(&lines)
+            );
+        }
+        #[test]
+        fn timestep_too_small() {
+            let lines = vec![String::from(
+                "stderr doAnalyses: TRAN:  Timestep too small; time = 2.5e-05, timestep = 1.25e-19: trouble with node \"out\"",
+            )];
+            assert_eq!(
+                vec![Diagnostic::TimestepTooSmall {
+                    element: String::from("out"),
+                    time: String::from("2.5e-05")
+                }],
+                parse_diagnostics(&lines)
+            );
+        }
+        #[test]
+        fn unknown_node() {
+            let lines = vec![String::from("stderr Error: no such vector v(foo)")];
+            assert_eq!(
+                vec![Diagnostic::UnknownNode {
+                    node: String::from("v(foo)")
+                }],
+                parse_diagnostics(&lines)
+            );
+        }
+    }
+    mod project {
+        extern crate simulation;
+        use self::simulation::{Circuit, Project};
+        use std::path::Path;
+
+        #[test]
+        fn test_sheets() {
+            let project = Project::load(Path::new("tests/hierarchy/root.kicad_sch")).unwrap();
+            let paths: Vec<&str> = project.sheets.iter().map(|s| s.path.as_str()).collect();
+            assert_eq!(vec!["/", "/filter1/", "/filter2/"], paths);
+            assert_eq!(Some(0), project.sheets[1].parent);
+        }
+        #[test]
+        fn test_circuit() {
+            let project = Project::load(Path::new("tests/hierarchy/root.kicad_sch")).unwrap();
+            let mut circuit = Circuit::new(String::from("hierarchy"), vec![]);
+            project.circuit(&mut circuit).unwrap();
+            assert_eq!(
+                vec![
+                    String::from(".title auto generated netlist file."),
+                    String::from("R3 OUTPUT GND 1k"),
+                    String::from("R1 INPUT MID 4.7k"),
+                    String::from("C1 MID GND 47n"),
+                    String::from("R2 MID OUTPUT 4.7k"),
+                    String::from("C2 OUTPUT GND 47n"),
+                ],
+                circuit.to_str(false).unwrap()
+            );
+        }
+        #[test]
+        fn test_missing_sheet() {
+            assert!(Project::load(Path::new("tests/hierarchy/missing.kicad_sch")).is_err());
+        }
+    }
+    mod bus {
+        extern crate sexp;
+        extern crate simulation;
+        use self::sexp::{SexpParser, SexpTree};
+        use self::simulation::{Circuit, Netlist, Project};
+        use std::path::Path;
+
+        #[test]
+        fn test_buses() {
+            let doc = SexpParser::load("tests/bus/root.kicad_sch").unwrap();
+            let tree = SexpTree::from(doc.iter()).unwrap();
+            let netlist = Netlist::from(&tree).unwrap();
+            assert_eq!(1, netlist.buses().len());
+            let bus = &netlist.buses()[0];
+            assert_eq!(vec![String::from("DATA[0..1]")], bus.labels);
+            assert_eq!(
+                vec![(
+                    String::from("9e3a1c2b-4d5e-4f60-8a7b-0c1d2e3f4a02"),
+                    String::from("D[0..1]")
+                )],
+                bus.sheet_pins
+            );
+            assert_eq!(
+                Some(vec![String::from("EN"), String::from("RST")]),
+                netlist.bus_members("{CTRL}")
+            );
+        }
+        #[test]
+        fn test_circuit() {
+            let project = Project::load(Path::new("tests/bus/root.kicad_sch")).unwrap();
+            let mut circuit = Circuit::new(String::from("bus"), vec![]);
+            project.circuit(&mut circuit).unwrap();
+            assert_eq!(
+                vec![
+                    String::from(".title auto generated netlist file."),
+                    String::from("R1 DATA0 GND 1k"),
+                    String::from("R2 DATA1 GND 1k"),
+                    String::from("R3 DATA0 GND 2k"),
+                    String::from("R4 DATA1 GND 2k"),
+                ],
+                circuit.to_str(false).unwrap()
+            );
+        }
+    }
+    mod export {
+        extern crate json;
+        extern crate simulation;
+        use self::simulation::Project;
+        use std::path::Path;
+
+        #[test]
+        fn test_connectivity() {
+            let project = Project::load(Path::new("tests/hierarchy/root.kicad_sch")).unwrap();
+            let connectivity = project.connectivity().unwrap();
+            let references: Vec<&str> = connectivity
+                .components
+                .iter()
+                .map(|c| c.reference.as_str())
+                .collect();
+            assert_eq!(vec!["C1", "C2", "R1", "R2", "R3"], references);
+            let nets: Vec<(&str, usize)> = connectivity
+                .nets
+                .iter()
+                .map(|n| (n.name.as_str(), n.nodes.len()))
+                .collect();
+            assert_eq!(
+                vec![("/MID", 3), ("GND", 3), ("INPUT", 1), ("OUTPUT", 3)],
+                nets
+            );
+        }
+        #[test]
+        fn test_formats() {
+            let project = Project::load(Path::new("tests/hierarchy/root.kicad_sch")).unwrap();
+            let connectivity = project.connectivity().unwrap();
+
+            let kicad = connectivity.to_kicad().unwrap();
+            assert!(kicad.starts_with("(export\n  (version \"E\")"));
+            assert!(kicad.contains("(names \"/filter2/\")"));
+            assert!(kicad.contains("(name \"/MID\")"));
+
+            let data = json::parse(&connectivity.to_json()).unwrap();
+            assert_eq!("/MID", data["nets"][0]["name"]);
+            assert_eq!("R1", data["nets"][0]["nodes"][1]["reference"]);
+            assert_eq!("2", data["nets"][0]["nodes"][1]["pin"]);
+
+            let dot = connectivity.to_dot();
+            assert!(dot.starts_with("graph netlist {"));
+            assert!(dot.contains("  \"R1\" -- \"net:/MID\" [label=\"2\"];"));
+        }
+    }
+    mod import {
+        extern crate simulation;
+        use self::simulation::{Circuit, Error};
+        use std::path::Path;
+        #[test]
+        fn test_load() {
+            let mut circuit =
+                Circuit::load(Path::new("tests/spice/amplifier.cir"), vec![]).unwrap();
+            circuit.set_value("R1", "1k").unwrap();
+            let netlist = circuit.to_str(true).unwrap();
+            assert_eq!(
+                vec![
+                    ".title auto generated netlist file.",
+                    ".include tests/spice/TL072.lib\n",
+                    ".param gain=10",
+                    ".subckt divider in out",
+                    "R1 in out 10k",
+                    "R2 out 0 10k",
+                    ".ends",
+                    ".options savecurrents",
+                    ".tran 10u 5m",
+                    ".model SW1 SW(VT=1 VH=0.1)",
+                    "V1 in 0 DC 0 AC 1 SIN(0 0.1 1k)",
+                    "V2 vcc 0 DC 15",
+                    "V3 vee 0 DC -15",
+                    "R1 in minus 1k",
+                    "R2 minus out {gain*10k} tc1=0",
+                    "XU1 0 minus vcc vee out TL072",
+                    "X1 out half divider",
+                    ".end",
+                ],
+                netlist
+            );
+            assert_eq!(
+                vec![
+                    String::from("run"),
+                    String::from("let gain = maximum(v(out)) / maximum(v(in))")
+                ],
+                circuit.controls
+            );
+        }
+        #[test]
+        fn test_library() {
+            let circuit =
+                Circuit::load(Path::new("tests/spice/Potentiometer.cir"), vec![]).unwrap();
+            assert_eq!(
+                vec![
+                    ".title auto generated netlist file.",
+                    ".subckt Potentiometer n1 n2 n3",
+                    "R1 n1 n2 10.0kOhm",
+                    "R2 n2 n3 90.0kOhm",
+                    ".ends",
+                ],
+                circuit.to_str(false).unwrap()
+            );
+        }
+        #[test]
+        fn test_libraries() {
+            for entry in std::fs::read_dir("tests/spice").unwrap() {
+                let path = entry.unwrap().path();
+                let circuit = Circuit::load(&path, vec![]);
+                assert!(circuit.is_ok(), "{}: {:?}", path.display(), circuit);
+            }
+        }
+        #[test]
+        fn test_params() {
+            let mut circuit = Circuit::parse(
+                concat!(
+                    "parameters\n",
+                    ".param rval = 1k gain={rval * 10}\n",
+                    ".func db(x) = {20*log10(x)}\n",
+                    ".subckt amp in out params: r = 1k\n",
+                    "R1 in out {r}\n",
+                    ".ends\n",
+                    "X1 in out amp params: r=2k\n",
+                    "R1 out 0 {rval}\n",
+                ),
+                vec![],
+            )
+            .unwrap();
+            circuit.set_value("rval", "2k").unwrap();
+            circuit.set_value("X1.r", "4k").unwrap();
+            assert_eq!(
+                vec![
+                    ".title auto generated netlist file.",
+                    ".param rval=2k",
+                    ".param gain={rval * 10}",
+                    ".func db(x) {20*log10(x)}",
+                    ".subckt amp in out params: r=1k",
+                    "R1 in out {r}",
+                    ".ends",
+                    "X1 in out amp r=4k",
+                    "R1 out 0 {rval}",
+                ],
+                circuit.to_str(false).unwrap()
+            );
+        }
+        #[test]
+        fn test_parent_models() {
+            let circuit = Circuit::parse(
+                concat!(
+                    "parent models\n",
+                    ".model D1N4148 D(is=2.52n rs=0.568 n=1.752)\n",
+                    ".subckt clamp in out\n",
+                    "D1 in out d1n4148\n",
+                    ".ends\n",
+                    "X1 in out clamp\n",
+                    "D1 out 0 D1n4148\n",
+                ),
+                vec![],
+            )
+            .unwrap();
+            assert_eq!(
+                vec![
+                    ".title auto generated netlist file.",
+                    ".subckt clamp in out",
+                    "D1 in out d1n4148",
+                    ".ends",
+                    ".model D1N4148 D(is=2.52n rs=0.568 n=1.752)",
+                    "X1 in out clamp",
+                    "D1 out 0 D1n4148",
+                ],
+                circuit.to_str(false).unwrap()
+            );
+        }
+        #[test]
+        fn test_sections() {
+            let circuit = Circuit::parse(
+                concat!(
+                    ".lib tt\n",
+                    ".model NCH nmos (level=1 vto=0.7)\n",
+                    ".endl tt\n",
+                    ";\n",
+                    ".subckt amp in out params: r = 1k\n",
+                    "R1 in out {r}\n",
+                    ".ends\n",
+                    "X1 in out amp params: r = 2k\n",
+                ),
+                vec![],
+            )
+            .unwrap();
+            assert_eq!(
+                vec![
+                    ".title auto generated netlist file.",
+                    ".subckt amp in out params: r=1k",
+                    "R1 in out {r}",
+                    ".ends",
+                    "X1 in out amp params: r=2k",
+                ],
+                circuit.to_str(false).unwrap()
+            );
+            assert!(matches!(
+                Circuit::parse("title\n.lib tt\n.model NCH nmos\n", vec![]),
+                Err(Error::ParseError(2, _))
+            ));
+        }
+        #[test]
+        fn test_errors() {
+            assert!(matches!(
+                Circuit::parse("title\n.subckt amp in out\nR1 in out 1k\n", vec![]),
+                Err(Error::ParseError(2, _))
+            ));
+            assert!(matches!(
+                Circuit::parse("title\nZ1 in out 1k\n", vec![]),
+                Err(Error::ParseError(2, _))
+            ));
+            assert!(matches!(
+                Circuit::parse("title\nR1 in out\n", vec![]),
+                Err(Error::ParseError(2, _))
+            ));
+            assert!(matches!(
+                Circuit::parse("title\nR1 in out abc\n", vec![]),
+                Err(Error::InvalidValue(..))
+            ));
+            assert!(matches!(
+                Circuit::parse("title\nµ1 in out 1k\n", vec![]),
+                Err(Error::ParseError(2, _))
+            ));
+        }
+    }
+    mod xspice {
+        extern crate simulation;
+        use self::simulation::{Circuit, CodeModel, Error};
+        #[test]
+        fn test_mixed_signal() {
+            let mut circuit = Circuit::new(String::from("mixed"), vec![]);
+            circuit
+                .code_model(String::from("adc"), CodeModel::AdcBridge, vec![])
+                .unwrap();
+            circuit
+                .code_model(
+                    String::from("inv"),
+                    CodeModel::Inverter,
+                    vec![(String::from("rise_delay"), String::from("1n"))],
+                )
+                .unwrap();
+            circuit
+                .code_model(String::from("dac"), CodeModel::DacBridge, vec![])
+                .unwrap();
+            circuit
+                .bridge(
+                    String::from("A1"),
+                    vec![String::from("in")],
+                    vec![String::from("din")],
+                    String::from("adc"),
+                )
+                .unwrap();
+            circuit
+                .xspice(
+                    String::from("A2"),
+                    vec![String::from("din"), String::from("dout")],
+                    String::from("inv"),
+                )
+                .unwrap();
+            circuit
+                .bridge(
+                    String::from("A3"),
+                    vec![String::from("dout")],
+                    vec![String::from("out")],
+                    String::from("dac"),
+                )
+                .unwrap();
+            assert!(matches!(
+                circuit.bridge(
+                    String::from("A4"),
+                    vec![String::from("a"), String::from("b")],
+                    vec![String::from("c")],
+                    String::from("dac"),
+                ),
+                Err(Error::InvalidElement(..))
+            ));
+            assert!(circuit
+                .xspice(
+                    String::from("A4"),
+                    vec![String::from("[a b")],
+                    String::from("inv"),
+                )
+                .is_err());
+            assert_eq!(
+                vec![
+                    ".title auto generated netlist file.",
+                    ".model adc adc_bridge()",
+                    ".model inv d_inverter(rise_delay=1n)",
+                    ".model dac dac_bridge()",
+                    "A1 [in] [din] adc",
+                    "A2 din dout inv",
+                    "A3 [dout] [out] dac",
+                ],
+                circuit.to_str(false).unwrap()
+            );
+            assert_eq!(vec!["din", "dout"], circuit.digital_nodes());
+        }
+        #[test]
+        fn test_import() {
+            let circuit = Circuit::parse(
+                concat!(
+                    "gates\n",
+                    ".model nand1 d_nand(rise_delay=1n fall_delay=1n)\n",
+                    "A1 [ a b ] y nand1\n",
+                    "A2 [a] [an] adc\n",
+                    ".model adc adc_bridge(in_low=0.3 in_high=0.7)\n",
+                ),
+                vec![],
+            )
+            .unwrap();
+            let netlist = circuit.to_str(false).unwrap();
+            assert_eq!("A1 [ a b ] y nand1", netlist[3]);
+            assert_eq!("A2 [a] [an] adc", netlist[4]);
+            assert_eq!(vec!["a", "b", "y", "an"], circuit.digital_nodes());
+        }
+        #[test]
+        fn test_subcircuit_ports() {
+            let circuit = Circuit::parse(
+                concat!(
+                    "subcircuit\n",
+                    ".model adc adc_bridge(in_low=0.3 in_high=0.7)\n",
+                    ".subckt latch in q\n",
+                    ".model inv d_inverter(rise_delay=1n)\n",
+                    "A1 in d inv\n",
+                    "A2 d q inv\n",
+                    ".ends\n",
+                    "A1 %vd (inp inn) din adc\n",
+                    "X1 din out latch\n",
+                ),
+                vec![],
+            )
+            .unwrap();
+            let netlist = circuit.to_str(false).unwrap();
+            assert!(netlist.contains(&String::from("A1 %vd (inp inn) din adc")));
+            assert_eq!(vec!["din", "x1.d", "out"], circuit.digital_nodes());
+        }
+    }
+    mod simulation {
+        extern crate simulation;
+        use self::simulation::{Circuit, Error, Simulation};
+        fn circuit() -> Circuit {
+            let mut circuit = Circuit::parse(
+                concat!(
+                    "low pass\n",
+                    "V1 in 0 DC 0 AC 1\n",
+                    "R1 in out 4.7k\n",
+                    "C1 out 0 47n\n",
+                ),
+                vec![],
+            )
+            .unwrap();
+            circuit.control(String::from("tran 1u 10"));
+            circuit
+        }
+        #[test]
+        fn test_shared_while_background() {
+            let mut background = Simulation::new(circuit());
+            background.bg_run().unwrap();
+            let mut simulation = Simulation::new(circuit());
+            assert!(matches!(simulation.run(), Err(Error::NgSpiceError(_))));
+            assert!(matches!(simulation.op(), Err(Error::NgSpiceError(_))));
+            assert!(matches!(simulation.bg_run(), Err(Error::NgSpiceError(_))));
+            background.bg_halt();
+            let _ = background.bg_wait();
+        }
+    }
 }